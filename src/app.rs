@@ -1,7 +1,9 @@
+use std::cmp::Reverse;
 use std::collections::HashMap;
+use std::time::{Duration, Instant};
 
-use crate::terminal::TerminalBuffer;
-use crate::tmux::{Commands, TmuxConnection};
+use crate::terminal::{Palette, TerminalBuffer};
+use crate::tmux::{Commands, Placement, TmuxClient};
 use crate::ui::TabInfo;
 
 /// A single tab in helmux (corresponds to a tmux window)
@@ -16,6 +18,22 @@ pub struct Tab {
     pub buffer: TerminalBuffer,
     /// Whether there's unseen activity
     pub activity: bool,
+    /// Whether a bell rang in this tab (from tmux's window flags)
+    pub bell: bool,
+    /// Whether tmux names this window after its running command
+    pub auto_rename: bool,
+    /// Whether tmux's synchronize-panes is on for this window
+    pub synchronized: bool,
+    /// Whether the active pane's command has exited (kept by remain-on-exit)
+    pub dead: bool,
+    /// Foreground command in the active pane (e.g. "vim")
+    pub command: String,
+    /// Working directory of the active pane
+    pub path: String,
+    /// When this tab last stopped being the active tab
+    pub last_active: Option<Instant>,
+    /// When output last arrived in this tab
+    pub last_activity: Option<Instant>,
 }
 
 impl Tab {
@@ -26,8 +44,194 @@ impl Tab {
             name,
             buffer: TerminalBuffer::new(width, height),
             activity: false,
+            bell: false,
+            auto_rename: false,
+            synchronized: false,
+            dead: false,
+            command: String::new(),
+            path: String::new(),
+            last_active: None,
+            last_activity: None,
         }
     }
+
+    /// Whether output arriving at `now` should flag activity on this (background) tab
+    /// Output within `grace` of the tab losing focus is treated as trailing output, not activity
+    pub fn should_flag_activity(&self, now: Instant, grace: Duration) -> bool {
+        match self.last_active {
+            Some(left) => now.saturating_duration_since(left) >= grace,
+            None => true,
+        }
+    }
+}
+
+/// How long a status message stays visible
+const STATUS_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// How often the window list is re-read while there are background tabs, since
+/// tmux doesn't notify control clients when it sets a window's activity flag
+const ACTIVITY_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// A scrollback search on the active tab
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Search {
+    /// What was searched for
+    pub query: String,
+    /// Match positions as (history row, column), oldest first
+    pub matches: Vec<(usize, usize)>,
+    /// The match the view was last moved to
+    pub current: (usize, usize),
+}
+
+impl Search {
+    /// 1-based position of the current match among all matches
+    pub fn current_index(&self) -> usize {
+        self.matches.iter().position(|m| *m == self.current).map_or(0, |i| i + 1)
+    }
+
+    /// Move matches up after `count` lines were evicted from the front of the
+    /// scrollback, dropping those that went with them
+    fn shift_up(&mut self, count: usize) {
+        self.matches.retain(|m| m.0 >= count);
+        for m in &mut self.matches {
+            m.0 -= count;
+        }
+        self.current.0 = self.current.0.saturating_sub(count);
+    }
+}
+
+/// Running totals shown in the debug overlay
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DebugStats {
+    /// Pane output fed to the terminal emulator
+    pub bytes_parsed: u64,
+    /// tmux events handled
+    pub events_handled: u64,
+    /// Frames drawn
+    pub frames: u64,
+    /// Viewport cells drawn, over all frames
+    pub cells_drawn: u64,
+}
+
+/// What an outstanding tmux command was, so its response can be routed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CommandKind {
+    /// list-windows, response is the tab list
+    ListWindows,
+    /// capture-pane, response is the pane's current contents
+    CapturePane { pane_id: String },
+    /// show-options -v, response is the option's value
+    ShowOption { name: String },
+    /// display-message for a window's pane_dead, asked before restarting it
+    PaneDead { window_id: String },
+    /// display-message, response is shown to the user
+    DisplayMessage,
+    /// Anything else; non-empty output is shown to the user
+    Other,
+}
+
+/// One line of `list-windows` output (see `Commands::list_windows` for the format)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WindowListEntry {
+    pub window_id: String,
+    pub name: String,
+    pub active: bool,
+    pub pane_id: String,
+    /// tmux's window_activity_flag
+    pub activity: bool,
+    /// tmux's window_bell_flag
+    pub bell: bool,
+    /// The window's automatic-rename option
+    pub auto_rename: bool,
+    /// The window's synchronize-panes option
+    pub synchronized: bool,
+    /// tmux's pane_dead for the window's active pane
+    pub dead: bool,
+    /// Foreground command in the window's active pane (empty if not reported)
+    pub command: String,
+    /// Working directory of the window's active pane (empty if not reported)
+    pub path: String,
+}
+
+impl WindowListEntry {
+    /// Parse a list-windows line
+    /// The current format separates fields with ASCII unit separators; the older colon-separated
+    /// id:name:active:pane[:activity:bell] forms are still accepted, with the
+    /// missing fields defaulting to off/empty
+    /// Lines whose window or pane id isn't well formed are dropped, as the ids
+    /// become targets of later commands
+    pub fn parse(line: &str) -> Option<Self> {
+        let entry = if line.contains(Commands::FIELD_SEPARATOR) {
+            Self::parse_unit_separated(line)
+        } else {
+            Self::parse_colon_separated(line)
+        }?;
+        (is_id(&entry.window_id, '@') && is_id(&entry.pane_id, '%')).then_some(entry)
+    }
+
+    /// id, name, active, pane, activity, bell, automatic-rename, synchronize-panes,
+    /// pane dead, command, path separated by unit separators. A separator inside
+    /// the window name is kept as part of the name
+    fn parse_unit_separated(line: &str) -> Option<Self> {
+        let fields: Vec<&str> = line.split(Commands::FIELD_SEPARATOR).collect();
+        if fields.len() < 11 {
+            return None;
+        }
+        let trailing = &fields[fields.len() - 9..];
+        Some(Self {
+            window_id: fields[0].to_string(),
+            name: fields[1..fields.len() - 9].join(&Commands::FIELD_SEPARATOR.to_string()),
+            active: trailing[0] == "1",
+            pane_id: trailing[1].to_string(),
+            activity: trailing[2] == "1",
+            bell: trailing[3] == "1",
+            auto_rename: trailing[4] == "1",
+            synchronized: trailing[5] == "1",
+            dead: trailing[6] == "1",
+            command: trailing[7].to_string(),
+            path: trailing[8].to_string(),
+        })
+    }
+
+    /// Legacy colon-separated form
+    /// Window names may contain colons, so the name is whatever sits between the
+    /// leading window id and the known trailing fields
+    fn parse_colon_separated(line: &str) -> Option<Self> {
+        let (window_id, rest) = line.split_once(':')?;
+
+        // The 4-field form ends with the pane id; the 6-field one ends with the flags
+        let trailing = if rest.rsplit(':').next()?.starts_with('%') {
+            2
+        } else {
+            4
+        };
+        let mut fields: Vec<&str> = rest.rsplitn(trailing + 1, ':').collect();
+        if fields.len() != trailing + 1 {
+            return None;
+        }
+        fields.reverse();
+
+        let flag = |i: usize| fields.get(i).is_some_and(|v| *v == "1");
+        Some(Self {
+            window_id: window_id.to_string(),
+            name: fields[0].to_string(),
+            active: fields[1] == "1",
+            pane_id: fields[2].to_string(),
+            activity: flag(3),
+            bell: flag(4),
+            auto_rename: false,
+            synchronized: false,
+            dead: false,
+            command: String::new(),
+            path: String::new(),
+        })
+    }
+}
+
+/// Whether `id` is `sigil` followed by digits, as tmux window and pane ids are
+fn is_id(id: &str, sigil: char) -> bool {
+    id.strip_prefix(sigil)
+        .is_some_and(|digits| !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit()))
 }
 
 /// Application state
@@ -38,6 +242,50 @@ pub struct App {
     tab_order: Vec<String>,
     /// Currently active window ID
     active_window_id: Option<String>,
+    /// The window that was active before the current one, for LastTab
+    previous_window_id: Option<String>,
+    /// Marked window ID, the default target for swap/join operations
+    marked_window_id: Option<String>,
+    /// Scrollback search on the active tab, while one is open
+    search: Option<Search>,
+    /// Text waiting to be copied to the host terminal's clipboard
+    pending_clipboard: Option<String>,
+    /// A window whose restart was asked for, and whether tmux says its pane has exited
+    respawn_check: Option<(String, bool)>,
+    /// Outstanding tmux commands by command ID
+    pending_commands: HashMap<u64, CommandKind>,
+    /// A notification asked for a fresh window list that hasn't been sent yet
+    resync_pending: bool,
+    /// When the last window list arrived, to poll tmux's activity flags from it
+    window_list_at: Option<Instant>,
+    /// Message shown in the status line, and when it was set
+    status: Option<(String, Instant)>,
+    /// tmux's global mouse option, once known
+    tmux_mouse: Option<bool>,
+    /// tmux's global monitor-activity option from before helmux turned it on,
+    /// put back on exit
+    saved_monitor_activity: Option<bool>,
+    /// Typed input goes to every tab's pane, not just the active one
+    broadcast: bool,
+    /// The host terminal window has focus (assumed until it reports otherwise)
+    terminal_focused: bool,
+    /// Grace period after a tab loses focus before its output counts as activity
+    activity_grace: Duration,
+    /// Colors for ANSI indices 0-15 in new tabs, if configured
+    palette: Option<Palette>,
+    /// New tabs start in the active tab's working directory
+    new_tab_in_cwd: bool,
+    /// Open new tabs beside the active one rather than at the end
+    new_tab_placement: Option<Placement>,
+    /// List tabs by most recent output rather than in tmux's order
+    sort_by_activity: bool,
+    /// Moving a tab past either end of the list takes it to the other end
+    wrap_tab_moves: bool,
+    /// Characters besides letters and digits that double-click selects as part of a word
+    word_chars: String,
+    /// Counters for the debug overlay, and whether it's shown
+    stats: DebugStats,
+    debug_overlay: bool,
     /// Viewport dimensions
     viewport_width: u16,
     viewport_height: u16,
@@ -49,78 +297,368 @@ impl App {
             tabs: HashMap::new(),
             tab_order: Vec::new(),
             active_window_id: None,
+            previous_window_id: None,
+            marked_window_id: None,
+            search: None,
+            pending_clipboard: None,
+            respawn_check: None,
+            pending_commands: HashMap::new(),
+            resync_pending: false,
+            window_list_at: None,
+            status: None,
+            tmux_mouse: None,
+            saved_monitor_activity: None,
+            broadcast: false,
+            terminal_focused: true,
+            activity_grace: Duration::ZERO,
+            palette: None,
+            new_tab_in_cwd: false,
+            new_tab_placement: None,
+            sort_by_activity: false,
+            wrap_tab_moves: false,
+            word_chars: String::new(),
+            stats: DebugStats::default(),
+            debug_overlay: false,
             viewport_width,
             viewport_height,
         }
     }
 
-    /// Initialize tabs from tmux window list
-    pub async fn sync_from_tmux(&mut self, tmux: &mut TmuxConnection) -> anyhow::Result<()> {
+    /// Set the grace period before output on a just-left tab counts as activity
+    pub fn with_activity_grace(mut self, grace: Duration) -> Self {
+        self.activity_grace = grace;
+        self
+    }
+
+    /// Render ANSI colors 0-15 with the given palette in every tab's output
+    pub fn with_palette(mut self, palette: Option<Palette>) -> Self {
+        self.palette = palette;
+        self
+    }
+
+    /// Start new tabs in the active tab's working directory
+    pub fn with_new_tab_in_cwd(mut self, enabled: bool) -> Self {
+        self.new_tab_in_cwd = enabled;
+        self
+    }
+
+    /// Open new tabs beside the active one (None puts them at the end)
+    pub fn with_new_tab_placement(mut self, placement: Option<Placement>) -> Self {
+        self.new_tab_placement = placement;
+        self
+    }
+
+    pub fn new_tab_placement(&self) -> Option<Placement> {
+        self.new_tab_placement
+    }
+
+    /// List tabs by most recent output rather than in tmux's order
+    pub fn with_sort_by_activity(mut self, enabled: bool) -> Self {
+        self.sort_by_activity = enabled;
+        self
+    }
+
+    /// Let moving a tab past either end of the list take it to the other end
+    pub fn with_wrap_tab_moves(mut self, enabled: bool) -> Self {
+        self.wrap_tab_moves = enabled;
+        self
+    }
+
+    pub fn wraps_tab_moves(&self) -> bool {
+        self.wrap_tab_moves
+    }
+
+    /// Count these characters as part of a word for double-click selection
+    pub fn with_word_chars(mut self, chars: &str) -> Self {
+        self.word_chars = chars.to_string();
+        self
+    }
+
+    pub fn word_chars(&self) -> &str {
+        &self.word_chars
+    }
+
+    pub fn sorts_by_activity(&self) -> bool {
+        self.sort_by_activity
+    }
+
+    /// Switch between tmux's order and most-recent-output order, returning the new state
+    pub fn toggle_sort_by_activity(&mut self) -> bool {
+        self.sort_by_activity = !self.sort_by_activity;
+        self.sort_by_activity
+    }
+
+    /// Whether new tabs start in the active tab's working directory
+    pub fn new_tab_in_cwd(&self) -> bool {
+        self.new_tab_in_cwd
+    }
+
+    /// Change the active window, stamping the tab that loses focus
+    fn switch_active(&mut self, new_active: Option<String>) {
+        if new_active == self.active_window_id {
+            return;
+        }
+        if let Some(old) = self.active_window_id.take() {
+            if let Some(tab) = self.tabs.get_mut(&old) {
+                tab.last_active = Some(Instant::now());
+            }
+            self.previous_window_id = Some(old);
+        }
+        self.search = None;
+        self.active_window_id = new_active;
+    }
+
+    /// Request the tmux window list
+    pub async fn sync_from_tmux<T: TmuxClient>(&mut self, tmux: &mut T) -> anyhow::Result<()> {
         // Query current windows
-        tmux.send_command(&Commands::list_windows()).await?;
+        self.send_tracked(tmux, &Commands::list_windows(), CommandKind::ListWindows)
+            .await?;
         Ok(())
     }
 
+    /// Ask for a window list refresh after a tmux notification
+    /// Requests are coalesced: nothing is sent until `send_pending_resync`, and
+    /// none is needed while a list is in flight, since its response will
+    /// already include any change notified before it arrives
+    pub fn request_resync(&mut self) {
+        if !self.window_list_in_flight() {
+            self.resync_pending = true;
+        }
+    }
+
+    /// Whether a coalesced resync is waiting to be sent
+    pub fn resync_pending(&self) -> bool {
+        self.resync_pending
+    }
+
+    /// When the window list should next be re-read for activity flags: only
+    /// with background tabs, and not while a list is already on its way
+    pub fn activity_poll_deadline(&self) -> Option<Instant> {
+        if self.tab_count() < 2 || self.resync_pending || self.window_list_in_flight() {
+            return None;
+        }
+        self.window_list_at.map(|at| at + ACTIVITY_POLL_INTERVAL)
+    }
+
+    /// Ask for a fresh window list if the activity poll is due
+    pub fn poll_activity(&mut self, now: Instant) {
+        if self.activity_poll_deadline().is_some_and(|deadline| deadline <= now) {
+            self.request_resync();
+        }
+    }
+
+    /// Send the window list request collected by `request_resync`, if any
+    pub async fn send_pending_resync<T: TmuxClient>(&mut self, tmux: &mut T) -> anyhow::Result<()> {
+        if std::mem::take(&mut self.resync_pending) && !self.window_list_in_flight() {
+            self.sync_from_tmux(tmux).await?;
+        }
+        Ok(())
+    }
+
+    /// Send a command and remember what it was so its response can be routed
+    pub async fn send_tracked<T: TmuxClient>(
+        &mut self,
+        tmux: &mut T,
+        cmd: &str,
+        kind: CommandKind,
+    ) -> anyhow::Result<u64> {
+        let id = tmux.send_command(cmd).await?;
+        self.track_command(id, kind);
+        Ok(id)
+    }
+
+    /// Record what an outstanding command was
+    pub fn track_command(&mut self, id: u64, kind: CommandKind) {
+        self.pending_commands.insert(id, kind);
+    }
+
+    /// Resolve a response's command ID to what was sent
+    /// Consumes the entry, so each response is only routed once
+    pub fn take_command(&mut self, id: u64) -> Option<CommandKind> {
+        self.pending_commands.remove(&id)
+    }
+
+    /// Whether a list-windows request is still waiting for its response
+    /// Its response will reflect any window change notified before it arrives
+    pub fn window_list_in_flight(&self) -> bool {
+        self.pending_commands
+            .values()
+            .any(|kind| matches!(kind, CommandKind::ListWindows))
+    }
+
+    /// Queue text for the host terminal's clipboard
+    /// The main loop writes it out, keeping terminal I/O out of the app state
+    pub fn copy_to_clipboard(&mut self, text: String) {
+        self.pending_clipboard = Some(text);
+    }
+
+    /// Take the text waiting to be copied, if any
+    pub fn take_clipboard(&mut self) -> Option<String> {
+        self.pending_clipboard.take()
+    }
+
+    /// Record tmux's answer to a pane_dead query sent before restarting a window
+    pub fn resolve_pane_dead(&mut self, window_id: &str, dead: bool) {
+        if let Some(tab) = self.tabs.get_mut(window_id) {
+            tab.dead = dead;
+        }
+        self.respawn_check = Some((window_id.to_string(), dead));
+    }
+
+    /// Take the answered restart request, if any
+    pub fn take_respawn_check(&mut self) -> Option<(String, bool)> {
+        self.respawn_check.take()
+    }
+
+    /// Show a message in the status line
+    pub fn set_status(&mut self, message: impl Into<String>) {
+        self.status = Some((message.into(), Instant::now()));
+    }
+
+    /// Get the current status message, if it hasn't expired
+    pub fn status_message(&self) -> Option<&str> {
+        self.status
+            .as_ref()
+            .filter(|(_, set_at)| set_at.elapsed() < STATUS_TIMEOUT)
+            .map(|(msg, _)| msg.as_str())
+    }
+
+    /// When the current status message expires, if one is showing
+    pub fn status_deadline(&self) -> Option<Instant> {
+        self.status
+            .as_ref()
+            .map(|(_, set_at)| *set_at + STATUS_TIMEOUT)
+            .filter(|deadline| *deadline > Instant::now())
+    }
+
+    /// Record the value of a tmux option read back with show-options
+    pub fn apply_option(&mut self, name: &str, value: &str) {
+        match name {
+            "mouse" => self.tmux_mouse = Some(value.trim() == "on"),
+            "monitor-activity" => self.saved_monitor_activity = Some(value.trim() == "on"),
+            _ => {}
+        }
+    }
+
+    /// tmux's global monitor-activity option as it was before helmux connected, if known
+    pub fn saved_monitor_activity(&self) -> Option<bool> {
+        self.saved_monitor_activity
+    }
+
+    /// Record a focus change reported by the host terminal
+    pub fn set_terminal_focused(&mut self, focused: bool) {
+        self.terminal_focused = focused;
+    }
+
+    /// Whether the host terminal window has focus, so the cursor is drawn solid
+    pub fn terminal_focused(&self) -> bool {
+        self.terminal_focused
+    }
+
+    /// Get tmux's global mouse option, if known
+    pub fn tmux_mouse(&self) -> Option<bool> {
+        self.tmux_mouse
+    }
+
+    /// Flip the tracked mouse option and return the new value
+    /// Unknown state is treated as off, so the first toggle turns it on
+    pub fn toggle_tmux_mouse(&mut self) -> bool {
+        let on = !self.tmux_mouse.unwrap_or(false);
+        self.tmux_mouse = Some(on);
+        on
+    }
+
+    /// Seed a pane's buffer from capture-pane output
+    pub fn seed_pane(&mut self, pane_id: &str, contents: &str) {
+        if let Some(tab) = self.tab_by_pane_mut(pane_id) {
+            // capture-pane separates lines with bare newlines
+            let data = contents.replace('\n', "\r\n");
+            tab.buffer.process(data.as_bytes());
+        }
+    }
+
     /// Process list-windows response data
     /// This preserves existing tab buffers when updating
     pub fn process_window_list(&mut self, data: &str) {
-        // Format: @window_id:name:active:pane_id per line
         let mut new_order = Vec::new();
         let mut seen_windows = std::collections::HashSet::new();
         let mut new_active = None;
+        let now = Instant::now();
+        let grace = self.activity_grace;
+        self.window_list_at = Some(now);
+        // The active tab's command has just exited
+        let mut exited = false;
+
+        for entry in data.lines().filter_map(WindowListEntry::parse) {
+            let window_id = entry.window_id;
 
-        for line in data.lines() {
-            let parts: Vec<&str> = line.split(':').collect();
-            if parts.len() >= 4 {
-                let window_id = parts[0].to_string();
-                let name = parts[1].to_string();
-                let is_active = parts[2] == "1";
-                let pane_id = parts[3].to_string();
-
-                seen_windows.insert(window_id.clone());
-                new_order.push(window_id.clone());
-
-                if is_active {
-                    new_active = Some(window_id.clone());
-                }
-
-                // Update existing tab or create new one
-                if let Some(tab) = self.tabs.get_mut(&window_id) {
-                    // Preserve buffer, update metadata
-                    tab.name = name;
-                    tab.pane_id = pane_id;
-                } else {
-                    // Create new tab
-                    let tab = Tab::new(
-                        window_id.clone(),
-                        pane_id,
-                        name,
-                        self.viewport_width,
-                        self.viewport_height,
-                    );
-                    self.tabs.insert(window_id, tab);
-                }
+            seen_windows.insert(window_id.clone());
+            new_order.push(window_id.clone());
+
+            if entry.active {
+                new_active = Some(window_id.clone());
+            }
+
+            // Update existing tab or create new one
+            let tab = self.tabs.entry(window_id.clone()).or_insert_with(|| {
+                let mut tab = Tab::new(
+                    window_id,
+                    entry.pane_id.clone(),
+                    entry.name.clone(),
+                    self.viewport_width,
+                    self.viewport_height,
+                );
+                tab.buffer.set_palette(self.palette);
+                tab
+            });
+            // Preserve buffer, update metadata
+            tab.name = entry.name;
+            tab.pane_id = entry.pane_id;
+            // tmux's flags only ever add to locally detected activity, and its
+            // activity flag is subject to the same grace period as local output,
+            // so leaving a busy tab doesn't flag it straight away. The active
+            // tab never shows either
+            let tmux_activity = entry.activity && tab.should_flag_activity(now, grace);
+            tab.activity = !entry.active && (tab.activity || tmux_activity);
+            tab.bell = !entry.active && entry.bell;
+            tab.auto_rename = entry.auto_rename;
+            tab.synchronized = entry.synchronized;
+            if entry.active && entry.dead && !tab.dead {
+                exited = true;
             }
+            tab.dead = entry.dead;
+            tab.command = entry.command;
+            tab.path = entry.path;
         }
 
         // Remove tabs that are no longer in the list
         self.tabs.retain(|id, _| seen_windows.contains(id));
+        if let Some(marked) = &self.marked_window_id {
+            if !self.tabs.contains_key(marked) {
+                self.marked_window_id = None;
+            }
+        }
 
         // Update order and active window
         self.tab_order = new_order;
-        self.active_window_id = new_active;
+        self.switch_active(new_active);
+
+        if exited {
+            self.set_status("Command exited; Ctrl-b R restarts it");
+        }
     }
 
     /// Add a new tab from tmux window-add event
     pub fn add_tab(&mut self, window_id: &str, pane_id: &str, name: &str) {
         if !self.tabs.contains_key(window_id) {
-            let tab = Tab::new(
+            let mut tab = Tab::new(
                 window_id.to_string(),
                 pane_id.to_string(),
                 name.to_string(),
                 self.viewport_width,
                 self.viewport_height,
             );
+            tab.buffer.set_palette(self.palette);
             self.tab_order.push(window_id.to_string());
             self.tabs.insert(window_id.to_string(), tab);
         }
@@ -135,6 +673,12 @@ impl App {
         if self.active_window_id.as_deref() == Some(window_id) {
             self.active_window_id = self.tab_order.first().cloned();
         }
+        if self.marked_window_id.as_deref() == Some(window_id) {
+            self.marked_window_id = None;
+        }
+        if self.previous_window_id.as_deref() == Some(window_id) {
+            self.previous_window_id = None;
+        }
     }
 
     /// Rename a tab
@@ -144,14 +688,170 @@ impl App {
         }
     }
 
+    /// Record a window's automatic-rename option after changing it
+    pub fn set_auto_rename(&mut self, window_id: &str, on: bool) {
+        if let Some(tab) = self.tabs.get_mut(window_id) {
+            tab.auto_rename = on;
+        }
+    }
+
+    /// Record a window's synchronize-panes option after changing it
+    pub fn set_synchronized(&mut self, window_id: &str, on: bool) {
+        if let Some(tab) = self.tabs.get_mut(window_id) {
+            tab.synchronized = on;
+        }
+    }
+
     /// Set the active tab by window ID
     pub fn set_active(&mut self, window_id: &str) {
         if self.tabs.contains_key(window_id) {
             // Clear activity on the newly active tab
             if let Some(tab) = self.tabs.get_mut(window_id) {
                 tab.activity = false;
+                tab.bell = false;
             }
-            self.active_window_id = Some(window_id.to_string());
+            self.switch_active(Some(window_id.to_string()));
+        }
+    }
+
+    /// Search the active tab's scrollback and screen, moving the view to the
+    /// most recent match. Returns false (and closes any search) if nothing matched
+    pub fn start_search(&mut self, query: &str) -> bool {
+        self.search = None;
+        let Some(tab) = self.active_tab_mut() else {
+            return false;
+        };
+        let matches = tab.buffer.search(query);
+        let Some(&current) = matches.last() else {
+            return false;
+        };
+        tab.buffer.scroll_to_history_row(current.0);
+        self.search = Some(Search {
+            query: query.to_string(),
+            matches,
+            current,
+        });
+        true
+    }
+
+    /// Move to the next match further back in history (`older`) or towards the
+    /// live screen. Matches are recomputed first, as output may have arrived.
+    /// Returns false if there's no match in that direction
+    pub fn step_search(&mut self, older: bool) -> bool {
+        let Some(mut search) = self.search.take() else {
+            return false;
+        };
+        let Some(tab) = self.active_tab_mut() else {
+            return false;
+        };
+        search.matches = tab.buffer.search(&search.query);
+        let next = if older {
+            search.matches.iter().rev().find(|m| **m < search.current)
+        } else {
+            search.matches.iter().find(|m| **m > search.current)
+        };
+        let moved = match next.copied() {
+            Some(next) => {
+                tab.buffer.scroll_to_history_row(next.0);
+                search.current = next;
+                true
+            }
+            None => false,
+        };
+        self.search = Some(search);
+        moved
+    }
+
+    /// Scroll the active tab to the previous (`older`) or next shell prompt, counting
+    /// from the top of the view. Returns false if there's no prompt that way
+    pub fn step_prompt(&mut self, older: bool) -> bool {
+        let Some(tab) = self.active_tab_mut() else {
+            return false;
+        };
+        let from_row = tab.buffer.history_row(0);
+        let row = if older {
+            tab.buffer.prev_prompt(from_row)
+        } else {
+            tab.buffer.next_prompt(from_row)
+        };
+        row.is_some()
+    }
+
+    /// Get the open scrollback search, if any
+    pub fn search(&self) -> Option<&Search> {
+        self.search.as_ref()
+    }
+
+    /// Close the scrollback search
+    pub fn clear_search(&mut self) {
+        self.search = None;
+    }
+
+    /// Toggle the mark on the active tab
+    /// Marking a tab moves the mark from any previously marked tab
+    pub fn toggle_mark(&mut self) {
+        let Some(active) = self.active_window_id.clone() else {
+            return;
+        };
+        if self.marked_window_id.as_ref() == Some(&active) {
+            self.marked_window_id = None;
+        } else {
+            self.marked_window_id = Some(active);
+        }
+    }
+
+    /// Clear the marked tab
+    pub fn clear_mark(&mut self) {
+        self.marked_window_id = None;
+    }
+
+    /// Get the marked window ID
+    pub fn marked_window_id(&self) -> Option<&str> {
+        self.marked_window_id.as_deref()
+    }
+
+    /// Get the pane ID of the marked tab, if it isn't the active tab
+    pub fn marked_pane_id(&self) -> Option<&str> {
+        let marked = self.marked_window_id.as_deref()?;
+        if self.active_window_id.as_deref() == Some(marked) {
+            return None;
+        }
+        self.tabs.get(marked).map(|t| t.pane_id.as_str())
+    }
+
+    /// Find the first tab (in display order) matching a find-window query
+    /// Names are matched first, case-insensitively by substring; failing that,
+    /// each tab's screen and scrollback are searched
+    pub fn find_window(&self, query: &str) -> Option<&str> {
+        let needle = query.to_lowercase();
+        if needle.is_empty() {
+            return None;
+        }
+        let tabs = || self.tab_order.iter().filter_map(|id| self.tabs.get(id));
+        tabs()
+            .find(|tab| tab.name.to_lowercase().contains(&needle))
+            .or_else(|| tabs().find(|tab| tab.buffer.contains_text(&needle)))
+            .map(|tab| tab.window_id.as_str())
+    }
+
+    /// Get the window to return to with LastTab: the previously active one,
+    /// or the next tab if that's gone
+    pub fn last_window_id(&self) -> Option<&str> {
+        let active = self.active_window_id.as_deref();
+        self.previous_window_id
+            .as_deref()
+            .filter(|id| self.tabs.contains_key(*id) && active != Some(*id))
+            .or_else(|| self.next_window_id().filter(|id| active != Some(*id)))
+    }
+
+    /// Get the target window for swap/move operations
+    /// Uses the marked tab when set (and not the active one), otherwise the next tab
+    pub fn swap_target(&self) -> Option<&str> {
+        match self.marked_window_id.as_deref() {
+            Some(marked) if self.active_window_id.as_deref() != Some(marked) => Some(marked),
+            _ => self
+                .next_window_id()
+                .filter(|id| self.active_window_id.as_deref() != Some(*id)),
         }
     }
 
@@ -169,11 +869,67 @@ impl App {
             .and_then(|id| self.tabs.get_mut(id))
     }
 
+    /// Get the active tab's visible screen as plain text (see `TerminalBuffer::to_text`)
+    pub fn active_screen_text(&self) -> Option<String> {
+        self.active_tab().map(|tab| tab.buffer.to_text())
+    }
+
     /// Get the active pane ID
     pub fn active_pane_id(&self) -> Option<&str> {
         self.active_tab().map(|t| t.pane_id.as_str())
     }
 
+    /// Whether typed input is broadcast to every tab
+    pub fn is_broadcasting(&self) -> bool {
+        self.broadcast
+    }
+
+    /// Whether the debug overlay is shown
+    pub fn is_debug_overlay_shown(&self) -> bool {
+        self.debug_overlay
+    }
+
+    /// Show or hide the debug overlay, returning the new state
+    pub fn toggle_debug_overlay(&mut self) -> bool {
+        self.debug_overlay = !self.debug_overlay;
+        self.debug_overlay
+    }
+
+    pub fn stats(&self) -> DebugStats {
+        self.stats
+    }
+
+    /// Count tmux events handled
+    pub fn record_events(&mut self, count: usize) {
+        self.stats.events_handled += count as u64;
+    }
+
+    /// Count a drawn frame and the viewport cells in it
+    pub fn record_frame(&mut self, cells: u64) {
+        self.stats.frames += 1;
+        self.stats.cells_drawn += cells;
+    }
+
+    /// Turn input broadcasting on or off, returning the new state
+    pub fn toggle_broadcast(&mut self) -> bool {
+        self.broadcast = !self.broadcast;
+        self.broadcast
+    }
+
+    /// Panes that typed input should go to: the active pane, or every tab's
+    /// pane (in tab order) while broadcasting
+    pub fn input_pane_ids(&self) -> Vec<String> {
+        if self.broadcast {
+            self.tab_order
+                .iter()
+                .filter_map(|id| self.tabs.get(id))
+                .map(|tab| tab.pane_id.clone())
+                .collect()
+        } else {
+            self.active_pane_id().map(str::to_string).into_iter().collect()
+        }
+    }
+
     /// Get the active window ID
     pub fn active_window_id(&self) -> Option<&str> {
         self.active_window_id.as_deref()
@@ -196,19 +952,34 @@ impl App {
     pub fn process_output(&mut self, pane_id: &str, data: &[u8]) {
         // Check if this is the active pane
         let is_active = self.active_pane_id() == Some(pane_id);
+        let grace = self.activity_grace;
+        let mut evicted = 0;
 
         if let Some(tab) = self.tab_by_pane_mut(pane_id) {
+            let evicted_before = tab.buffer.evicted_lines();
             tab.buffer.process(data);
-            // Mark activity if not active tab
-            if !is_active {
+            evicted = tab.buffer.evicted_lines() - evicted_before;
+            let now = Instant::now();
+            tab.last_activity = Some(now);
+            // Mark activity if not active tab (and not just trailing output after leaving it)
+            if !is_active && tab.should_flag_activity(now, grace) {
                 tab.activity = true;
             }
+            self.stats.bytes_parsed += data.len() as u64;
+        }
+
+        // Keep an open search's rows on the lines they matched
+        if let Some(search) = self.search.as_mut().filter(|_| is_active && evicted > 0) {
+            search.shift_up(evicted);
         }
     }
 
-    /// Get tab info for the sidebar
+    /// Get tab info for the sidebar, in display order
+    /// Each tab keeps its number from tmux's order even when sorted by
+    /// activity, so the number keys always pick the tab showing that number
     pub fn tab_infos(&self) -> Vec<TabInfo> {
-        self.tab_order
+        let mut infos: Vec<TabInfo> = self
+            .tab_order
             .iter()
             .enumerate()
             .filter_map(|(idx, window_id)| {
@@ -217,10 +988,23 @@ impl App {
                     name: tab.name.clone(),
                     active: self.active_window_id.as_ref() == Some(window_id),
                     activity: tab.activity,
+                    bell: tab.bell,
+                    auto_rename: tab.auto_rename,
+                    synchronized: tab.synchronized,
+                    dead: tab.dead,
+                    command: tab.command.clone(),
+                    path: tab.path.clone(),
+                    marked: self.marked_window_id.as_ref() == Some(window_id),
+                    last_activity: tab.last_activity,
                     index: idx + 1,
                 })
             })
-            .collect()
+            .collect();
+        if self.sort_by_activity {
+            // Stable, so tabs without output yet stay in tmux's order at the end
+            infos.sort_by_key(|info| Reverse(info.last_activity));
+        }
+        infos
     }
 
     /// Get the number of tabs
@@ -252,12 +1036,40 @@ impl App {
         self.tab_order.get(prev_idx).map(|s| s.as_str())
     }
 
-    /// Get window ID by index (1-based, for Ctrl-b 1-9)
-    pub fn window_id_by_index(&self, index: usize) -> Option<&str> {
-        if index == 0 || index > self.tab_order.len() {
+    /// The tab next to the active one in tmux's order, to the right or left,
+    /// and whether reaching it wraps around the end of the list
+    pub fn neighbor_window_id(&self, right: bool) -> Option<(&str, bool)> {
+        let current_idx = self
+            .active_window_id
+            .as_ref()
+            .and_then(|id| self.tab_order.iter().position(|x| x == id))?;
+        if self.tab_order.len() < 2 {
             return None;
         }
-        self.tab_order.get(index - 1).map(|s| s.as_str())
+        let last = self.tab_order.len() - 1;
+        let (idx, wrapped) = match (right, current_idx) {
+            (true, i) if i == last => (0, true),
+            (true, i) => (i + 1, false),
+            (false, 0) => (last, true),
+            (false, i) => (i - 1, false),
+        };
+        Some((self.tab_order[idx].as_str(), wrapped))
+    }
+
+    /// Every tab's window ID, in tmux's order
+    pub fn window_ids(&self) -> &[String] {
+        &self.tab_order
+    }
+
+    /// Get window ID by the number shown beside the tab, 0-based (its
+    /// position in tmux's order)
+    pub fn window_id_by_number(&self, number: usize) -> Option<&str> {
+        self.tab_order.get(number).map(|s| s.as_str())
+    }
+
+    /// The 0-based number of the tab at a display position (top of the sidebar is 0)
+    pub fn number_at_display_position(&self, pos: usize) -> Option<usize> {
+        self.tab_infos().get(pos).map(|info| info.index - 1)
     }
 
     /// Resize all tab buffers
@@ -274,3 +1086,498 @@ impl App {
         !self.tabs.is_empty()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn app_with_tabs() -> App {
+        let mut app = App::new(80, 24);
+        app.process_window_list("@1:one:1:%1\n@2:two:0:%2\n@3:three:0:%3");
+        app
+    }
+
+    #[test]
+    fn test_parse_window_list_entry_with_flags() {
+        let entry = WindowListEntry::parse("@2:build:0:%5:1:1").unwrap();
+        assert_eq!(entry.window_id, "@2");
+        assert_eq!(entry.name, "build");
+        assert!(!entry.active);
+        assert_eq!(entry.pane_id, "%5");
+        assert!(entry.activity);
+        assert!(entry.bell);
+    }
+
+    #[test]
+    fn test_parse_window_list_entry_legacy_form() {
+        let entry = WindowListEntry::parse("@1:shell:1:%1").unwrap();
+        assert_eq!(entry.pane_id, "%1");
+        assert!(entry.active);
+        assert!(!entry.activity);
+        assert!(!entry.bell);
+
+        assert_eq!(WindowListEntry::parse("@1:shell:1"), None);
+    }
+
+    #[test]
+    fn test_parse_window_list_entry_rejects_bad_ids() {
+        assert_eq!(WindowListEntry::parse("@1;kill-server:shell:1:%1"), None);
+        assert_eq!(WindowListEntry::parse("1:shell:1:%1"), None);
+        assert_eq!(WindowListEntry::parse("@1:shell:1:%x:0:0"), None);
+
+        let mut app = App::new(80, 24);
+        app.process_window_list("@1:one:1:%1\n@ 2:two:0:%2\n@3:three:0:%3");
+        assert_eq!(app.tab_count(), 2);
+        assert_eq!(app.window_id_by_number(1), Some("@3"));
+    }
+
+    #[test]
+    fn test_parse_window_list_entry_with_colons_in_name() {
+        let entry = WindowListEntry::parse("@3:vim: src/main.rs:1:%7:0:1").unwrap();
+        assert_eq!(entry.window_id, "@3");
+        assert_eq!(entry.name, "vim: src/main.rs");
+        assert!(entry.active);
+        assert_eq!(entry.pane_id, "%7");
+        assert!(!entry.activity);
+        assert!(entry.bell);
+
+        let legacy = WindowListEntry::parse("@3:a:b:0:%7").unwrap();
+        assert_eq!(legacy.name, "a:b");
+        assert!(!legacy.active);
+        assert_eq!(legacy.pane_id, "%7");
+    }
+
+    #[test]
+    fn test_parse_window_list_entry_unit_separated() {
+        let entry =
+            WindowListEntry::parse("@4\x1fvim: a:b\x1f0\x1f%9\x1f1\x1f0\x1f1\x1f1\x1f0\x1fcargo\x1f/home/me/src:x")
+                .unwrap();
+        assert_eq!(entry.window_id, "@4");
+        assert_eq!(entry.name, "vim: a:b");
+        assert!(!entry.active);
+        assert_eq!(entry.pane_id, "%9");
+        assert!(entry.activity);
+        assert!(!entry.bell);
+        assert!(entry.auto_rename);
+        assert!(entry.synchronized);
+        assert!(!entry.dead);
+        assert_eq!(entry.command, "cargo");
+        assert_eq!(entry.path, "/home/me/src:x");
+
+        // Too few fields
+        assert_eq!(WindowListEntry::parse("@4\x1fname\x1f0"), None);
+    }
+
+    #[test]
+    fn test_window_list_path_reaches_tab_infos() {
+        let mut app = App::new(80, 24);
+        app.process_window_list("@1\x1fone\x1f1\x1f%1\x1f0\x1f0\x1f0\x1f0\x1f0\x1fvim\x1f/srv/app");
+        assert!(!app.tab_infos()[0].synchronized);
+        assert_eq!(app.tab_infos()[0].command, "vim");
+        assert_eq!(app.tab_infos()[0].path, "/srv/app");
+    }
+
+    #[test]
+    fn test_window_list_flags_reach_tab_infos() {
+        let mut app = App::new(80, 24);
+        app.process_window_list("@1:one:1:%1:1:1\n@2:two:0:%2:1:0\n@3:three:0:%3:0:1");
+        let infos = app.tab_infos();
+        // The active tab never shows activity or bell
+        assert!(!infos[0].activity && !infos[0].bell);
+        assert!(infos[1].activity && !infos[1].bell);
+        assert!(!infos[2].activity && infos[2].bell);
+    }
+
+    #[test]
+    fn test_search_steps_through_matches() {
+        let mut app = app_with_tabs();
+        app.process_output("%1", b"match A\r\n");
+        for _ in 0..30 {
+            app.process_output("%1", b"filler\r\n");
+        }
+        app.process_output("%1", b"match B\r\nmatch C\r\n");
+
+        assert!(app.start_search("MATCH"));
+        let search = app.search().unwrap();
+        assert_eq!(search.matches.len(), 3);
+        assert_eq!(search.current_index(), 3);
+
+        // Back to the oldest match, which is in scrollback
+        assert!(app.step_search(true));
+        assert!(app.step_search(true));
+        assert_eq!(app.search().unwrap().current_index(), 1);
+        assert!(app.active_tab().unwrap().buffer.is_scrolled());
+        assert!(!app.step_search(true));
+
+        // And forward again
+        assert!(app.step_search(false));
+        assert_eq!(app.search().unwrap().current_index(), 2);
+
+        // Switching tabs closes the search
+        app.set_active("@2");
+        assert!(app.search().is_none());
+        assert!(!app.start_search("match"));
+    }
+
+    #[test]
+    fn test_search_rows_follow_scrollback_eviction() {
+        let mut app = app_with_tabs();
+        app.process_output("%1", b"match A\r\n");
+        assert!(app.start_search("match"));
+        let (row, col) = app.search().unwrap().current;
+
+        // Fill the scrollback past its limit so its oldest lines are dropped
+        for _ in 0..1100 {
+            app.process_output("%1", b"filler\r\n");
+        }
+        let evicted = app.active_tab().unwrap().buffer.evicted_lines();
+        assert!(evicted > row);
+        assert!(app.search().unwrap().matches.is_empty());
+
+        app.process_output("%1", b"match B\r\n");
+        assert!(app.step_search(false));
+        let search = app.search().unwrap();
+        let buffer = &app.active_tab().unwrap().buffer;
+        assert_eq!(search.current.1, col);
+        assert_eq!(buffer.history_text(search.current.0..search.current.0 + 1), "match B");
+    }
+
+    #[test]
+    fn test_find_window_by_name() {
+        let app = app_with_tabs();
+        assert_eq!(app.find_window("TWO"), Some("@2"));
+        assert_eq!(app.find_window("hre"), Some("@3"));
+        // The first match in display order wins
+        assert_eq!(app.find_window("o"), Some("@1"));
+        assert_eq!(app.find_window("nope"), None);
+    }
+
+    #[test]
+    fn test_find_window_by_content_in_scrollback() {
+        let mut app = app_with_tabs();
+        app.process_output("%3", b"cargo test: ok\r\n");
+        for _ in 0..30 {
+            app.process_output("%3", b"more\r\n");
+        }
+        assert!(app.tabs["@3"].buffer.scrollback_len() > 0);
+        assert_eq!(app.find_window("Cargo Test"), Some("@3"));
+
+        // A name match beats an earlier tab's content
+        app.process_output("%1", b"two\r\n");
+        assert_eq!(app.find_window("two"), Some("@2"));
+    }
+
+    #[test]
+    fn test_last_window_toggles_between_recent_tabs() {
+        let mut app = app_with_tabs();
+        app.set_active("@2");
+        app.set_active("@3");
+        assert_eq!(app.last_window_id(), Some("@2"));
+
+        app.set_active("@2");
+        assert_eq!(app.last_window_id(), Some("@3"));
+    }
+
+    #[test]
+    fn test_last_window_falls_back_to_next_when_closed() {
+        let mut app = app_with_tabs();
+        app.set_active("@3");
+        app.set_active("@2");
+        app.remove_tab("@3");
+        assert_eq!(app.last_window_id(), Some("@1"));
+
+        // A single tab has nowhere to go
+        app.remove_tab("@1");
+        assert_eq!(app.last_window_id(), None);
+    }
+
+    #[test]
+    fn test_activity_grace_period() {
+        let mut tab = Tab::new("@1".into(), "%1".into(), "one".into(), 80, 24);
+        let grace = Duration::from_millis(500);
+        let left = Instant::now();
+
+        // Never been active: any output is activity
+        assert!(tab.should_flag_activity(left, grace));
+
+        tab.last_active = Some(left);
+        assert!(!tab.should_flag_activity(left, grace));
+        assert!(!tab.should_flag_activity(left + Duration::from_millis(499), grace));
+        assert!(tab.should_flag_activity(left + Duration::from_millis(500), grace));
+
+        // Zero grace disables the check
+        assert!(tab.should_flag_activity(left, Duration::ZERO));
+    }
+
+    #[test]
+    fn test_tmux_activity_flag_respects_grace_period() {
+        let mut app = App::new(80, 24).with_activity_grace(Duration::from_secs(60));
+        app.process_window_list("@1:one:1:%1:0:0\n@2:two:0:%2:1:0");
+        // Never visited: tmux's flag counts
+        assert!(app.tabs["@2"].activity);
+
+        // Just left: tmux flagging the output that followed doesn't
+        app.set_active("@2");
+        app.process_window_list("@1:one:0:%1:1:0\n@2:two:1:%2:0:0");
+        assert!(!app.tabs["@1"].activity);
+        assert!(!app.tabs["@2"].activity);
+    }
+
+    #[test]
+    fn test_output_after_switching_away_is_not_activity() {
+        let mut app = app_with_tabs().with_activity_grace(Duration::from_secs(60));
+        app.process_output("%3", b"never active");
+        assert!(app.tabs["@3"].activity);
+
+        app.set_active("@2");
+        assert!(app.tabs["@1"].last_active.is_some());
+        app.process_output("%1", b"trailing output");
+        assert!(!app.tabs["@1"].activity);
+    }
+
+    #[test]
+    fn test_command_registry() {
+        let mut app = App::new(80, 24);
+        app.track_command(1, CommandKind::ListWindows);
+        app.track_command(2, CommandKind::CapturePane { pane_id: "%1".into() });
+
+        assert_eq!(app.take_command(2), Some(CommandKind::CapturePane { pane_id: "%1".into() }));
+        assert_eq!(app.take_command(1), Some(CommandKind::ListWindows));
+        // Resolved entries are gone, unknown IDs resolve to nothing
+        assert_eq!(app.take_command(1), None);
+        assert_eq!(app.take_command(99), None);
+    }
+
+    #[test]
+    fn test_tmux_mouse_tracking() {
+        let mut app = App::new(80, 24);
+        assert_eq!(app.tmux_mouse(), None);
+
+        assert!(app.toggle_tmux_mouse());
+        assert!(!app.toggle_tmux_mouse());
+
+        app.apply_option("mouse", "on\n");
+        assert_eq!(app.tmux_mouse(), Some(true));
+        app.apply_option("status", "off");
+        assert_eq!(app.tmux_mouse(), Some(true));
+    }
+
+    #[test]
+    fn test_terminal_focus() {
+        let mut app = App::new(80, 24);
+        assert!(app.terminal_focused());
+        app.set_terminal_focused(false);
+        assert!(!app.terminal_focused());
+    }
+
+    #[test]
+    fn test_saved_monitor_activity() {
+        let mut app = App::new(80, 24);
+        assert_eq!(app.saved_monitor_activity(), None);
+        app.apply_option("monitor-activity", "off\n");
+        assert_eq!(app.saved_monitor_activity(), Some(false));
+    }
+
+    #[test]
+    fn test_activity_poll_rereads_window_list() {
+        let mut app = App::new(80, 24);
+        app.process_window_list("@1:one:1:%1");
+        // A lone tab has no background tabs to watch
+        assert_eq!(app.activity_poll_deadline(), None);
+
+        app.process_window_list("@1:one:1:%1\n@2:two:0:%2");
+        let deadline = app.activity_poll_deadline().unwrap();
+        app.poll_activity(deadline - Duration::from_millis(1));
+        assert!(!app.resync_pending());
+        app.poll_activity(deadline);
+        assert!(app.resync_pending());
+        // Nothing to wait for until that list comes back
+        assert_eq!(app.activity_poll_deadline(), None);
+    }
+
+    #[test]
+    fn test_seed_pane() {
+        let mut app = app_with_tabs();
+        app.seed_pane("%2", "first\nsecond");
+        let buffer = &app.tabs["@2"].buffer;
+        assert_eq!(buffer.get_cell(0, 0).unwrap().character, 'f');
+        assert_eq!(buffer.get_cell(1, 0).unwrap().character, 's');
+    }
+
+    #[test]
+    fn test_debug_stats_count_output_events_and_frames() {
+        let mut app = app_with_tabs();
+        assert_eq!(app.stats(), DebugStats::default());
+        app.process_output("%1", b"hello");
+        app.process_output("%2", b"\x1b[31mred");
+        // Output for a pane helmux doesn't know isn't parsed
+        app.process_output("%9", b"lost");
+        app.record_events(3);
+        app.record_frame(80 * 24);
+        app.record_frame(80 * 24);
+        assert_eq!(
+            app.stats(),
+            DebugStats { bytes_parsed: 13, events_handled: 3, frames: 2, cells_drawn: 2 * 80 * 24 }
+        );
+    }
+
+    #[test]
+    fn test_status_message() {
+        let mut app = App::new(80, 24);
+        assert_eq!(app.status_message(), None);
+        app.set_status("hello");
+        assert_eq!(app.status_message(), Some("hello"));
+    }
+
+    #[test]
+    fn test_window_id_by_number() {
+        let app = app_with_tabs();
+        assert_eq!(app.window_id_by_number(0), Some("@1"));
+        assert_eq!(app.window_id_by_number(2), Some("@3"));
+        assert_eq!(app.window_id_by_number(3), None);
+        assert_eq!(app.number_at_display_position(1), Some(1));
+    }
+
+    #[test]
+    fn test_sort_by_activity() {
+        let mut app = app_with_tabs();
+        app.process_window_list("@1:one:1:%1\n@2:two:0:%2\n@3:three:0:%3\n@4:four:0:%4");
+        let now = Instant::now();
+        app.tabs.get_mut("@1").unwrap().last_activity = Some(now - Duration::from_secs(30));
+        app.tabs.get_mut("@3").unwrap().last_activity = Some(now - Duration::from_secs(10));
+        app.tabs.get_mut("@4").unwrap().last_activity = Some(now - Duration::from_secs(20));
+        let order = |app: &App| -> Vec<(String, usize)> {
+            app.tab_infos().into_iter().map(|info| (info.id, info.index)).collect()
+        };
+
+        // tmux's order until sorting is turned on
+        assert_eq!(order(&app)[0], ("@1".to_string(), 1));
+        assert!(app.toggle_sort_by_activity());
+
+        // Most recent first, a tab with no output last, numbers unchanged
+        assert_eq!(
+            order(&app),
+            vec![
+                ("@3".to_string(), 3),
+                ("@4".to_string(), 4),
+                ("@1".to_string(), 1),
+                ("@2".to_string(), 2),
+            ]
+        );
+        // Number keys still go by the shown number, clicks by position
+        assert_eq!(app.window_id_by_number(0), Some("@1"));
+        assert_eq!(app.number_at_display_position(0), Some(2));
+
+        // Output moves a tab to the top
+        app.process_output("%2", b"x");
+        assert_eq!(order(&app)[0], ("@2".to_string(), 2));
+    }
+
+    #[test]
+    fn test_toggle_mark() {
+        let mut app = app_with_tabs();
+        assert_eq!(app.marked_window_id(), None);
+
+        app.toggle_mark();
+        assert_eq!(app.marked_window_id(), Some("@1"));
+        assert!(app.tab_infos()[0].marked);
+
+        app.toggle_mark();
+        assert_eq!(app.marked_window_id(), None);
+    }
+
+    #[test]
+    fn test_mark_moves_to_new_active_tab() {
+        let mut app = app_with_tabs();
+        app.toggle_mark();
+        app.set_active("@3");
+        app.toggle_mark();
+        assert_eq!(app.marked_window_id(), Some("@3"));
+    }
+
+    #[test]
+    fn test_mark_cleared_when_tab_closes() {
+        let mut app = app_with_tabs();
+        app.set_active("@2");
+        app.toggle_mark();
+        app.remove_tab("@2");
+        assert_eq!(app.marked_window_id(), None);
+
+        app.set_active("@3");
+        app.toggle_mark();
+        app.process_window_list("@1:one:1:%1");
+        assert_eq!(app.marked_window_id(), None);
+    }
+
+    #[test]
+    fn test_marked_pane_id() {
+        let mut app = app_with_tabs();
+        app.toggle_mark();
+        // Marked tab is active - nothing to join
+        assert_eq!(app.marked_pane_id(), None);
+
+        app.set_active("@2");
+        assert_eq!(app.marked_pane_id(), Some("%1"));
+    }
+
+    #[test]
+    fn test_sidebar_numbers_are_contiguous() {
+        let mut app = App::new(80, 24);
+        // tmux's indices are 1, 3, 4 after windows 2 and 5 closed; the sidebar
+        // numbers tabs by position, so they stay 1, 2, 3 with or without move-window -r
+        app.process_window_list("@1:one:1:%1\n@3:three:0:%3\n@4:four:0:%4");
+        let numbers: Vec<usize> = app.tab_infos().iter().map(|info| info.index).collect();
+        assert_eq!(numbers, vec![1, 2, 3]);
+        assert_eq!(app.window_id_by_number(1), Some("@3"));
+    }
+
+    #[test]
+    fn test_neighbor_window_id() {
+        let mut app = app_with_tabs();
+        assert_eq!(app.neighbor_window_id(true), Some(("@2", false)));
+        assert_eq!(app.neighbor_window_id(false), Some(("@3", true)));
+        app.process_window_list("@1:one:0:%1\n@2:two:0:%2\n@3:three:1:%3");
+        assert_eq!(app.neighbor_window_id(true), Some(("@1", true)));
+        assert_eq!(app.neighbor_window_id(false), Some(("@2", false)));
+        app.process_window_list("@3:three:1:%3");
+        assert_eq!(app.neighbor_window_id(true), None);
+    }
+
+    #[test]
+    fn test_swap_target_prefers_marked() {
+        let mut app = app_with_tabs();
+        // No mark: falls back to the next tab
+        assert_eq!(app.swap_target(), Some("@2"));
+
+        app.set_active("@3");
+        app.toggle_mark();
+        app.set_active("@1");
+        assert_eq!(app.swap_target(), Some("@3"));
+
+        // Marked tab is the active one: fall back to the next tab
+        app.set_active("@3");
+        assert_eq!(app.swap_target(), Some("@1"));
+    }
+
+    #[test]
+    fn test_dead_pane_offers_respawn() {
+        let mut app = App::new(80, 24);
+        app.process_window_list("@1\x1fsh\x1f1\x1f%1\x1f0\x1f0\x1f1\x1f0\x1f0\x1fsh\x1f/");
+        assert!(!app.tab_infos()[0].dead);
+        assert_eq!(app.status_message(), None);
+
+        app.process_window_list("@1\x1fsh\x1f1\x1f%1\x1f0\x1f0\x1f1\x1f0\x1f1\x1fsh\x1f/");
+        assert!(app.tab_infos()[0].dead);
+        assert_eq!(app.status_message(), Some("Command exited; Ctrl-b R restarts it"));
+    }
+
+    #[test]
+    fn test_active_screen_text() {
+        let mut app = App::new(20, 4);
+        assert_eq!(app.active_screen_text(), None);
+        app.process_window_list("@1:one:0:%1\n@2:two:1:%2");
+        app.process_output("%1", b"background");
+        app.process_output("%2", b"$ echo hi\r\nhi\r\n$ ");
+        assert_eq!(app.active_screen_text().as_deref(), Some("$ echo hi\nhi\n$"));
+    }
+}
@@ -0,0 +1,29 @@
+use std::io::{self, Write};
+
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+
+/// OSC 52 sequence asking the host terminal to put `text` on the clipboard
+pub fn osc52(text: &str) -> String {
+    format!("\x1b]52;c;{}\x07", STANDARD.encode(text))
+}
+
+/// Copy `text` to the clipboard of the terminal helmux runs in
+/// OSC 52 works over SSH and needs no system clipboard access, though some
+/// terminals have it turned off
+pub fn copy(out: &mut impl Write, text: &str) -> io::Result<()> {
+    out.write_all(osc52(text).as_bytes())?;
+    out.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_osc52_sequence() {
+        let mut out = Vec::new();
+        copy(&mut out, "hi\n").unwrap();
+        assert_eq!(out, b"\x1b]52;c;aGkK\x07");
+        assert_eq!(osc52("f"), "\x1b]52;c;Zg==\x07");
+    }
+}
@@ -0,0 +1,289 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+use anyhow::Context;
+use serde::Deserialize;
+
+use crate::tmux::Placement;
+use crate::ui::Theme;
+
+/// Default grace period after a tab loses focus before output counts as activity
+const DEFAULT_ACTIVITY_GRACE_MS: u64 = 250;
+/// Default cap on redraws per second
+const DEFAULT_MAX_FPS: u32 = 60;
+/// Default longest gap between the clicks of a double or triple click
+const DEFAULT_DOUBLE_CLICK_MS: u64 = 400;
+/// Default characters that join a word for double-click selection, besides
+/// letters and digits, so paths and URLs select whole
+const DEFAULT_WORD_CHARS: &str = "-_./~:@%+#?&=";
+
+/// User configuration loaded from `~/.config/helmux/config.toml`
+/// Every field has a default, so a missing file or section is fine
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub tabs: TabsConfig,
+    pub sidebar: SidebarConfig,
+    pub display: DisplayConfig,
+    pub mouse: MouseConfig,
+    /// Colors for the sidebar and overlays
+    pub appearance: Theme,
+}
+
+/// `[tabs]` section
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct TabsConfig {
+    /// Ignore output for this long after a tab loses focus before flagging activity
+    /// Avoids flicker from trailing output of the tab you just left (0 disables)
+    pub activity_grace_ms: u64,
+    /// Ask for y/n before closing a tab with prefix x
+    pub confirm_kill: bool,
+    /// Start new tabs in the active tab's working directory rather than tmux's default
+    pub new_tab_in_cwd: bool,
+    /// Where new tabs go in the list
+    pub new_tab_position: NewTabPosition,
+    /// Moving a tab past either end of the list takes it to the other end
+    pub wrap_tab_moves: bool,
+}
+
+/// Where a new tab is opened
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NewTabPosition {
+    /// After the last tab, tmux's default
+    #[default]
+    End,
+    /// Right after the active tab
+    After,
+    /// Right before the active tab
+    Before,
+}
+
+impl Default for TabsConfig {
+    fn default() -> Self {
+        Self {
+            activity_grace_ms: DEFAULT_ACTIVITY_GRACE_MS,
+            confirm_kill: false,
+            new_tab_in_cwd: false,
+            new_tab_position: NewTabPosition::End,
+            wrap_tab_moves: false,
+        }
+    }
+}
+
+/// `[sidebar]` section
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct SidebarConfig {
+    /// Which side of the viewport the sidebar starts on
+    pub position: SidebarPosition,
+    /// Show each tab's working directory beside its name
+    pub show_path: bool,
+    /// List tabs by most recent output instead of tmux's order
+    pub sort_by_activity: bool,
+}
+
+/// Side of the screen the sidebar sits on
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SidebarPosition {
+    #[default]
+    Left,
+    Right,
+}
+
+/// `[display]` section
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct DisplayConfig {
+    /// Most redraws per second; output arriving faster is drawn together (0 disables)
+    pub max_fps: u32,
+    /// Draw a scrollbar on the viewport's right edge while a tab has scrollback
+    pub scrollbar: bool,
+}
+
+impl Default for DisplayConfig {
+    fn default() -> Self {
+        Self { max_fps: DEFAULT_MAX_FPS, scrollbar: false }
+    }
+}
+
+/// `[mouse]` section
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct MouseConfig {
+    /// Longest gap between clicks that still makes a double or triple click
+    pub double_click_ms: u64,
+    /// Characters that count as part of a word when double-clicking, besides letters and digits
+    pub word_chars: String,
+}
+
+impl Default for MouseConfig {
+    fn default() -> Self {
+        Self {
+            double_click_ms: DEFAULT_DOUBLE_CLICK_MS,
+            word_chars: DEFAULT_WORD_CHARS.to_string(),
+        }
+    }
+}
+
+impl MouseConfig {
+    pub fn double_click_timeout(&self) -> Duration {
+        Duration::from_millis(self.double_click_ms)
+    }
+}
+
+impl TabsConfig {
+    pub fn activity_grace(&self) -> Duration {
+        Duration::from_millis(self.activity_grace_ms)
+    }
+
+    /// Which side of the active tab new tabs go, or None for the end
+    pub fn new_tab_placement(&self) -> Option<Placement> {
+        match self.new_tab_position {
+            NewTabPosition::End => None,
+            NewTabPosition::After => Some(Placement::After),
+            NewTabPosition::Before => Some(Placement::Before),
+        }
+    }
+}
+
+impl Config {
+    /// Path to the config file
+    pub fn path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("helmux").join("config.toml"))
+    }
+
+    /// Load the config file, falling back to defaults if it doesn't exist
+    pub fn load() -> anyhow::Result<Self> {
+        let Some(path) = Self::path() else {
+            return Ok(Self::default());
+        };
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let contents = std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        Self::parse(&contents).with_context(|| format!("Invalid config in {}", path.display()))
+    }
+
+    /// Parse config from TOML text
+    pub fn parse(contents: &str) -> anyhow::Result<Self> {
+        Ok(toml::from_str(contents)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_empty_config_uses_defaults() {
+        let config = Config::parse("").unwrap();
+        assert_eq!(config.tabs.activity_grace_ms, DEFAULT_ACTIVITY_GRACE_MS);
+    }
+
+    #[test]
+    fn test_activity_grace() {
+        let config = Config::parse("[tabs]\nactivity_grace_ms = 1000\n").unwrap();
+        assert_eq!(config.tabs.activity_grace(), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_confirm_kill() {
+        assert!(!Config::parse("").unwrap().tabs.confirm_kill);
+        let config = Config::parse("[tabs]\nconfirm_kill = true\n").unwrap();
+        assert!(config.tabs.confirm_kill);
+    }
+
+    #[test]
+    fn test_new_tab_in_cwd() {
+        assert!(!Config::parse("").unwrap().tabs.new_tab_in_cwd);
+        let config = Config::parse("[tabs]\nnew_tab_in_cwd = true\n").unwrap();
+        assert!(config.tabs.new_tab_in_cwd);
+    }
+
+    #[test]
+    fn test_sidebar_show_path() {
+        assert!(!Config::parse("").unwrap().sidebar.show_path);
+        let config = Config::parse("[sidebar]\nshow_path = true\n").unwrap();
+        assert!(config.sidebar.show_path);
+    }
+
+    #[test]
+    fn test_display_max_fps() {
+        assert_eq!(Config::parse("").unwrap().display.max_fps, DEFAULT_MAX_FPS);
+        let config = Config::parse("[display]\nmax_fps = 0\n").unwrap();
+        assert_eq!(config.display.max_fps, 0);
+    }
+
+    #[test]
+    fn test_new_tab_position() {
+        let config = Config::parse("").unwrap();
+        assert_eq!(config.tabs.new_tab_position, NewTabPosition::End);
+        assert_eq!(config.tabs.new_tab_placement(), None);
+        let config = Config::parse("[tabs]\nnew_tab_position = \"after\"\n").unwrap();
+        assert_eq!(config.tabs.new_tab_placement(), Some(Placement::After));
+        let config = Config::parse("[tabs]\nnew_tab_position = \"before\"\n").unwrap();
+        assert_eq!(config.tabs.new_tab_placement(), Some(Placement::Before));
+        assert!(Config::parse("[tabs]\nnew_tab_position = \"middle\"\n").is_err());
+    }
+
+    #[test]
+    fn test_double_click_timeout() {
+        let config = Config::parse("").unwrap();
+        assert_eq!(config.mouse.double_click_timeout(), Duration::from_millis(DEFAULT_DOUBLE_CLICK_MS));
+        let config = Config::parse("[mouse]\ndouble_click_ms = 200\n").unwrap();
+        assert_eq!(config.mouse.double_click_timeout(), Duration::from_millis(200));
+    }
+
+    #[test]
+    fn test_display_scrollbar() {
+        assert!(!Config::parse("").unwrap().display.scrollbar);
+        let config = Config::parse("[display]\nscrollbar = true\n").unwrap();
+        assert!(config.display.scrollbar);
+    }
+
+    #[test]
+    fn test_mouse_word_chars() {
+        assert_eq!(Config::parse("").unwrap().mouse.word_chars, DEFAULT_WORD_CHARS);
+        let config = Config::parse("[mouse]\nword_chars = \"-_\"\n").unwrap();
+        assert_eq!(config.mouse.word_chars, "-_");
+    }
+
+    #[test]
+    fn test_wrap_tab_moves() {
+        assert!(!Config::parse("").unwrap().tabs.wrap_tab_moves);
+        let config = Config::parse("[tabs]\nwrap_tab_moves = true\n").unwrap();
+        assert!(config.tabs.wrap_tab_moves);
+    }
+
+    #[test]
+    fn test_sidebar_position() {
+        assert_eq!(Config::parse("").unwrap().sidebar.position, SidebarPosition::Left);
+        let config = Config::parse("[sidebar]\nposition = \"right\"\n").unwrap();
+        assert_eq!(config.sidebar.position, SidebarPosition::Right);
+    }
+
+    #[test]
+    fn test_sidebar_sort_by_activity() {
+        assert!(!Config::parse("").unwrap().sidebar.sort_by_activity);
+        let config = Config::parse("[sidebar]\nsort_by_activity = true\n").unwrap();
+        assert!(config.sidebar.sort_by_activity);
+    }
+
+    #[test]
+    fn test_appearance_overrides_theme() {
+        assert_eq!(Config::parse("").unwrap().appearance, Theme::default());
+        let config = Config::parse("[appearance]\nactive_tab_fg = \"black\"\nactive_tab_bg = \"yellow\"\n").unwrap();
+        assert_eq!(config.appearance.active_tab_fg, ratatui::style::Color::Black);
+        assert_eq!(config.appearance.active_tab_bg, ratatui::style::Color::Yellow);
+        assert_eq!(config.appearance.sidebar_bg, Theme::default().sidebar_bg);
+    }
+
+    #[test]
+    fn test_invalid_config_is_an_error() {
+        assert!(Config::parse("[tabs]\nactivity_grace_ms = \"soon\"\n").is_err());
+    }
+}
@@ -1,3 +1,5 @@
+use crate::tmux::Direction;
+
 /// Actions that can be triggered by keybindings
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Action {
@@ -7,22 +9,118 @@ pub enum Action {
     Exit,
     /// Create a new tab
     NewTab,
+    /// Create a new tab with the given name
+    NewNamedTab(String),
     /// Close the current tab
     CloseTab,
+    /// Close the tab showing the given window
+    CloseWindow(String),
+    /// Close every tab except the current one
+    CloseOtherTabs,
     /// Switch to next tab
     NextTab,
     /// Switch to previous tab
     PrevTab,
-    /// Switch to tab by number (1-based)
+    /// Switch back to the previously active tab
+    LastTab,
+    /// Switch to the first tab whose name or contents contain the query
+    FindWindow(String),
+    /// Switch to tab by the number shown beside it (0-based)
     SelectTab(usize),
     /// Toggle sidebar visibility
     ToggleSidebar,
+    /// Move the sidebar to the other side of the viewport
+    FlipSidebar,
     /// Start rename mode for current tab
     StartRename,
+    /// Toggle tmux's automatic-rename option for the current tab
+    ToggleAutoRename,
+    /// Toggle the mark on the current tab
+    ToggleMark,
+    /// Swap the current tab with the marked tab (or the next tab if none is marked)
+    SwapWindow,
+    /// Close the gaps tmux leaves in window indices after tabs are closed
+    RenumberWindows,
+    /// Move the active tab one place left in the list
+    MoveTabLeft,
+    /// Move the active tab one place right in the list
+    MoveTabRight,
+    /// Join the marked tab's pane into the current tab as a split
+    JoinMarked,
+    /// Resize the active pane by moving its edge in a direction
+    ResizePane(Direction, u16),
+    /// Clear the active pane's scrollback history
+    ClearHistory,
+    /// Toggle tmux's global mouse option
+    ToggleMouse,
+    /// Toggle sending typed input to every tab at once
+    ToggleBroadcast,
+    /// Toggle tmux's synchronize-panes for the active window
+    ToggleSyncPanes,
+    /// Switch the sidebar between tmux's order and most recent output first
+    ToggleSortByActivity,
+    /// Show or hide the debug overlay
+    ToggleDebugOverlay,
+    /// Write the active tab's visible screen to a text file
+    Screenshot,
+    /// Copy the output of the last shell command (found via OSC 133 marks)
+    CopyLastOutput,
+    /// Copy the active tab's visible screen as plain text
+    CopyScreen,
     /// Detach from tmux session
     Detach,
+    /// Kill the tmux session and exit
+    KillSession,
+    /// Restart the active tab's command if it has exited, asking first if it hasn't
+    RespawnWindow,
+    /// Kill the active tab's command and start it again
+    RestartWindow,
     /// Send literal Ctrl-B to the pane
     SendCtrlB,
+    /// Send a bare Escape key to the pane
+    SendEscape,
     /// Send a key to the active pane (key string for tmux send-keys)
     SendKey(String),
+    /// Send literal text to the active pane, byte for byte
+    SendText(String),
+    /// Enter scroll mode to browse the active tab's scrollback
+    EnterScrollMode,
+    /// Leave scroll mode and return to the live screen
+    ExitScrollMode,
+    /// Search the active tab's scrollback and show the most recent match
+    Search(String),
+    /// Move to the search match further back in history
+    SearchNext,
+    /// Move to the search match nearer the live screen
+    SearchPrev,
+    /// Scroll back to the previous shell prompt (found via OSC 133 marks)
+    PrevPrompt,
+    /// Scroll forward to the next shell prompt
+    NextPrompt,
+    /// Scroll the view back into history
+    ScrollUp(ScrollAmount),
+    /// Scroll the view forward towards the live screen
+    ScrollDown(ScrollAmount),
+}
+
+/// How far a scroll action moves the view
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScrollAmount {
+    /// A fixed number of lines
+    Lines(usize),
+    /// Half the viewport height (Ctrl-u/Ctrl-d)
+    HalfPage,
+    /// The full viewport height
+    Page,
+}
+
+impl ScrollAmount {
+    /// Resolve to a line count for a viewport of the given height
+    pub fn lines(self, page_height: u16) -> usize {
+        match self {
+            ScrollAmount::Lines(n) => n,
+            ScrollAmount::HalfPage => (page_height as usize / 2).max(1),
+            ScrollAmount::Page => (page_height as usize).max(1),
+        }
+    }
 }
@@ -0,0 +1,103 @@
+use std::time::{Duration, Instant};
+
+/// How many times in a row the same target was clicked
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClickCount {
+    Single,
+    Double,
+    Triple,
+}
+
+/// Spots double and triple clicks: repeated clicks on the same target, each
+/// within `timeout` of the one before. After a triple click it starts over
+#[derive(Debug, Clone)]
+pub struct ClickTracker<T> {
+    timeout: Duration,
+    /// The last click's target, when it happened and what it counted as
+    last: Option<(T, Instant, ClickCount)>,
+}
+
+impl<T: PartialEq> ClickTracker<T> {
+    pub fn new(timeout: Duration) -> Self {
+        Self { timeout, last: None }
+    }
+
+    /// Record a click on `target` at `now` and say what it counts as
+    pub fn register(&mut self, target: T, now: Instant) -> ClickCount {
+        let count = match &self.last {
+            Some((last, at, count)) if *last == target && now.saturating_duration_since(*at) < self.timeout => {
+                match count {
+                    ClickCount::Single => ClickCount::Double,
+                    ClickCount::Double => ClickCount::Triple,
+                    ClickCount::Triple => ClickCount::Single,
+                }
+            }
+            _ => ClickCount::Single,
+        };
+        self.last = Some((target, now, count));
+        count
+    }
+
+    /// Whether a click at `now` comes soon enough after the last one to continue
+    /// it, whatever its target
+    pub fn follows_last(&self, now: Instant) -> bool {
+        self.last
+            .as_ref()
+            .is_some_and(|(_, at, _)| now.saturating_duration_since(*at) < self.timeout)
+    }
+
+    /// Forget the last click, so the next one is a single click
+    pub fn reset(&mut self) {
+        self.last = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escalates_single_double_triple() {
+        let mut tracker = ClickTracker::new(Duration::from_millis(400));
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        assert_eq!(tracker.register(1, at(0)), ClickCount::Single);
+        assert_eq!(tracker.register(1, at(300)), ClickCount::Double);
+        assert_eq!(tracker.register(1, at(600)), ClickCount::Triple);
+        assert_eq!(tracker.register(1, at(900)), ClickCount::Single);
+        // Another target starts over
+        assert_eq!(tracker.register(2, at(1000)), ClickCount::Single);
+        assert_eq!(tracker.register(2, at(1100)), ClickCount::Double);
+        tracker.reset();
+        assert_eq!(tracker.register(2, at(1200)), ClickCount::Single);
+    }
+
+    #[test]
+    fn test_configured_timeout() {
+        // A 200ms window makes clicks 300ms apart two singles
+        let mut tracker = ClickTracker::new(Duration::from_millis(200));
+        let start = Instant::now();
+        assert_eq!(tracker.register(0, start), ClickCount::Single);
+        assert_eq!(tracker.register(0, start + Duration::from_millis(300)), ClickCount::Single);
+        assert_eq!(tracker.register(0, start + Duration::from_millis(450)), ClickCount::Double);
+    }
+
+    #[test]
+    fn test_timeout_resets() {
+        let mut tracker = ClickTracker::new(Duration::from_millis(400));
+        let start = Instant::now();
+        assert_eq!(tracker.register((3, 4), start), ClickCount::Single);
+        assert_eq!(tracker.register((3, 4), start + Duration::from_millis(400)), ClickCount::Single);
+        assert_eq!(tracker.register((3, 4), start + Duration::from_millis(500)), ClickCount::Double);
+    }
+
+    #[test]
+    fn test_follows_last() {
+        let mut tracker = ClickTracker::new(Duration::from_millis(400));
+        let start = Instant::now();
+        assert!(!tracker.follows_last(start));
+        tracker.register(1, start);
+        assert!(tracker.follows_last(start + Duration::from_millis(399)));
+        assert!(!tracker.follows_last(start + Duration::from_millis(400)));
+    }
+}
@@ -1,6 +1,16 @@
-use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::time::{Duration, Instant};
 
-use super::Action;
+use crossterm::event::{KeyCode, KeyEvent, KeyEventState, KeyModifiers};
+
+use super::{kitty, Action, ScrollAmount};
+use crate::tmux::Direction;
+
+/// Longest window name sent to tmux, in characters
+pub const MAX_WINDOW_NAME_CHARS: usize = 64;
+
+/// How long a bare Esc waits for a following character before it's sent on its own
+/// Terminals that report Alt-x as Esc then x send both together, well inside this
+pub const ESCAPE_TIMEOUT: Duration = Duration::from_millis(25);
 
 /// Input mode for the application
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -11,14 +21,31 @@ pub enum InputMode {
     Prefix,
     /// Renaming a tab - capturing input
     Rename,
+    /// Naming a tab about to be created - capturing input
+    NewTabName,
+    /// Typing a find-window query - capturing input
+    Find,
+    /// Typing a scrollback search query - capturing input
+    Search,
+    /// Browsing scrollback - keys move the view instead of going to tmux
+    Scroll,
+    /// Waiting for y/n before running a destructive action
+    Confirm { action: Action, prompt: String },
 }
 
 /// Input handler with modal state
 pub struct InputHandler {
     /// Current input mode
     mode: InputMode,
-    /// Buffer for rename input
-    rename_buffer: String,
+    /// Text typed at the rename, find or search prompt
+    text_buffer: String,
+    /// Ask before closing a tab
+    confirm_kill: bool,
+    /// When a bare Esc was typed in normal mode, if it's still waiting to see
+    /// whether it starts an Alt combination
+    pending_escape: Option<Instant>,
+    /// The active pane's program asked for kitty keyboard protocol key encoding
+    kitty_keyboard: bool,
 }
 
 impl Default for InputHandler {
@@ -31,10 +58,24 @@ impl InputHandler {
     pub fn new() -> Self {
         Self {
             mode: InputMode::Normal,
-            rename_buffer: String::new(),
+            text_buffer: String::new(),
+            confirm_kill: false,
+            pending_escape: None,
+            kitty_keyboard: false,
         }
     }
 
+    /// Set whether closing a tab asks for confirmation first
+    pub fn with_confirm_kill(mut self, confirm: bool) -> Self {
+        self.confirm_kill = confirm;
+        self
+    }
+
+    /// Check whether closing a tab asks for confirmation first
+    pub fn confirms_kill(&self) -> bool {
+        self.confirm_kill
+    }
+
     /// Get the current input mode
     pub fn mode(&self) -> &InputMode {
         &self.mode
@@ -45,27 +86,154 @@ impl InputHandler {
         self.mode == InputMode::Rename
     }
 
-    /// Get the current rename buffer content
-    pub fn rename_buffer(&self) -> &str {
-        &self.rename_buffer
+    /// Check if we're naming a new tab
+    pub fn is_naming_new_tab(&self) -> bool {
+        self.mode == InputMode::NewTabName
+    }
+
+    /// Check if we're typing a find-window query
+    pub fn is_finding(&self) -> bool {
+        self.mode == InputMode::Find
+    }
+
+    /// Check if we're typing a scrollback search query
+    pub fn is_searching(&self) -> bool {
+        self.mode == InputMode::Search
+    }
+
+    /// Check if a prompt (rename, new tab name, find, search or confirm) is capturing input
+    pub fn is_prompting(&self) -> bool {
+        matches!(
+            self.mode,
+            InputMode::Rename
+                | InputMode::NewTabName
+                | InputMode::Find
+                | InputMode::Search
+                | InputMode::Confirm { .. }
+        )
+    }
+
+    /// Dismiss whichever prompt is open without acting on it
+    pub fn cancel_prompt(&mut self) {
+        if self.is_prompting() {
+            self.mode = InputMode::Normal;
+            self.text_buffer.clear();
+        }
+    }
+
+    /// Encode keys for the kitty keyboard protocol from now on (or stop), following
+    /// the active pane's flags (see `kitty::DISAMBIGUATE`)
+    pub fn set_kitty_keyboard(&mut self, flags: u16) {
+        self.kitty_keyboard = flags & kitty::DISAMBIGUATE != 0;
+    }
+
+    /// When a held-back Esc has to be sent if nothing follows it
+    pub fn escape_deadline(&self) -> Option<Instant> {
+        self.pending_escape.map(|typed| typed + ESCAPE_TIMEOUT)
+    }
+
+    /// Release a held-back Esc as a plain Escape if it can't start an Alt combination
+    /// any more: it has timed out, or `next` (the key about to be handled) isn't a
+    /// character. Call this before handle_key, and when the escape deadline passes;
+    /// with no `next` key (a mouse or paste event is about to be handled) it always
+    /// releases
+    pub fn take_stale_escape(&mut self, next: Option<&KeyEvent>, now: Instant) -> Option<Action> {
+        let typed = self.pending_escape?;
+        let combines = next.is_some_and(|key| {
+            matches!(key.code, KeyCode::Char(_))
+                && !key.modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT)
+        });
+        if combines && now < typed + ESCAPE_TIMEOUT {
+            return None;
+        }
+        self.pending_escape = None;
+        Some(Action::SendEscape)
+    }
+
+    /// Check if we're in scroll mode
+    pub fn is_scrolling(&self) -> bool {
+        self.mode == InputMode::Scroll
+    }
+
+    /// Leave scroll mode
+    pub fn exit_scroll(&mut self) {
+        if self.mode == InputMode::Scroll {
+            self.mode = InputMode::Normal;
+        }
+    }
+
+    /// Check if we're waiting for a confirmation
+    pub fn is_confirming(&self) -> bool {
+        matches!(self.mode, InputMode::Confirm { .. })
+    }
+
+    /// Get the prompt of a pending confirmation
+    pub fn confirm_prompt(&self) -> Option<&str> {
+        match &self.mode {
+            InputMode::Confirm { prompt, .. } => Some(prompt),
+            _ => None,
+        }
+    }
+
+    /// Ask for confirmation before running an action
+    pub fn start_confirm(&mut self, action: Action, prompt: impl Into<String>) {
+        self.mode = InputMode::Confirm {
+            action,
+            prompt: prompt.into(),
+        };
+    }
+
+    /// Drop a pending confirmation without running its action
+    pub fn cancel_confirm(&mut self) {
+        if self.is_confirming() {
+            self.mode = InputMode::Normal;
+        }
+    }
+
+    /// Get the text typed at the rename, find or search prompt
+    pub fn text_buffer(&self) -> &str {
+        &self.text_buffer
     }
 
     /// Start rename mode with the current tab name
     pub fn start_rename(&mut self, current_name: &str) {
         self.mode = InputMode::Rename;
-        self.rename_buffer = current_name.to_string();
+        self.text_buffer = current_name.to_string();
     }
 
     /// Cancel rename mode
     pub fn cancel_rename(&mut self) {
         self.mode = InputMode::Normal;
-        self.rename_buffer.clear();
+        self.text_buffer.clear();
+    }
+
+    /// Start naming a tab before it's created
+    pub fn start_new_tab_name(&mut self) {
+        self.mode = InputMode::NewTabName;
+        self.text_buffer.clear();
+    }
+
+    /// Start typing a find-window query
+    pub fn start_find(&mut self) {
+        self.mode = InputMode::Find;
+        self.text_buffer.clear();
     }
 
-    /// Finish rename mode and return the new name
+    /// Start typing a scrollback search query
+    pub fn start_search(&mut self) {
+        self.mode = InputMode::Search;
+        self.text_buffer.clear();
+    }
+
+    /// Switch to scroll mode, e.g. to step through search matches
+    pub fn enter_scroll(&mut self) {
+        self.mode = InputMode::Scroll;
+    }
+
+    /// Finish rename mode and return the new name, sanitized for rename-window
     pub fn finish_rename(&mut self) -> String {
         self.mode = InputMode::Normal;
-        std::mem::take(&mut self.rename_buffer)
+        sanitize_window_name(&std::mem::take(&mut self.text_buffer))
     }
 
     /// Handle a key event and return the corresponding action
@@ -79,6 +247,11 @@ impl InputHandler {
             InputMode::Normal => self.handle_normal_key(key),
             InputMode::Prefix => self.handle_prefix_key(key),
             InputMode::Rename => self.handle_rename_key(key),
+            InputMode::NewTabName => self.handle_new_tab_name_key(key),
+            InputMode::Find => self.handle_find_key(key),
+            InputMode::Search => self.handle_search_key(key),
+            InputMode::Scroll => self.handle_scroll_key(key),
+            InputMode::Confirm { .. } => self.handle_confirm_key(key),
         }
     }
 
@@ -90,6 +263,31 @@ impl InputHandler {
             return Action::None;
         }
 
+        // Programs using the kitty keyboard protocol get unambiguous CSI u
+        // sequences (Esc included, so it needn't be held back)
+        if self.kitty_keyboard {
+            if let Some(sequence) = kitty::encode(&key) {
+                return Action::SendText(sequence);
+            }
+        }
+
+        // Esc followed straight away by a character is how some terminals send
+        // Alt plus that character, so it goes to tmux as M-<char>
+        if let (Some(_), KeyCode::Char(c)) = (self.pending_escape.take(), key.code) {
+            if !key.modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) {
+                return Action::SendKey(format!("M-{}", c));
+            }
+        }
+
+        // A bare Esc always reaches the pane, as tmux's Escape key by name so tmux
+        // never has to guess whether it starts an escape sequence. It's held back
+        // briefly in case a character follows (see take_stale_escape). Alt combos
+        // crossterm already recognised arrive with ALT set and become M- keys
+        if key.code == KeyCode::Esc && key.modifiers.is_empty() {
+            self.pending_escape = Some(Instant::now());
+            return Action::None;
+        }
+
         // Pass key through to tmux
         key_to_send_action(key)
     }
@@ -103,7 +301,17 @@ impl InputHandler {
             // Create new tab
             KeyCode::Char('c') => Action::NewTab,
 
+            // Create new tab, asking for its name first
+            KeyCode::Char('C') => {
+                self.start_new_tab_name();
+                Action::None
+            }
+
             // Close current tab
+            KeyCode::Char('x') if self.confirm_kill => {
+                self.start_confirm(Action::CloseTab, "Kill window? (y/n)");
+                Action::None
+            }
             KeyCode::Char('x') => Action::CloseTab,
 
             // Next tab
@@ -112,29 +320,175 @@ impl InputHandler {
             // Previous tab
             KeyCode::Char('p') => Action::PrevTab,
 
-            // Tab by number (1-9)
+            // Last (previously active) tab
+            KeyCode::Char('l') => Action::LastTab,
+
+            // Find a window by name or content
+            KeyCode::Char('f') => {
+                self.start_find();
+                Action::None
+            }
+
+            // Tab by number (1-9), converted to a 0-based display position
             KeyCode::Char(c) if c.is_ascii_digit() && c != '0' => {
-                let index = c.to_digit(10).unwrap() as usize;
-                Action::SelectTab(index)
+                let number = c.to_digit(10).unwrap() as usize;
+                Action::SelectTab(number - 1)
             }
 
             // Toggle sidebar
             KeyCode::Char('b') => Action::ToggleSidebar,
+            KeyCode::Char('|') => Action::FlipSidebar,
 
             // Rename tab
             KeyCode::Char(',') => Action::StartRename,
+            KeyCode::Char('A') => Action::ToggleAutoRename,
+
+            // Mark tab / swap with marked tab
+            KeyCode::Char('m') => Action::ToggleMark,
+            KeyCode::Char('S') => Action::SwapWindow,
+            KeyCode::Char('J') => Action::JoinMarked,
+
+            // Compact tmux's window indices
+            KeyCode::Char('r') => Action::RenumberWindows,
+
+            // Move the active tab along the list
+            KeyCode::Char('<') => Action::MoveTabLeft,
+            KeyCode::Char('>') => Action::MoveTabRight,
+
+            // Resize the active pane: Ctrl-arrow by one cell, Alt-arrow by five (as in tmux)
+            KeyCode::Up | KeyCode::Down | KeyCode::Left | KeyCode::Right
+                if key.modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) =>
+            {
+                let direction = match key.code {
+                    KeyCode::Up => Direction::Up,
+                    KeyCode::Down => Direction::Down,
+                    KeyCode::Left => Direction::Left,
+                    _ => Direction::Right,
+                };
+                let amount = if key.modifiers.contains(KeyModifiers::CONTROL) { 1 } else { 5 };
+                Action::ResizePane(direction, amount)
+            }
+
+            // Shift-arrows move the active tab too
+            KeyCode::Left if key.modifiers.contains(KeyModifiers::SHIFT) => Action::MoveTabLeft,
+            KeyCode::Right if key.modifiers.contains(KeyModifiers::SHIFT) => Action::MoveTabRight,
+
+            // Close every tab but this one (asks first)
+            KeyCode::Char('O') => {
+                self.start_confirm(Action::CloseOtherTabs, "Close all other tabs? (y/n)");
+                Action::None
+            }
+
+            // Clear scrollback
+            KeyCode::Char('K') => Action::ClearHistory,
+
+            // Restart the tab's command
+            KeyCode::Char('R') => Action::RespawnWindow,
+
+            // Toggle tmux mouse mode
+            KeyCode::Char('M') => Action::ToggleMouse,
+
+            // Type into every tab at once
+            KeyCode::Char('*') => Action::ToggleBroadcast,
+
+            // Type into every pane of this tab, through tmux
+            KeyCode::Char('=') => Action::ToggleSyncPanes,
+
+            // List tabs by most recent output, or back in tmux's order
+            KeyCode::Char('T') => Action::ToggleSortByActivity,
+
+            // Show counters and internal state over the viewport
+            KeyCode::Char('~') => Action::ToggleDebugOverlay,
+
+            // Save a plain-text screenshot of the active tab
+            KeyCode::Char('P') => Action::Screenshot,
+
+            // Copy the last command's output, or the whole screen
+            KeyCode::Char('Y') => Action::CopyLastOutput,
+            KeyCode::Char('y') => Action::CopyScreen,
+
+            // Jump between shell prompts
+            KeyCode::Char('{') => Action::PrevPrompt,
+            KeyCode::Char('}') => Action::NextPrompt,
 
             // Detach
             KeyCode::Char('d') => Action::Detach,
 
+            // Kill the whole session (asks first)
+            KeyCode::Char('X') => {
+                self.start_confirm(Action::KillSession, "Kill session and all its windows? (y/n)");
+                Action::None
+            }
+
+            // Search the scrollback
+            KeyCode::Char('/') => {
+                self.start_search();
+                Action::None
+            }
+
+            // Scroll mode (like tmux copy-mode)
+            KeyCode::Char('[') => {
+                self.mode = InputMode::Scroll;
+                Action::EnterScrollMode
+            }
+
             // Send literal Ctrl-B (Ctrl-B Ctrl-B)
             KeyCode::Char('B') if key.modifiers.contains(KeyModifiers::SHIFT) => Action::SendCtrlB,
 
+            // Send a literal Escape, e.g. when Esc alone would be taken by a helmux mode
+            KeyCode::Esc => Action::SendEscape,
+
             // Unknown prefix command - ignore
             _ => Action::None,
         }
     }
 
+    /// Handle key in scroll mode
+    fn handle_scroll_key(&mut self, key: KeyEvent) -> Action {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+
+        match key.code {
+            // Leave scroll mode
+            KeyCode::Esc | KeyCode::Char('q') => {
+                self.mode = InputMode::Normal;
+                Action::ExitScrollMode
+            }
+
+            // Half page (vim/less style)
+            KeyCode::Char('u') if ctrl => Action::ScrollUp(ScrollAmount::HalfPage),
+            KeyCode::Char('d') if ctrl => Action::ScrollDown(ScrollAmount::HalfPage),
+
+            // Full page
+            KeyCode::PageUp => Action::ScrollUp(ScrollAmount::Page),
+            KeyCode::PageDown => Action::ScrollDown(ScrollAmount::Page),
+            KeyCode::Char('b') if ctrl => Action::ScrollUp(ScrollAmount::Page),
+            KeyCode::Char('f') if ctrl => Action::ScrollDown(ScrollAmount::Page),
+
+            // Step through search matches: n goes further back, N towards the live screen
+            KeyCode::Char('n') => Action::SearchNext,
+            KeyCode::Char('N') => Action::SearchPrev,
+
+            // Jump between shell prompts
+            KeyCode::Char('{') => Action::PrevPrompt,
+            KeyCode::Char('}') => Action::NextPrompt,
+
+            // Single line
+            KeyCode::Up | KeyCode::Char('k') => Action::ScrollUp(ScrollAmount::Lines(1)),
+            KeyCode::Down | KeyCode::Char('j') => Action::ScrollDown(ScrollAmount::Lines(1)),
+
+            _ => Action::None,
+        }
+    }
+
+    /// Handle key while confirming: y runs the pending action, anything else cancels
+    fn handle_confirm_key(&mut self, key: KeyEvent) -> Action {
+        let mode = std::mem::replace(&mut self.mode, InputMode::Normal);
+        match (mode, key.code) {
+            (InputMode::Confirm { action, .. }, KeyCode::Char('y' | 'Y')) => action,
+            _ => Action::None,
+        }
+    }
+
     /// Handle key in rename mode
     fn handle_rename_key(&mut self, key: KeyEvent) -> Action {
         match key.code {
@@ -145,31 +499,106 @@ impl InputHandler {
             }
 
             // Confirm rename - we don't have a FinishRename action,
-            // the main loop should check rename_buffer and send the command
+            // the main loop should check text_buffer and send the command
             KeyCode::Enter => {
                 // The caller should call finish_rename() to get the name
                 // and send the rename command to tmux
                 Action::None
             }
 
-            // Backspace - delete character
-            KeyCode::Backspace => {
-                self.rename_buffer.pop();
+            _ => {
+                self.edit_text(key);
+                Action::None
+            }
+        }
+    }
+
+    /// Handle key while naming a new tab: Enter creates it, Esc creates nothing
+    fn handle_new_tab_name_key(&mut self, key: KeyEvent) -> Action {
+        match key.code {
+            KeyCode::Esc => {
+                self.cancel_prompt();
                 Action::None
             }
+            KeyCode::Enter => {
+                self.mode = InputMode::Normal;
+                let name = sanitize_window_name(&std::mem::take(&mut self.text_buffer));
+                if name.is_empty() {
+                    Action::NewTab
+                } else {
+                    Action::NewNamedTab(name)
+                }
+            }
+            _ => {
+                self.edit_text(key);
+                Action::None
+            }
+        }
+    }
 
-            // Type character
-            KeyCode::Char(c) => {
-                // Don't allow control characters
-                if !key.modifiers.contains(KeyModifiers::CONTROL)
-                    && !key.modifiers.contains(KeyModifiers::ALT)
-                {
-                    self.rename_buffer.push(c);
+    /// Handle key in find mode
+    fn handle_find_key(&mut self, key: KeyEvent) -> Action {
+        match key.code {
+            KeyCode::Esc => {
+                self.cancel_prompt();
+                Action::None
+            }
+            KeyCode::Enter => {
+                self.mode = InputMode::Normal;
+                let query = std::mem::take(&mut self.text_buffer);
+                if query.trim().is_empty() {
+                    Action::None
+                } else {
+                    Action::FindWindow(query.trim().to_string())
                 }
+            }
+            _ => {
+                self.edit_text(key);
                 Action::None
             }
+        }
+    }
 
-            _ => Action::None,
+    /// Handle key in search mode
+    /// Unlike find, the query isn't trimmed: spaces can be part of what's searched for
+    fn handle_search_key(&mut self, key: KeyEvent) -> Action {
+        match key.code {
+            KeyCode::Esc => {
+                self.cancel_prompt();
+                Action::None
+            }
+            KeyCode::Enter => {
+                self.mode = InputMode::Normal;
+                let query = std::mem::take(&mut self.text_buffer);
+                if query.is_empty() {
+                    Action::None
+                } else {
+                    Action::Search(query)
+                }
+            }
+            _ => {
+                self.edit_text(key);
+                Action::None
+            }
+        }
+    }
+
+    /// Apply a typing key to the prompt text
+    fn edit_text(&mut self, key: KeyEvent) {
+        match key.code {
+            // Backspace - delete character
+            KeyCode::Backspace => {
+                self.text_buffer.pop();
+            }
+
+            // Type character, but not control/alt combinations
+            KeyCode::Char(c)
+                if !key.modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) =>
+            {
+                self.text_buffer.push(c);
+            }
+
+            _ => {}
         }
     }
 }
@@ -179,30 +608,29 @@ fn key_to_send_action(key: KeyEvent) -> Action {
     let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
     let alt = key.modifiers.contains(KeyModifiers::ALT);
 
-    let key_str = match key.code {
+    if let Some(name) = keypad_key_name(key) {
+        return Action::SendKey(format!("{}{}", modifier_prefix(key.modifiers), name));
+    }
+
+    let key_name = match key.code {
         KeyCode::Char(c) => {
             if ctrl {
-                format!("C-{}", c)
+                let name = ctrl_char_key_name(c);
+                return Action::SendKey(if alt { format!("M-{}", name) } else { name });
             } else if alt {
-                format!("M-{}", c)
+                return Action::SendKey(format!("M-{}", c));
             } else {
-                // Regular character - use literal mode
-                let escaped = match c {
-                    '\'' => "'\\''".to_string(),
-                    _ => c.to_string(),
-                };
-                return Action::SendKey(format!("-l '{}'", escaped));
+                // Regular character - sent as raw bytes, so nothing needs quoting
+                return Action::SendText(c.to_string());
             }
         }
+        KeyCode::Tab if key.modifiers.contains(KeyModifiers::SHIFT) => {
+            return Action::SendKey("BTab".to_string());
+        }
+        KeyCode::BackTab => return Action::SendKey("BTab".to_string()),
         KeyCode::Enter => "Enter".to_string(),
         KeyCode::Backspace => "BSpace".to_string(),
-        KeyCode::Tab => {
-            if key.modifiers.contains(KeyModifiers::SHIFT) {
-                "BTab".to_string()
-            } else {
-                "Tab".to_string()
-            }
-        }
+        KeyCode::Tab => "Tab".to_string(),
         KeyCode::Esc => "Escape".to_string(),
         KeyCode::Up => "Up".to_string(),
         KeyCode::Down => "Down".to_string(),
@@ -218,5 +646,524 @@ fn key_to_send_action(key: KeyEvent) -> Action {
         _ => return Action::None,
     };
 
-    Action::SendKey(key_str)
+    // Named keys carry their modifiers as tmux prefixes (C-Left, S-F3, C-S-Up)
+    Action::SendKey(format!("{}{}", modifier_prefix(key.modifiers), key_name))
+}
+
+/// tmux key name for Ctrl plus a character
+/// Terminals report the non-letter control codes in several ways (crossterm
+/// turns 0x1C-0x1F into Ctrl-4..Ctrl-7, Ctrl-Space may arrive as Ctrl-@ or
+/// Ctrl-2), so these map to the key tmux would see. Letters are case-folded:
+/// control codes have no case, so Ctrl-Shift-A is just C-a
+fn ctrl_char_key_name(c: char) -> String {
+    match c {
+        ' ' | '@' | '2' => "C-Space".to_string(),
+        '[' | '3' => "Escape".to_string(),
+        // Doubled so tmux's command parser leaves a single backslash
+        '\\' | '4' => "C-\\\\".to_string(),
+        ']' | '5' => "C-]".to_string(),
+        '^' | '~' | '6' => "C-^".to_string(),
+        '_' | '/' | '7' => "C-_".to_string(),
+        '?' | '8' => "BSpace".to_string(),
+        c => format!("C-{}", c.to_ascii_lowercase()),
+    }
+}
+
+/// tmux modifier prefix for a key, in tmux's own order: C-, M-, S-
+fn modifier_prefix(modifiers: KeyModifiers) -> String {
+    let mut prefix = String::new();
+    if modifiers.contains(KeyModifiers::CONTROL) {
+        prefix.push_str("C-");
+    }
+    if modifiers.contains(KeyModifiers::ALT) {
+        prefix.push_str("M-");
+    }
+    if modifiers.contains(KeyModifiers::SHIFT) {
+        prefix.push_str("S-");
+    }
+    prefix
+}
+
+/// tmux name for a keypad key (KP0-KP9, KP/, KP*, KP-, KP+, KP., KPEnter)
+/// Terminals only report keypad keys distinctly with enhanced keyboard reporting
+fn keypad_key_name(key: KeyEvent) -> Option<String> {
+    if !key.state.contains(KeyEventState::KEYPAD) {
+        return None;
+    }
+    match key.code {
+        KeyCode::Char(c @ ('0'..='9' | '/' | '*' | '-' | '+' | '.')) => Some(format!("KP{}", c)),
+        KeyCode::Enter => Some("KPEnter".to_string()),
+        _ => None,
+    }
+}
+
+/// Make a typed or pasted name safe to send as a window name
+/// Line breaks become spaces, other control characters are dropped, and the
+/// result is trimmed and capped at MAX_WINDOW_NAME_CHARS
+pub fn sanitize_window_name(name: &str) -> String {
+    let cleaned: String = name
+        .replace("\r\n", " ")
+        .chars()
+        .filter_map(|c| match c {
+            '\n' | '\r' | '\t' => Some(' '),
+            c if c.is_control() => None,
+            c => Some(c),
+        })
+        .collect();
+    let capped: String = cleaned.trim().chars().take(MAX_WINDOW_NAME_CHARS).collect();
+    capped.trim_end().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(code: KeyCode, modifiers: KeyModifiers) -> KeyEvent {
+        KeyEvent::new(code, modifiers)
+    }
+
+    fn enter_scroll_mode(input: &mut InputHandler) {
+        input.handle_key(key(KeyCode::Char('b'), KeyModifiers::CONTROL));
+        let action = input.handle_key(key(KeyCode::Char('['), KeyModifiers::NONE));
+        assert_eq!(action, Action::EnterScrollMode);
+        assert!(input.is_scrolling());
+    }
+
+    #[test]
+    fn test_scroll_mode_half_page_keys() {
+        let mut input = InputHandler::new();
+        enter_scroll_mode(&mut input);
+
+        assert_eq!(
+            input.handle_key(key(KeyCode::Char('u'), KeyModifiers::CONTROL)),
+            Action::ScrollUp(ScrollAmount::HalfPage)
+        );
+        assert_eq!(
+            input.handle_key(key(KeyCode::Char('d'), KeyModifiers::CONTROL)),
+            Action::ScrollDown(ScrollAmount::HalfPage)
+        );
+        assert!(input.is_scrolling());
+    }
+
+    #[test]
+    fn test_scroll_mode_exit() {
+        let mut input = InputHandler::new();
+        enter_scroll_mode(&mut input);
+
+        assert_eq!(
+            input.handle_key(key(KeyCode::Char('q'), KeyModifiers::NONE)),
+            Action::ExitScrollMode
+        );
+        assert_eq!(input.mode(), &InputMode::Normal);
+    }
+
+    #[test]
+    fn test_prefix_digit_selects_zero_based_position() {
+        let mut input = InputHandler::new();
+        input.handle_key(key(KeyCode::Char('b'), KeyModifiers::CONTROL));
+        assert_eq!(
+            input.handle_key(key(KeyCode::Char('1'), KeyModifiers::NONE)),
+            Action::SelectTab(0)
+        );
+
+        input.handle_key(key(KeyCode::Char('b'), KeyModifiers::CONTROL));
+        assert_eq!(
+            input.handle_key(key(KeyCode::Char('0'), KeyModifiers::NONE)),
+            Action::None
+        );
+    }
+
+    fn send(code: KeyCode, modifiers: KeyModifiers) -> Action {
+        InputHandler::new().handle_key(key(code, modifiers))
+    }
+
+    #[test]
+    fn test_modified_named_keys() {
+        assert_eq!(send(KeyCode::Left, KeyModifiers::CONTROL), Action::SendKey("C-Left".into()));
+        assert_eq!(send(KeyCode::F(3), KeyModifiers::SHIFT), Action::SendKey("S-F3".into()));
+        assert_eq!(send(KeyCode::Up, KeyModifiers::ALT), Action::SendKey("M-Up".into()));
+        assert_eq!(
+            send(KeyCode::Right, KeyModifiers::CONTROL | KeyModifiers::SHIFT),
+            Action::SendKey("C-S-Right".into())
+        );
+        assert_eq!(send(KeyCode::F(5), KeyModifiers::NONE), Action::SendKey("F5".into()));
+        assert_eq!(send(KeyCode::BackTab, KeyModifiers::SHIFT), Action::SendKey("BTab".into()));
+    }
+
+    #[test]
+    fn test_plain_chars_are_sent_as_text() {
+        assert_eq!(send(KeyCode::Char('\''), KeyModifiers::NONE), Action::SendText("'".into()));
+        assert_eq!(send(KeyCode::Char('€'), KeyModifiers::NONE), Action::SendText("€".into()));
+    }
+
+    #[test]
+    fn test_ctrl_special_chars() {
+        assert_eq!(send(KeyCode::Char(' '), KeyModifiers::CONTROL), Action::SendKey("C-Space".into()));
+        assert_eq!(send(KeyCode::Char('@'), KeyModifiers::CONTROL), Action::SendKey("C-Space".into()));
+        assert_eq!(send(KeyCode::Char('['), KeyModifiers::CONTROL), Action::SendKey("Escape".into()));
+        assert_eq!(send(KeyCode::Char('4'), KeyModifiers::CONTROL), Action::SendKey("C-\\\\".into()));
+        assert_eq!(send(KeyCode::Char(']'), KeyModifiers::CONTROL), Action::SendKey("C-]".into()));
+        assert_eq!(send(KeyCode::Char('_'), KeyModifiers::CONTROL), Action::SendKey("C-_".into()));
+    }
+
+    #[test]
+    fn test_ctrl_uppercase_is_not_shifted() {
+        assert_eq!(
+            send(KeyCode::Char('A'), KeyModifiers::CONTROL | KeyModifiers::SHIFT),
+            Action::SendKey("C-a".into())
+        );
+        assert_eq!(
+            send(KeyCode::Char('x'), KeyModifiers::CONTROL | KeyModifiers::ALT),
+            Action::SendKey("M-C-x".into())
+        );
+    }
+
+    #[test]
+    fn test_keypad_keys() {
+        let keypad = |code| {
+            let mut event = key(code, KeyModifiers::NONE);
+            event.state = KeyEventState::KEYPAD;
+            InputHandler::new().handle_key(event)
+        };
+        assert_eq!(keypad(KeyCode::Char('7')), Action::SendKey("KP7".into()));
+        assert_eq!(keypad(KeyCode::Char('*')), Action::SendKey("KP*".into()));
+        assert_eq!(keypad(KeyCode::Enter), Action::SendKey("KPEnter".into()));
+    }
+
+    #[test]
+    fn test_prefix_ctrl_arrow_resizes_pane() {
+        let mut input = InputHandler::new();
+        input.handle_key(key(KeyCode::Char('b'), KeyModifiers::CONTROL));
+        assert_eq!(
+            input.handle_key(key(KeyCode::Left, KeyModifiers::CONTROL)),
+            Action::ResizePane(Direction::Left, 1)
+        );
+
+        input.handle_key(key(KeyCode::Char('b'), KeyModifiers::CONTROL));
+        assert_eq!(
+            input.handle_key(key(KeyCode::Down, KeyModifiers::ALT)),
+            Action::ResizePane(Direction::Down, 5)
+        );
+    }
+
+    #[test]
+    fn test_close_other_tabs_needs_confirmation() {
+        let mut input = InputHandler::new();
+        input.handle_key(key(KeyCode::Char('b'), KeyModifiers::CONTROL));
+        assert_eq!(input.handle_key(key(KeyCode::Char('O'), KeyModifiers::NONE)), Action::None);
+        assert!(input.is_confirming());
+        assert_eq!(input.confirm_prompt(), Some("Close all other tabs? (y/n)"));
+
+        assert_eq!(
+            input.handle_key(key(KeyCode::Char('y'), KeyModifiers::NONE)),
+            Action::CloseOtherTabs
+        );
+        assert_eq!(input.mode(), &InputMode::Normal);
+
+        // Any other key cancels
+        input.handle_key(key(KeyCode::Char('b'), KeyModifiers::CONTROL));
+        input.handle_key(key(KeyCode::Char('O'), KeyModifiers::NONE));
+        assert_eq!(input.handle_key(key(KeyCode::Char('n'), KeyModifiers::NONE)), Action::None);
+        assert_eq!(input.mode(), &InputMode::Normal);
+    }
+
+    #[test]
+    fn test_confirm_kill() {
+        let mut input = InputHandler::new().with_confirm_kill(true);
+        input.handle_key(key(KeyCode::Char('b'), KeyModifiers::CONTROL));
+        assert_eq!(input.handle_key(key(KeyCode::Char('x'), KeyModifiers::NONE)), Action::None);
+        assert_eq!(
+            input.mode(),
+            &InputMode::Confirm {
+                action: Action::CloseTab,
+                prompt: "Kill window? (y/n)".to_string(),
+            }
+        );
+        assert_eq!(input.handle_key(key(KeyCode::Char('y'), KeyModifiers::NONE)), Action::CloseTab);
+
+        input.handle_key(key(KeyCode::Char('b'), KeyModifiers::CONTROL));
+        input.handle_key(key(KeyCode::Char('x'), KeyModifiers::NONE));
+        assert_eq!(input.handle_key(key(KeyCode::Esc, KeyModifiers::NONE)), Action::None);
+        assert_eq!(input.mode(), &InputMode::Normal);
+    }
+
+    #[test]
+    fn test_close_tab_without_confirm_kill() {
+        let mut input = InputHandler::new();
+        input.handle_key(key(KeyCode::Char('b'), KeyModifiers::CONTROL));
+        assert_eq!(input.handle_key(key(KeyCode::Char('x'), KeyModifiers::NONE)), Action::CloseTab);
+    }
+
+    #[test]
+    fn test_scroll_amount_lines() {
+        assert_eq!(ScrollAmount::HalfPage.lines(24), 12);
+        assert_eq!(ScrollAmount::HalfPage.lines(1), 1);
+        assert_eq!(ScrollAmount::Page.lines(24), 24);
+        assert_eq!(ScrollAmount::Lines(3).lines(24), 3);
+    }
+
+    #[test]
+    fn test_finish_rename_sanitizes_name() {
+        let mut input = InputHandler::new();
+        input.start_rename("build\r\nlogs\x07\n");
+        assert_eq!(input.finish_rename(), "build logs");
+        assert_eq!(input.mode(), &InputMode::Normal);
+
+        // Whitespace and control characters alone leave an empty name
+        input.start_rename(" \x1b\n ");
+        assert_eq!(input.finish_rename(), "");
+    }
+
+    #[test]
+    fn test_finish_rename_caps_length() {
+        let mut input = InputHandler::new();
+        input.start_rename(&"é".repeat(MAX_WINDOW_NAME_CHARS + 10));
+        assert_eq!(input.finish_rename().chars().count(), MAX_WINDOW_NAME_CHARS);
+    }
+
+    #[test]
+    fn test_find_prompt_returns_query() {
+        let mut input = InputHandler::new();
+        input.handle_key(key(KeyCode::Char('b'), KeyModifiers::CONTROL));
+        input.handle_key(key(KeyCode::Char('f'), KeyModifiers::NONE));
+        assert!(input.is_finding());
+
+        for c in "logsx".chars() {
+            input.handle_key(key(KeyCode::Char(c), KeyModifiers::NONE));
+        }
+        input.handle_key(key(KeyCode::Backspace, KeyModifiers::NONE));
+        assert_eq!(input.text_buffer(), "logs");
+        assert_eq!(
+            input.handle_key(key(KeyCode::Enter, KeyModifiers::NONE)),
+            Action::FindWindow("logs".into())
+        );
+        assert_eq!(input.mode(), &InputMode::Normal);
+
+        // Esc and empty queries do nothing
+        input.start_find();
+        input.handle_key(key(KeyCode::Char('a'), KeyModifiers::NONE));
+        assert_eq!(input.handle_key(key(KeyCode::Esc, KeyModifiers::NONE)), Action::None);
+        assert_eq!(input.text_buffer(), "");
+        input.start_find();
+        assert_eq!(input.handle_key(key(KeyCode::Enter, KeyModifiers::NONE)), Action::None);
+        assert_eq!(input.mode(), &InputMode::Normal);
+    }
+
+    #[test]
+    fn test_search_prompt_and_match_keys() {
+        let mut input = InputHandler::new();
+        input.handle_key(key(KeyCode::Char('b'), KeyModifiers::CONTROL));
+        input.handle_key(key(KeyCode::Char('/'), KeyModifiers::NONE));
+        assert!(input.is_searching());
+        for c in "a b".chars() {
+            input.handle_key(key(KeyCode::Char(c), KeyModifiers::NONE));
+        }
+        assert_eq!(
+            input.handle_key(key(KeyCode::Enter, KeyModifiers::NONE)),
+            Action::Search("a b".into())
+        );
+
+        // Once matches are shown, n/N step through them in scroll mode
+        input.enter_scroll();
+        assert_eq!(input.handle_key(key(KeyCode::Char('n'), KeyModifiers::NONE)), Action::SearchNext);
+        assert_eq!(input.handle_key(key(KeyCode::Char('N'), KeyModifiers::SHIFT)), Action::SearchPrev);
+        assert_eq!(input.handle_key(key(KeyCode::Esc, KeyModifiers::NONE)), Action::ExitScrollMode);
+    }
+
+    #[test]
+    fn test_prompt_jump_keys() {
+        let mut input = InputHandler::new();
+        input.handle_key(key(KeyCode::Char('b'), KeyModifiers::CONTROL));
+        assert_eq!(input.handle_key(key(KeyCode::Char('{'), KeyModifiers::SHIFT)), Action::PrevPrompt);
+        input.handle_key(key(KeyCode::Char('b'), KeyModifiers::CONTROL));
+        assert_eq!(input.handle_key(key(KeyCode::Char('}'), KeyModifiers::SHIFT)), Action::NextPrompt);
+
+        // And without the prefix once scrolling
+        enter_scroll_mode(&mut input);
+        assert_eq!(input.handle_key(key(KeyCode::Char('{'), KeyModifiers::SHIFT)), Action::PrevPrompt);
+        assert_eq!(input.handle_key(key(KeyCode::Char('}'), KeyModifiers::SHIFT)), Action::NextPrompt);
+    }
+
+    #[test]
+    fn test_broadcast_toggle_key() {
+        let mut input = InputHandler::new();
+        input.handle_key(key(KeyCode::Char('b'), KeyModifiers::CONTROL));
+        assert_eq!(input.handle_key(key(KeyCode::Char('*'), KeyModifiers::SHIFT)), Action::ToggleBroadcast);
+        input.handle_key(key(KeyCode::Char('b'), KeyModifiers::CONTROL));
+        assert_eq!(input.handle_key(key(KeyCode::Char('='), KeyModifiers::NONE)), Action::ToggleSyncPanes);
+    }
+
+    #[test]
+    fn test_flip_sidebar_key() {
+        let mut input = InputHandler::new();
+        input.handle_key(key(KeyCode::Char('b'), KeyModifiers::CONTROL));
+        assert_eq!(input.handle_key(key(KeyCode::Char('|'), KeyModifiers::SHIFT)), Action::FlipSidebar);
+    }
+
+    #[test]
+    fn test_renumber_windows_key() {
+        let mut input = InputHandler::new();
+        input.handle_key(key(KeyCode::Char('b'), KeyModifiers::CONTROL));
+        assert_eq!(input.handle_key(key(KeyCode::Char('r'), KeyModifiers::NONE)), Action::RenumberWindows);
+    }
+
+    #[test]
+    fn test_move_tab_keys() {
+        let mut input = InputHandler::new();
+        for (code, modifiers, action) in [
+            (KeyCode::Char('<'), KeyModifiers::SHIFT, Action::MoveTabLeft),
+            (KeyCode::Char('>'), KeyModifiers::SHIFT, Action::MoveTabRight),
+            (KeyCode::Left, KeyModifiers::SHIFT, Action::MoveTabLeft),
+            (KeyCode::Right, KeyModifiers::SHIFT, Action::MoveTabRight),
+        ] {
+            input.handle_key(key(KeyCode::Char('b'), KeyModifiers::CONTROL));
+            assert_eq!(input.handle_key(key(code, modifiers)), action);
+        }
+        // Ctrl/Alt-arrows still resize
+        input.handle_key(key(KeyCode::Char('b'), KeyModifiers::CONTROL));
+        assert_eq!(
+            input.handle_key(key(KeyCode::Left, KeyModifiers::CONTROL | KeyModifiers::SHIFT)),
+            Action::ResizePane(Direction::Left, 1)
+        );
+    }
+
+    #[test]
+    fn test_sort_by_activity_key() {
+        let mut input = InputHandler::new();
+        input.handle_key(key(KeyCode::Char('b'), KeyModifiers::CONTROL));
+        assert_eq!(input.handle_key(key(KeyCode::Char('T'), KeyModifiers::SHIFT)), Action::ToggleSortByActivity);
+    }
+
+    #[test]
+    fn test_debug_overlay_key() {
+        let mut input = InputHandler::new();
+        input.handle_key(key(KeyCode::Char('b'), KeyModifiers::CONTROL));
+        assert_eq!(input.handle_key(key(KeyCode::Char('~'), KeyModifiers::SHIFT)), Action::ToggleDebugOverlay);
+    }
+
+    #[test]
+    fn test_escape_and_alt_keys() {
+        let mut input = InputHandler::new();
+        assert_eq!(input.handle_key(key(KeyCode::Esc, KeyModifiers::NONE)), Action::None);
+        let deadline = input.escape_deadline().unwrap();
+        assert_eq!(input.take_stale_escape(None, deadline), Some(Action::SendEscape));
+        assert_eq!(input.escape_deadline(), None);
+        assert_eq!(input.handle_key(key(KeyCode::Char('x'), KeyModifiers::ALT)), Action::SendKey("M-x".into()));
+        assert_eq!(input.handle_key(key(KeyCode::Esc, KeyModifiers::ALT)), Action::SendKey("M-Escape".into()));
+
+        // The explicit path, which also drops out of prefix mode
+        input.handle_key(key(KeyCode::Char('b'), KeyModifiers::CONTROL));
+        assert_eq!(input.handle_key(key(KeyCode::Esc, KeyModifiers::NONE)), Action::SendEscape);
+        assert_eq!(input.mode(), &InputMode::Normal);
+    }
+
+    #[test]
+    fn test_escape_then_char_is_alt() {
+        let mut input = InputHandler::new();
+        let x = key(KeyCode::Char('x'), KeyModifiers::NONE);
+        assert_eq!(input.handle_key(key(KeyCode::Esc, KeyModifiers::NONE)), Action::None);
+        assert_eq!(input.take_stale_escape(Some(&x), Instant::now()), None);
+        assert_eq!(input.handle_key(x), Action::SendKey("M-x".into()));
+        assert_eq!(input.escape_deadline(), None);
+
+        // Characters tmux would parse as syntax still pair with the Esc;
+        // send_keys quotes them
+        for c in [';', ' '] {
+            input.handle_key(key(KeyCode::Esc, KeyModifiers::NONE));
+            assert_eq!(input.handle_key(key(KeyCode::Char(c), KeyModifiers::NONE)), Action::SendKey(format!("M-{}", c)));
+        }
+
+        // Too slow: the Esc goes on its own and the character as itself
+        input.handle_key(key(KeyCode::Esc, KeyModifiers::NONE));
+        let late = input.escape_deadline().unwrap();
+        assert_eq!(input.take_stale_escape(Some(&x), late), Some(Action::SendEscape));
+        assert_eq!(input.handle_key(x), Action::SendText("x".into()));
+
+        // A non-character key releases the Esc first
+        input.handle_key(key(KeyCode::Esc, KeyModifiers::NONE));
+        let up = key(KeyCode::Up, KeyModifiers::NONE);
+        assert_eq!(input.take_stale_escape(Some(&up), Instant::now()), Some(Action::SendEscape));
+        assert_eq!(input.handle_key(up), Action::SendKey("Up".into()));
+
+        // So does any other event, however soon it comes
+        input.handle_key(key(KeyCode::Esc, KeyModifiers::NONE));
+        assert_eq!(input.take_stale_escape(None, Instant::now()), Some(Action::SendEscape));
+        assert_eq!(input.escape_deadline(), None);
+    }
+
+    #[test]
+    fn test_kitty_keyboard_encoding() {
+        let mut input = InputHandler::new();
+        input.set_kitty_keyboard(1);
+        assert_eq!(
+            input.handle_key(key(KeyCode::Char('i'), KeyModifiers::CONTROL)),
+            Action::SendText("\x1b[105;5u".into())
+        );
+        assert_eq!(input.handle_key(key(KeyCode::Esc, KeyModifiers::NONE)), Action::SendText("\x1b[27u".into()));
+        assert_eq!(input.escape_deadline(), None);
+        assert_eq!(input.handle_key(key(KeyCode::Char('a'), KeyModifiers::NONE)), Action::SendText("a".into()));
+
+        // The prefix key still belongs to helmux
+        assert_eq!(input.handle_key(key(KeyCode::Char('b'), KeyModifiers::CONTROL)), Action::None);
+        assert_eq!(input.mode(), &InputMode::Prefix);
+
+        input.set_kitty_keyboard(0);
+        input.handle_key(key(KeyCode::Esc, KeyModifiers::NONE));
+        assert_eq!(input.handle_key(key(KeyCode::Char('i'), KeyModifiers::CONTROL)), Action::SendKey("C-i".into()));
+    }
+
+    #[test]
+    fn test_kill_session_needs_confirmation() {
+        let mut input = InputHandler::new();
+        input.handle_key(key(KeyCode::Char('b'), KeyModifiers::CONTROL));
+        assert_eq!(input.handle_key(key(KeyCode::Char('X'), KeyModifiers::SHIFT)), Action::None);
+        assert_eq!(input.confirm_prompt(), Some("Kill session and all its windows? (y/n)"));
+        assert_eq!(
+            input.handle_key(key(KeyCode::Char('y'), KeyModifiers::NONE)),
+            Action::KillSession
+        );
+
+        input.handle_key(key(KeyCode::Char('b'), KeyModifiers::CONTROL));
+        input.handle_key(key(KeyCode::Char('X'), KeyModifiers::SHIFT));
+        assert_eq!(input.handle_key(key(KeyCode::Char('n'), KeyModifiers::NONE)), Action::None);
+        assert_eq!(input.mode(), &InputMode::Normal);
+    }
+
+    #[test]
+    fn test_new_named_tab_prompt() {
+        let mut input = InputHandler::new();
+        input.handle_key(key(KeyCode::Char('b'), KeyModifiers::CONTROL));
+        assert_eq!(input.handle_key(key(KeyCode::Char('C'), KeyModifiers::SHIFT)), Action::None);
+        assert!(input.is_naming_new_tab());
+        for c in "logs".chars() {
+            input.handle_key(key(KeyCode::Char(c), KeyModifiers::NONE));
+        }
+        assert_eq!(
+            input.handle_key(key(KeyCode::Enter, KeyModifiers::NONE)),
+            Action::NewNamedTab("logs".to_string())
+        );
+        assert_eq!(input.mode(), &InputMode::Normal);
+
+        // Esc creates nothing
+        input.handle_key(key(KeyCode::Char('b'), KeyModifiers::CONTROL));
+        input.handle_key(key(KeyCode::Char('C'), KeyModifiers::SHIFT));
+        input.handle_key(key(KeyCode::Char('x'), KeyModifiers::NONE));
+        assert_eq!(input.handle_key(key(KeyCode::Esc, KeyModifiers::NONE)), Action::None);
+        assert_eq!(input.mode(), &InputMode::Normal);
+        assert_eq!(input.text_buffer(), "");
+    }
+
+    #[test]
+    fn test_respawn_key() {
+        let mut input = InputHandler::new();
+        input.handle_key(key(KeyCode::Char('b'), KeyModifiers::CONTROL));
+        assert_eq!(input.handle_key(key(KeyCode::Char('R'), KeyModifiers::SHIFT)), Action::RespawnWindow);
+    }
+
+    #[test]
+    fn test_copy_screen_key() {
+        let mut input = InputHandler::new();
+        input.handle_key(key(KeyCode::Char('b'), KeyModifiers::CONTROL));
+        assert_eq!(input.handle_key(key(KeyCode::Char('y'), KeyModifiers::NONE)), Action::CopyScreen);
+    }
 }
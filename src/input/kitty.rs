@@ -0,0 +1,100 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+/// kitty keyboard protocol flag: disambiguate escape codes
+pub const DISAMBIGUATE: u16 = 1;
+
+/// Encode a key the way a terminal does once a program has asked for the kitty
+/// keyboard protocol's "disambiguate" mode: `CSI code ; modifiers u`
+/// Only keys the legacy encoding makes ambiguous are encoded (Esc, modified
+/// characters, and Enter/Tab/Backspace with modifiers); None means the key
+/// goes the usual way
+pub fn encode(key: &KeyEvent) -> Option<String> {
+    let mut modifiers = key.modifiers;
+    let code = match key.code {
+        KeyCode::Esc => 27,
+        KeyCode::Enter if !modifiers.is_empty() => 13,
+        KeyCode::Tab if !modifiers.is_empty() => 9,
+        KeyCode::BackTab => {
+            modifiers |= KeyModifiers::SHIFT;
+            9
+        }
+        KeyCode::Backspace if !modifiers.is_empty() => 127,
+        KeyCode::Char(c)
+            if modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT | KeyModifiers::SUPER) =>
+        {
+            // Keys are reported unshifted, with shift as a modifier. Only letters
+            // can be unshifted without knowing the keyboard layout, so other
+            // characters are sent as typed, shift already applied
+            if c.is_alphabetic() {
+                c.to_lowercase().next().unwrap_or(c) as u32
+            } else {
+                modifiers.remove(KeyModifiers::SHIFT);
+                c as u32
+            }
+        }
+        _ => return None,
+    };
+
+    let bits = modifier_bits(modifiers);
+    if bits == 0 {
+        Some(format!("\x1b[{}u", code))
+    } else {
+        Some(format!("\x1b[{};{}u", code, bits + 1))
+    }
+}
+
+/// kitty's modifier bit field: shift 1, alt 2, ctrl 4, super 8
+fn modifier_bits(modifiers: KeyModifiers) -> u8 {
+    let mut bits = 0;
+    if modifiers.contains(KeyModifiers::SHIFT) {
+        bits |= 1;
+    }
+    if modifiers.contains(KeyModifiers::ALT) {
+        bits |= 2;
+    }
+    if modifiers.contains(KeyModifiers::CONTROL) {
+        bits |= 4;
+    }
+    if modifiers.contains(KeyModifiers::SUPER) {
+        bits |= 8;
+    }
+    bits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(code: KeyCode, modifiers: KeyModifiers) -> KeyEvent {
+        KeyEvent::new(code, modifiers)
+    }
+
+    #[test]
+    fn test_encode_ambiguous_keys() {
+        assert_eq!(encode(&key(KeyCode::Esc, KeyModifiers::NONE)).as_deref(), Some("\x1b[27u"));
+        // Ctrl-I and Tab are no longer the same key
+        assert_eq!(encode(&key(KeyCode::Char('i'), KeyModifiers::CONTROL)).as_deref(), Some("\x1b[105;5u"));
+        assert_eq!(encode(&key(KeyCode::Tab, KeyModifiers::NONE)), None);
+        assert_eq!(encode(&key(KeyCode::Tab, KeyModifiers::CONTROL)).as_deref(), Some("\x1b[9;5u"));
+        assert_eq!(encode(&key(KeyCode::BackTab, KeyModifiers::SHIFT)).as_deref(), Some("\x1b[9;2u"));
+        assert_eq!(encode(&key(KeyCode::Enter, KeyModifiers::SHIFT)).as_deref(), Some("\x1b[13;2u"));
+    }
+
+    #[test]
+    fn test_encode_modified_characters() {
+        assert_eq!(encode(&key(KeyCode::Char('x'), KeyModifiers::ALT)).as_deref(), Some("\x1b[120;3u"));
+        assert_eq!(
+            encode(&key(KeyCode::Char('A'), KeyModifiers::CONTROL | KeyModifiers::SHIFT)).as_deref(),
+            Some("\x1b[97;6u")
+        );
+        // Shifted symbols keep their character and drop the shift
+        assert_eq!(
+            encode(&key(KeyCode::Char('!'), KeyModifiers::CONTROL | KeyModifiers::SHIFT)).as_deref(),
+            Some("\x1b[33;5u")
+        );
+        // Plain typing and keys the legacy encoding gets right are left alone
+        assert_eq!(encode(&key(KeyCode::Char('a'), KeyModifiers::NONE)), None);
+        assert_eq!(encode(&key(KeyCode::Char('A'), KeyModifiers::SHIFT)), None);
+        assert_eq!(encode(&key(KeyCode::Up, KeyModifiers::CONTROL)), None);
+    }
+}
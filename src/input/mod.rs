@@ -1,5 +1,8 @@
 mod action;
+mod click;
 mod handler;
+pub mod kitty;
 
-pub use action::Action;
+pub use action::{Action, ScrollAmount};
+pub use click::{ClickCount, ClickTracker};
 pub use handler::{InputHandler, InputMode};
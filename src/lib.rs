@@ -0,0 +1,16 @@
+// The helmux binary is built on these modules; they're a library so the
+// integration tests in tests/ can drive a real tmux, and so the terminal
+// emulator and control mode parser can be used on their own.
+
+pub mod app;
+pub mod clipboard;
+pub mod config;
+pub mod input;
+pub mod opener;
+pub mod screenshot;
+pub mod terminal;
+pub mod tmux;
+pub mod ui;
+
+pub use terminal::{Cell, CellAttributes, TerminalBuffer};
+pub use tmux::{Notification, ProtocolError};
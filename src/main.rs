@@ -1,30 +1,45 @@
-mod app;
-mod input;
-mod terminal;
-mod tmux;
-mod ui;
-
 use std::fs::OpenOptions;
 use std::io::{self, stdout, Write as IoWrite};
 use std::time::{Duration, Instant};
 
+use anyhow::Context;
 use crossterm::{
-    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, MouseButton, MouseEventKind},
+    event::{
+        DisableFocusChange, DisableMouseCapture, EnableFocusChange, EnableMouseCapture, Event,
+        EventStream, KeyCode, KeyModifiers, MouseButton, MouseEventKind,
+    },
     execute,
     terminal::{
         disable_raw_mode, enable_raw_mode, Clear, ClearType, EnterAlternateScreen,
         LeaveAlternateScreen,
     },
 };
-use ratatui::{backend::CrosstermBackend, Terminal};
+use futures::stream::{Peekable, Stream, StreamExt};
+use ratatui::{
+    backend::{Backend, CrosstermBackend},
+    Terminal,
+};
 
-use app::App;
-use input::{Action, InputHandler, InputMode};
-use tmux::{Commands, TmuxConnection, TmuxEvent};
-use ui::{is_new_tab_button, row_to_tab_index, HitRegion, Layout, RenameOverlay, Sidebar, SidebarMode, Viewport};
+use helmux::app::{App, CommandKind};
+use helmux::clipboard;
+use helmux::config::{Config, SidebarPosition};
+use helmux::input::{Action, ClickCount, ClickTracker, InputHandler, InputMode, ScrollAmount};
+use helmux::opener;
+use helmux::screenshot;
+use helmux::terminal::TerminalBuffer;
+use helmux::tmux::{
+    Commands, ConnectionError, ExitPolicy, Placement, TmuxClient, TmuxConnection, TmuxEvent, TmuxTarget,
+};
+use helmux::ui::{
+    is_new_tab_button, row_to_tab_index, ColorDepth, DebugOverlay, FramePacer, HitRegion, Layout, RenameOverlay,
+    Sidebar, SidebarMode, Splash, StatusLine, Viewport,
+};
 
 const DEFAULT_SESSION: &str = "helmux-default";
 const DEBUG_LOG: &str = "/tmp/helmux-debug.log";
+const CONNECTING_MESSAGE: &str = "Connecting to tmux…";
+/// Most tmux events handled in a row before redrawing and checking for input
+const MAX_TMUX_EVENTS_PER_FRAME: usize = 256;
 
 fn log_debug(msg: &str) {
     if let Ok(mut file) = OpenOptions::new().create(true).append(true).open(DEBUG_LOG) {
@@ -38,20 +53,24 @@ async fn main() -> anyhow::Result<()> {
     let _ = std::fs::write(DEBUG_LOG, "");
     log_debug("=== helmux starting ===");
 
+    // Load config and check for tmux before touching the terminal so errors print normally
+    let config = Config::load()?;
+    TmuxConnection::check_installed()?;
+
     // Set up terminal
     enable_raw_mode()?;
     let mut stdout = stdout();
-    execute!(stdout, EnterAlternateScreen, EnableMouseCapture, Clear(ClearType::All))?;
+    execute!(stdout, EnterAlternateScreen, EnableMouseCapture, EnableFocusChange, Clear(ClearType::All))?;
     let backend = CrosstermBackend::new(stdout);
     let mut term = Terminal::new(backend)?;
     term.clear()?;
 
     // Run the app and capture result
-    let result = run_app(&mut term).await;
+    let result = run_app(&mut term, &config).await;
 
     // Restore terminal
     disable_raw_mode()?;
-    execute!(term.backend_mut(), DisableMouseCapture, LeaveAlternateScreen)?;
+    execute!(term.backend_mut(), DisableMouseCapture, DisableFocusChange, LeaveAlternateScreen)?;
     term.show_cursor()?;
 
     log_debug("=== helmux exiting ===");
@@ -63,137 +82,306 @@ async fn main() -> anyhow::Result<()> {
     result
 }
 
-async fn run_app(term: &mut Terminal<CrosstermBackend<io::Stdout>>) -> anyhow::Result<()> {
+async fn run_app(
+    term: &mut Terminal<CrosstermBackend<io::Stdout>>,
+    config: &Config,
+) -> anyhow::Result<()> {
     // Get terminal size and create layout
     let size = term.size()?;
     let area = ratatui::layout::Rect::new(0, 0, size.width, size.height);
-    let mut layout = Layout::new(area);
+    let mut layout = Layout::new(area).with_sidebar_left(config.sidebar.position == SidebarPosition::Left);
     let (vp_width, vp_height) = layout.tmux_size();
+    let color_depth = ColorDepth::detect();
 
     // Connect to tmux
     let mut tmux = TmuxConnection::connect(DEFAULT_SESSION).await?;
 
     // Set tmux client size to match viewport (not full terminal)
-    tmux.send_command(&Commands::refresh_client_size(vp_width, vp_height))
+    tmux.send_command(&Commands::refresh_client_size(vp_width, vp_height, tmux.tmux_version()))
         .await?;
 
     // Create app state and input handler
-    let mut app = App::new(vp_width, vp_height);
-    let mut input = InputHandler::new();
-
-    // Double-click tracking for tab rename
-    let mut last_tab_click: Option<(usize, Instant)> = None;
-    const DOUBLE_CLICK_MS: u128 = 400;
+    let mut app = App::new(vp_width, vp_height)
+        .with_activity_grace(config.tabs.activity_grace())
+        .with_palette(config.appearance.palette)
+        .with_new_tab_in_cwd(config.tabs.new_tab_in_cwd)
+        .with_new_tab_placement(config.tabs.new_tab_placement())
+        .with_wrap_tab_moves(config.tabs.wrap_tab_moves)
+        .with_sort_by_activity(config.sidebar.sort_by_activity)
+        .with_word_chars(&config.mouse.word_chars);
+    let mut input = InputHandler::new().with_confirm_kill(config.tabs.confirm_kill);
+
+    // Double-click tracking for tab rename, and word/line selection in the viewport
+    let mut clicks = MouseClicks::new(config.mouse.double_click_timeout());
+
+    // Have tmux track activity itself, so tabs whose output helmux never saw
+    // still get flagged through the window list. The old setting is read
+    // first (responses come back in order) so it can be put back on exit
+    let query = Commands::show_window_option("monitor-activity");
+    app.send_tracked(&mut tmux, &query, CommandKind::ShowOption { name: "monitor-activity".to_string() })
+        .await?;
+    tmux.send_command(&Commands::monitor_activity(true)).await?;
 
-    // Query initial window list
+    // Query initial window list and tmux's mouse option
     app.sync_from_tmux(&mut tmux).await?;
+    let query = Commands::show_option("mouse", true);
+    app.send_tracked(&mut tmux, &query, CommandKind::ShowOption { name: "mouse".to_string() })
+        .await?;
 
     // Initial render (empty until we get window list)
-    render(term, &layout, &app, &input)?;
+    let mut pacer = FramePacer::new(config.display.max_fps);
+    pacer.ready(Instant::now());
+    app.record_frame(render(term, &layout, &app, &input, config, color_depth, tmux.last_command())?);
+
+    let mut terminal_events = EventStream::new().peekable();
 
     loop {
-        // Poll for terminal events with a short timeout
-        let has_event = event::poll(Duration::from_millis(10))?;
-
-        if has_event {
-            match event::read()? {
-                Event::Key(key) => {
-                    // Special handling for Enter in rename mode
-                    if input.is_renaming() && key.code == KeyCode::Enter {
-                        let new_name = input.finish_rename();
-                        if let Some(window_id) = app.active_window_id() {
-                            if new_name.trim().is_empty() {
-                                // Empty name - enable automatic rename (shows running process)
-                                tmux.send_command(&Commands::enable_automatic_rename(window_id))
-                                    .await?;
-                            } else {
-                                tmux.send_command(&Commands::rename_window(window_id, &new_name))
-                                    .await?;
-                            }
-                        }
-                        render(term, &layout, &app, &input)?;
-                        continue;
-                    }
+        // Send everything queued since the last wakeup in one write, including
+        // at most one window list for all the notifications just handled (or
+        // for the periodic re-read of tmux's activity flags)
+        app.poll_activity(Instant::now());
+        app.send_pending_resync(&mut tmux).await?;
+        tmux.flush().await?;
+
+        // Sleep until a terminal event, a tmux event, or a timed redraw (also
+        // used to send a held-back Esc once nothing followed it, to draw a
+        // frame the pacer held back, and to poll tmux's activity flags)
+        let wake_at = [
+            app.status_deadline(),
+            input.escape_deadline(),
+            pacer.deadline(),
+            app.activity_poll_deadline(),
+        ]
+            .into_iter()
+            .flatten()
+            .min();
+        let event = next_loop_event(&mut terminal_events, &mut tmux, wake_at).await?;
+
+        // A held-back Esc can only combine with the next key; anything else the
+        // terminal sends (a click, a paste, a resize) releases it first
+        if matches!(&event, LoopEvent::Terminal(e) if !matches!(e, Event::Key(_))) {
+            if let Some(action) = input.take_stale_escape(None, Instant::now()) {
+                handle_action(action, &mut app, &mut tmux, &mut input, &mut layout).await?;
+            }
+        }
 
-                    // Handle key through input handler
-                    let action = input.handle_key(key);
+        match event {
+            LoopEvent::Terminal(Event::Key(key)) => {
+                // An Esc that this key can't turn into an Alt combination goes first
+                if let Some(action) = input.take_stale_escape(Some(&key), Instant::now()) {
+                    handle_action(action, &mut app, &mut tmux, &mut input, &mut layout).await?;
+                }
 
-                    match handle_action(action, &mut app, &mut tmux, &mut input, &mut layout)
-                        .await?
-                    {
-                        LoopAction::Continue => {}
-                        LoopAction::Exit => break,
+                // Special handling for Enter in rename mode
+                if input.is_renaming() && key.code == KeyCode::Enter {
+                    let new_name = input.finish_rename();
+                    if let Some(window_id) = app.active_window_id().map(str::to_string) {
+                        let target = TmuxTarget::new(&window_id)?;
+                        if new_name.trim().is_empty() {
+                            // Empty name - enable automatic rename (shows running process)
+                            tmux.send_command(&Commands::enable_automatic_rename(target))
+                                .await?;
+                            app.set_auto_rename(&window_id, true);
+                        } else {
+                            // tmux turns automatic-rename off for explicitly named windows
+                            tmux.send_command(&Commands::rename_window(target, &new_name))
+                                .await?;
+                            app.set_auto_rename(&window_id, false);
+                        }
                     }
+                    app.record_frame(render(term, &layout, &app, &input, config, color_depth, tmux.last_command())?);
+                    continue;
                 }
-                Event::Resize(w, h) => {
-                    // Update layout with new size
-                    layout.set_area(ratatui::layout::Rect::new(0, 0, w, h));
-                    let (vp_width, vp_height) = layout.tmux_size();
-                    // Update tmux client size to match viewport
-                    tmux.send_command(&Commands::refresh_client_size(vp_width, vp_height))
-                        .await?;
-                    // Resize all tab buffers
-                    app.resize(vp_width, vp_height);
+
+                // Handle key through input handler, encoding it the way the
+                // active pane's program asked for
+                let kitty_flags = app.active_tab().map_or(0, |tab| tab.buffer.kitty_keyboard_flags());
+                input.set_kitty_keyboard(kitty_flags);
+                let action = input.handle_key(key);
+
+                match handle_action(action, &mut app, &mut tmux, &mut input, &mut layout)
+                    .await?
+                {
+                    LoopAction::Continue => {}
+                    LoopAction::Exit => break,
                 }
-                Event::Mouse(mouse) => {
-                    // In rename mode, clicking anywhere cancels the rename
-                    if input.is_renaming() {
-                        if let MouseEventKind::Down(MouseButton::Left) = mouse.kind {
-                            input.cancel_rename();
-                        }
-                        continue;
+            }
+            LoopEvent::Terminal(Event::Resize(w, h)) => {
+                // Update layout with new size
+                layout.set_area(ratatui::layout::Rect::new(0, 0, w, h));
+                resize_to_layout(&layout, &mut app, &mut tmux).await?;
+            }
+            LoopEvent::Terminal(Event::Mouse(mouse)) => {
+                // While a prompt is open, clicking anywhere cancels it
+                if input.is_prompting() {
+                    if click_cancels_prompt(&mouse, &layout, &input, &clicks, Instant::now()) {
+                        input.cancel_prompt();
                     }
+                    continue;
+                }
 
-                    let click_result = handle_mouse_event(
-                        mouse,
-                        &mut app,
-                        &mut tmux,
-                        &layout,
-                        &input,
-                        &mut last_tab_click,
-                        DOUBLE_CLICK_MS,
-                    ).await?;
-
-                    // If double-click detected, start rename
-                    if click_result.start_rename {
-                        if let Some(tab) = app.active_tab() {
-                            input.start_rename(&tab.name);
-                        }
-                    }
+                let action = handle_mouse_event(
+                    mouse,
+                    &mut app,
+                    &mut tmux,
+                    &layout,
+                    &mut input,
+                    &mut clicks,
+                ).await?;
+
+                match handle_action(action, &mut app, &mut tmux, &mut input, &mut layout)
+                    .await?
+                {
+                    LoopAction::Continue => {}
+                    LoopAction::Exit => break,
                 }
-                _ => {}
             }
-        }
-
-        // Check for tmux events (non-blocking)
-        match tokio::time::timeout(Duration::from_millis(1), tmux.next_event()).await {
-            Ok(Ok(event)) => {
-                handle_tmux_event(event, &mut app, &mut tmux).await?;
+            LoopEvent::Terminal(Event::FocusGained) => {
+                app.set_terminal_focused(true);
+                report_focus(true, &app, &mut tmux).await?;
+            }
+            LoopEvent::Terminal(Event::FocusLost) => {
+                app.set_terminal_focused(false);
+                report_focus(false, &app, &mut tmux).await?;
+            }
+            LoopEvent::Terminal(_) => {}
+            LoopEvent::Tmux(events) => {
+                app.record_events(events.len());
+                for event in events {
+                    handle_tmux_event(event, &mut app)?;
+                }
             }
-            Ok(Err(e)) => {
+            LoopEvent::TmuxClosed(e) => {
                 log_debug(&format!("Connection error: {}", e));
                 break;
             }
-            Err(_) => {
-                // Timeout - no tmux event, continue
+            LoopEvent::Redraw => {
+                if let Some(action) = input.take_stale_escape(None, Instant::now()) {
+                    handle_action(action, &mut app, &mut tmux, &mut input, &mut layout).await?;
+                }
             }
         }
 
-        // Render
-        render(term, &layout, &app, &input)?;
+        if let Some(text) = app.take_clipboard() {
+            clipboard::copy(&mut stdout(), &text)?;
+        }
+        finish_respawn(&mut app, &mut tmux, &mut input).await?;
+
+        // Render, unless the last frame was too recent
+        if pacer.ready(Instant::now()) {
+            app.record_frame(render(term, &layout, &app, &input, config, color_depth, tmux.last_command())?);
+        }
     }
 
+    // Make sure a final detach reaches tmux; the connection may already be gone
+    let _ = tmux.flush().await;
+
     Ok(())
 }
 
-/// Render the UI
-fn render(
-    term: &mut Terminal<CrosstermBackend<io::Stdout>>,
+/// Something the main loop has to react to
+#[derive(Debug)]
+enum LoopEvent {
+    /// Input or resize from the terminal
+    Terminal(Event),
+    /// Notifications and command responses from tmux, handled before one redraw
+    Tmux(Vec<TmuxEvent>),
+    /// The tmux connection failed or closed
+    TmuxClosed(ConnectionError),
+    /// Nothing happened, but a deadline passed (a status message expired, or a
+    /// held-back Esc is due)
+    Redraw,
+}
+
+/// Terminal events (crossterm's `EventStream` outside of tests), peekable so a
+/// batch of tmux events can tell when input is waiting
+type TerminalEvents<S> = Peekable<S>;
+
+/// Wait for whichever comes first: a terminal event, a tmux event, or the redraw deadline
+/// Idles without waking up when none of them fire
+async fn next_loop_event<S, T>(
+    terminal_events: &mut TerminalEvents<S>,
+    tmux: &mut T,
+    redraw_at: Option<Instant>,
+) -> anyhow::Result<LoopEvent>
+where
+    S: Stream<Item = io::Result<Event>> + Unpin,
+    T: TmuxClient,
+{
+    let deadline = tokio::time::Instant::from_std(redraw_at.unwrap_or_else(Instant::now));
+    let first = tokio::select! {
+        event = terminal_events.next() => return match event {
+            Some(event) => Ok(LoopEvent::Terminal(event?)),
+            None => anyhow::bail!("Terminal event stream ended"),
+        },
+        event = tmux.next_event() => match event {
+            Ok(event) => event,
+            Err(e) => return Ok(LoopEvent::TmuxClosed(e)),
+        },
+        _ = tokio::time::sleep_until(deadline), if redraw_at.is_some() => return Ok(LoopEvent::Redraw),
+    };
+    Ok(LoopEvent::Tmux(ready_tmux_events(first, tmux, terminal_events).await))
+}
+
+/// Collect the tmux events that are ready right away after `first`, so a pane
+/// flooding output is drawn once per batch instead of once per event
+/// A batch stops at MAX_TMUX_EVENTS_PER_FRAME events, or as soon as there's
+/// terminal input waiting, so typing stays responsive during a `cat bigfile`.
+/// A read error ends it too; the next read reports the error again
+async fn ready_tmux_events<S, T>(
+    first: TmuxEvent,
+    tmux: &mut T,
+    terminal_events: &mut TerminalEvents<S>,
+) -> Vec<TmuxEvent>
+where
+    S: Stream<Item = io::Result<Event>> + Unpin,
+    T: TmuxClient,
+{
+    let mut events = vec![first];
+    while events.len() < MAX_TMUX_EVENTS_PER_FRAME && !input_waiting(terminal_events).await {
+        // Reading is cancel safe, so giving up on an event that isn't ready loses nothing
+        match tokio::time::timeout(std::time::Duration::ZERO, tmux.next_event()).await {
+            Ok(Ok(event)) => events.push(event),
+            _ => break,
+        }
+    }
+    events
+}
+
+/// Whether a terminal event is ready to read; peeking leaves it for the loop,
+/// and a stream with nothing ready wakes this task once something arrives
+async fn input_waiting<S>(terminal_events: &mut TerminalEvents<S>) -> bool
+where
+    S: Stream<Item = io::Result<Event>> + Unpin,
+{
+    futures::poll!(std::pin::Pin::new(terminal_events).peek()).is_ready()
+}
+
+/// Tell the active pane that helmux's terminal gained or lost focus, if the
+/// program in it turned on focus reporting (CSI I / CSI O)
+async fn report_focus<T: TmuxClient>(gained: bool, app: &App, tmux: &mut T) -> anyhow::Result<()> {
+    if let Some(tab) = app.active_tab().filter(|tab| tab.buffer.focus_reporting()) {
+        let report: &[u8] = if gained { b"\x1b[I" } else { b"\x1b[O" };
+        let version = tmux.tmux_version();
+        tmux.send_command(&Commands::send_bytes(TmuxTarget::new(&tab.pane_id)?, report, version)).await?;
+    }
+    Ok(())
+}
+
+/// Render the UI, returning how many viewport cells were drawn
+fn render<B: Backend>(
+    term: &mut Terminal<B>,
     layout: &Layout,
     app: &App,
     input: &InputHandler,
-) -> anyhow::Result<()> {
+    config: &Config,
+    color_depth: ColorDepth,
+    last_command: Option<&str>,
+) -> anyhow::Result<u64> {
+    let _span = tracing::trace_span!("render").entered();
     let tabs = app.tab_infos();
+    let debug_rows = app.is_debug_overlay_shown().then(|| debug_rows(app, last_command));
+    let mut cells_drawn = 0;
 
     term.draw(|frame| {
         let sidebar_area = layout.sidebar_area();
@@ -204,80 +392,381 @@ fn render(
             InputMode::Normal => SidebarMode::Normal,
             InputMode::Prefix => SidebarMode::Prefix,
             InputMode::Rename => SidebarMode::Rename,
+            InputMode::NewTabName => SidebarMode::NewTab,
+            InputMode::Find => SidebarMode::Find,
+            InputMode::Search => SidebarMode::Search,
+            InputMode::Scroll => SidebarMode::Scroll,
+            InputMode::Confirm { .. } => SidebarMode::Confirm,
         };
 
-        frame.render_widget(Sidebar::new(&tabs).mode(sidebar_mode), sidebar_area);
+        frame.render_widget(
+            Sidebar::new(&tabs, &config.appearance)
+                .mode(sidebar_mode)
+                .broadcast(app.is_broadcasting())
+                .show_paths(config.sidebar.show_path)
+                .on_right(!layout.sidebar_left())
+                .collapsed(layout.is_sidebar_collapsed()),
+            sidebar_area,
+        );
 
         // Render the active tab's buffer
         if let Some(tab) = app.active_tab() {
-            frame.render_widget(Viewport::new(&tab.buffer), viewport_area);
+            frame.render_stateful_widget(
+                // The cursor is hollow while the terminal window is in the
+                // background, or typing goes to an open prompt instead
+                Viewport::new(&tab.buffer)
+                    .focused(app.terminal_focused() && !input.is_prompting())
+                    .search(app.search())
+                    .legacy_colors(config.appearance.legacy_color_fix)
+                    .color_depth(color_depth)
+                    .scroll_indicator(true)
+                    .scrollbar(config.display.scrollbar)
+                    .theme(&config.appearance),
+                viewport_area,
+                &mut cells_drawn,
+            );
+        } else {
+            // Until tmux sends the first window list there's nothing to show
+            frame.render_widget(Splash::new(CONNECTING_MESSAGE), viewport_area);
         }
 
-        // Render rename overlay if in rename mode
+        // Render a pending confirmation, or else the status message, over
+        // the bottom row of the viewport, with tmux's mouse setting at its end
+        let message = input.confirm_prompt().or(app.status_message());
+        let mouse = app.tmux_mouse().map(|on| if on { " mouse on " } else { " mouse off " });
+        if message.is_some() || mouse.is_some() {
+            frame.render_widget(
+                StatusLine::new(message.unwrap_or_default(), &config.appearance).indicator(mouse),
+                StatusLine::area(viewport_area),
+            );
+        }
+
+        if let Some(rows) = &debug_rows {
+            let overlay = DebugOverlay::new(rows, &config.appearance);
+            let area = overlay.area(viewport_area);
+            frame.render_widget(overlay, area);
+        }
+
+        // Render the rename or find prompt
         if input.is_renaming() {
             let overlay_area = RenameOverlay::centered_rect(frame.area());
-            frame.render_widget(RenameOverlay::new(input.rename_buffer()), overlay_area);
+            frame.render_widget(RenameOverlay::new(input.text_buffer(), &config.appearance), overlay_area);
+        } else if input.is_naming_new_tab() || input.is_finding() || input.is_searching() {
+            let title = if input.is_naming_new_tab() {
+                " New Tab Name "
+            } else if input.is_finding() {
+                " Find Window "
+            } else {
+                " Search "
+            };
+            let overlay_area = RenameOverlay::centered_rect(frame.area());
+            frame.render_widget(RenameOverlay::new(input.text_buffer(), &config.appearance).title(title), overlay_area);
         }
     })?;
 
-    Ok(())
+    Ok(cells_drawn)
+}
+
+/// A tmux command as the debug overlay shows it, with keys sent as hex bytes
+/// decoded back to (escaped) text
+fn describe_command(cmd: &str) -> String {
+    let Some((head, hex)) = cmd.split_once(" -H ") else {
+        return cmd.to_string();
+    };
+    let bytes: Option<Vec<u8>> = hex.split(' ').map(|byte| u8::from_str_radix(byte, 16).ok()).collect();
+    match bytes {
+        Some(bytes) => format!("{} {:?}", head, String::from_utf8_lossy(&bytes)),
+        None => cmd.to_string(),
+    }
+}
+
+/// Rows of the debug overlay: the active tab's terminal state, then counters
+fn debug_rows(app: &App, last_command: Option<&str>) -> Vec<(&'static str, String)> {
+    let mut rows = Vec::new();
+    if let Some(tab) = app.active_tab() {
+        let buffer = &tab.buffer;
+        let (row, col) = buffer.cursor();
+        let (top, bottom) = buffer.scroll_region();
+        let modes: Vec<&str> = [
+            (buffer.origin_mode(), "origin"),
+            (buffer.autowrap(), "autowrap"),
+            (buffer.insert_mode(), "insert"),
+            (buffer.application_cursor_keys(), "app-cursor"),
+            (buffer.bracketed_paste(), "paste"),
+        ]
+        .into_iter()
+        .filter_map(|(on, name)| on.then_some(name))
+        .collect();
+        rows.extend([
+            ("window", tab.window_id.clone()),
+            ("pane", tab.pane_id.clone()),
+            ("cursor", format!("{},{}", row, col)),
+            ("scroll region", format!("{}-{}", top, bottom)),
+            ("modes", if modes.is_empty() { "-".to_string() } else { modes.join(" ") }),
+            ("scrollback", buffer.scrollback_len().to_string()),
+        ]);
+    }
+    let stats = app.stats();
+    rows.extend([
+        ("last command", last_command.map_or("-".to_string(), describe_command)),
+        ("bytes parsed", stats.bytes_parsed.to_string()),
+        ("events", stats.events_handled.to_string()),
+        ("frames", stats.frames.to_string()),
+        ("cells drawn", stats.cells_drawn.to_string()),
+    ]);
+    rows
 }
 
 /// Result of handling an action
+#[derive(Debug, PartialEq, Eq)]
 enum LoopAction {
     Continue,
     Exit,
 }
 
+/// How an exiting action leaves the tmux session
+/// Quitting and detaching both leave it running; it's only killed when asked
+fn exit_policy(action: &Action) -> ExitPolicy {
+    match action {
+        Action::KillSession => ExitPolicy::KillSession,
+        _ => ExitPolicy::Detach,
+    }
+}
+
+/// Size tmux's client and every tab to the layout's viewport
+async fn resize_to_layout<T: TmuxClient>(layout: &Layout, app: &mut App, tmux: &mut T) -> anyhow::Result<()> {
+    let (width, height) = layout.tmux_size();
+    tmux.send_command(&Commands::refresh_client_size(width, height, tmux.tmux_version())).await?;
+    app.resize(width, height);
+    Ok(())
+}
+
+/// Open a new tab, starting in the active tab's directory and beside it if configured
+async fn open_new_tab<T: TmuxClient>(app: &mut App, tmux: &mut T, name: Option<&str>) -> anyhow::Result<()> {
+    // Let tmux expand the directory when it creates the window: the path from the
+    // last list-windows may be out of date if the shell has cd'd since
+    let dir = app.new_tab_in_cwd().then_some("#{pane_current_path}");
+    match (app.new_tab_placement(), app.active_window_id()) {
+        (Some(placement), Some(window_id)) => {
+            let target = TmuxTarget::new(window_id)?;
+            if placement == Placement::Before && tmux.tmux_version().is_some_and(|v| !v.supports_window_before()) {
+                // No -b before tmux 3.2: open it after the active tab, then swap
+                // the two (the new window is current, and stays so)
+                tmux.send_command(&Commands::new_window_placed(Placement::After, target, name, dir)).await?;
+                tmux.send_command(&Commands::swap_with_current(target)).await?;
+            } else {
+                tmux.send_command(&Commands::new_window_placed(placement, target, name, dir)).await?;
+            }
+            // Later windows were renumbered to make room; tmux has selected the
+            // new one, and the re-synced list puts it in its place as active
+            app.sync_from_tmux(tmux).await?;
+        }
+        _ => {
+            let cmd = match dir {
+                Some(dir) => Commands::new_window_in(name, dir),
+                None => Commands::new_window(name),
+            };
+            tmux.send_command(&cmd).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Restart a window once tmux has said whether its command exited: straight away
+/// if it has, or after confirming if it's still running
+async fn finish_respawn<T: TmuxClient>(app: &mut App, tmux: &mut T, input: &mut InputHandler) -> anyhow::Result<()> {
+    match app.take_respawn_check() {
+        Some((window_id, true)) => {
+            tmux.send_command(&Commands::respawn_window(TmuxTarget::new(&window_id)?)).await?;
+        }
+        Some((_, false)) => {
+            input.start_confirm(Action::RestartWindow, "Kill the running command and restart it? (y/n)");
+        }
+        None => {}
+    }
+    Ok(())
+}
+
 /// Handle an action from the input handler
-async fn handle_action(
+async fn handle_action<T: TmuxClient>(
     action: Action,
     app: &mut App,
-    tmux: &mut TmuxConnection,
+    tmux: &mut T,
     input: &mut InputHandler,
-    _layout: &mut Layout,
+    layout: &mut Layout,
 ) -> anyhow::Result<LoopAction> {
     match action {
         Action::None => {}
 
-        Action::Exit => {
+        Action::Exit | Action::Detach | Action::KillSession => {
+            // Leave tmux cleanly now, while the connection is certainly still up,
+            // undoing the monitor-activity setting turned on at startup
+            if app.saved_monitor_activity() == Some(false) {
+                tmux.send_command(&Commands::monitor_activity(false)).await?;
+            }
+            tmux.send_command(&exit_policy(&action).command()).await?;
             return Ok(LoopAction::Exit);
         }
 
         Action::NewTab => {
-            tmux.send_command(&Commands::new_window(None)).await?;
+            open_new_tab(app, tmux, None).await?;
+        }
+
+        Action::NewNamedTab(name) => {
+            open_new_tab(app, tmux, Some(&name)).await?;
+        }
+
+        Action::RespawnWindow => {
+            // Nothing tells us when a pane exits, so the last window list may be
+            // out of date: ask tmux, and let finish_respawn act on the answer
+            if let Some(window_id) = app.active_window_id().map(str::to_string) {
+                let query = Commands::pane_dead(TmuxTarget::new(&window_id)?);
+                app.send_tracked(tmux, &query, CommandKind::PaneDead { window_id }).await?;
+            }
+        }
+
+        Action::RestartWindow => {
+            if let Some(window_id) = app.active_window_id() {
+                tmux.send_command(&Commands::respawn_window(TmuxTarget::new(window_id)?)).await?;
+            }
         }
 
         Action::CloseTab => {
             if let Some(window_id) = app.active_window_id() {
-                tmux.send_command(&Commands::kill_window(window_id)).await?;
+                tmux.send_command(&Commands::kill_window(TmuxTarget::new(window_id)?)).await?;
+            }
+        }
+
+        Action::CloseWindow(window_id) => {
+            tmux.send_command(&Commands::kill_window(TmuxTarget::new(&window_id)?)).await?;
+        }
+
+        Action::CloseOtherTabs => {
+            if let Some(window_id) = app.active_window_id() {
+                tmux.send_command(&Commands::kill_other_windows(TmuxTarget::new(window_id)?)).await?;
+                app.sync_from_tmux(tmux).await?;
             }
         }
 
         Action::NextTab => {
             if let Some(window_id) = app.next_window_id() {
-                tmux.send_command(&Commands::select_window(window_id))
+                tmux.send_command(&Commands::select_window(TmuxTarget::new(window_id)?))
                     .await?;
             }
         }
 
+        Action::LastTab => {
+            if let Some(window_id) = app.last_window_id() {
+                tmux.send_command(&Commands::select_window(TmuxTarget::new(window_id)?))
+                    .await?;
+            }
+        }
+
+        Action::FindWindow(query) => {
+            match app.find_window(&query) {
+                Some(window_id) => {
+                    tmux.send_command(&Commands::select_window(TmuxTarget::new(window_id)?)).await?;
+                }
+                None => app.set_status(format!("No window matches \"{}\"", query)),
+            }
+        }
+
         Action::PrevTab => {
             if let Some(window_id) = app.prev_window_id() {
-                tmux.send_command(&Commands::select_window(window_id))
+                tmux.send_command(&Commands::select_window(TmuxTarget::new(window_id)?))
                     .await?;
             }
         }
 
-        Action::SelectTab(index) => {
-            if let Some(window_id) = app.window_id_by_index(index) {
-                tmux.send_command(&Commands::select_window(window_id))
+        Action::SelectTab(pos) => {
+            if let Some(window_id) = app.window_id_by_number(pos) {
+                tmux.send_command(&Commands::select_window(TmuxTarget::new(window_id)?))
                     .await?;
             }
         }
 
         Action::ToggleSidebar => {
-            // Will be implemented in Phase 9
-            // layout.toggle_sidebar();
+            layout.cycle_sidebar();
+            if layout.is_sidebar_hidden() {
+                app.set_status("Sidebar hidden; Ctrl-b b shows it");
+            }
+            resize_to_layout(layout, app, tmux).await?;
+        }
+
+        Action::FlipSidebar => {
+            layout.flip_sidebar();
+            // The viewport keeps its size but moves, so mouse positions change
+            resize_to_layout(layout, app, tmux).await?;
+        }
+
+        Action::ToggleAutoRename => {
+            if let Some(tab) = app.active_tab() {
+                let window_id = tab.window_id.clone();
+                let on = !tab.auto_rename;
+                let cmd = if on {
+                    Commands::enable_automatic_rename(TmuxTarget::new(&window_id)?)
+                } else {
+                    Commands::disable_automatic_rename(TmuxTarget::new(&window_id)?)
+                };
+                tmux.send_command(&cmd).await?;
+                app.set_auto_rename(&window_id, on);
+                app.set_status(format!("automatic rename {}", if on { "on" } else { "off" }));
+            }
+        }
+
+        Action::ToggleMark => {
+            app.toggle_mark();
+        }
+
+        Action::SwapWindow => {
+            if let (Some(active), Some(target)) = (app.active_window_id(), app.swap_target()) {
+                let (active, target) = (TmuxTarget::new(active)?, TmuxTarget::new(target)?);
+                tmux.send_command(&Commands::swap_window(active, target)).await?;
+                // Window order changed - re-sync the tab list
+                app.sync_from_tmux(tmux).await?;
+            }
+        }
+
+        Action::RenumberWindows => {
+            tmux.send_command(&Commands::renumber_windows()).await?;
+            // Indices changed - re-sync the tab list
+            app.sync_from_tmux(tmux).await?;
+        }
+
+        Action::MoveTabLeft | Action::MoveTabRight => {
+            let right = action == Action::MoveTabRight;
+            if let (Some(active), Some((neighbor, wrapped))) = (app.active_window_id(), app.neighbor_window_id(right)) {
+                let (active, neighbor) = (TmuxTarget::new(active)?, TmuxTarget::new(neighbor)?);
+                if !wrapped {
+                    tmux.send_command(&Commands::swap_window(active, neighbor)).await?;
+                } else if app.wraps_tab_moves() {
+                    // Past the end: go to the far side of the tab at the other end
+                    if right && tmux.tmux_version().is_some_and(|v| !v.supports_window_before()) {
+                        // No move-window -b before tmux 3.2: swap the last tab past
+                        // each of the others in turn, which keeps their order
+                        for other in app.window_ids().iter().rev().skip(1) {
+                            tmux.send_command(&Commands::swap_window(active, TmuxTarget::new(other)?)).await?;
+                        }
+                    } else {
+                        let placement = if right { Placement::Before } else { Placement::After };
+                        tmux.send_command(&Commands::move_window(active, placement, neighbor)).await?;
+                    }
+                } else {
+                    return Ok(LoopAction::Continue);
+                }
+                // Keep the moved tab focused, and pick up the new order
+                tmux.send_command(&Commands::select_window(active)).await?;
+                app.sync_from_tmux(tmux).await?;
+            }
+        }
+
+        Action::JoinMarked => {
+            if let (Some(src), Some(dst)) = (app.marked_pane_id(), app.active_window_id()) {
+                let (src, dst) = (TmuxTarget::new(src)?, TmuxTarget::new(dst)?);
+                tmux.send_command(&Commands::join_pane(src, dst)).await?;
+                // The pane has moved, so the mark no longer refers to it
+                app.clear_mark();
+            }
         }
 
         Action::StartRename => {
@@ -287,35 +776,207 @@ async fn handle_action(
             }
         }
 
-        Action::Detach => {
-            tmux.send_command(&Commands::detach()).await?;
-            return Ok(LoopAction::Exit);
+        Action::ResizePane(direction, amount) => {
+            if let Some(pane_id) = app.active_pane_id() {
+                tmux.send_command(&Commands::resize_pane(TmuxTarget::new(pane_id)?, direction, amount))
+                    .await?;
+            }
         }
 
-        Action::SendCtrlB => {
+        Action::ClearHistory => {
             if let Some(pane_id) = app.active_pane_id() {
-                tmux.send_command(&format!("send-keys -t {} C-b", pane_id))
+                tmux.send_command(&Commands::clear_history(TmuxTarget::new(pane_id)?)).await?;
+            }
+            // Match locally, snapping back to the live screen if scrolled up
+            if let Some(tab) = app.active_tab_mut() {
+                tab.buffer.clear_scrollback();
+            }
+            input.exit_scroll();
+        }
+
+        Action::ToggleMouse => {
+            let on = app.toggle_tmux_mouse();
+            let value = if on { "on" } else { "off" };
+            tmux.send_command(&Commands::set_option("mouse", value, true)).await?;
+            // Read the option back so the tracked state matches tmux
+            let query = Commands::show_option("mouse", true);
+            app.send_tracked(tmux, &query, CommandKind::ShowOption { name: "mouse".to_string() })
+                .await?;
+            app.set_status(format!("tmux mouse {}", value));
+        }
+
+        Action::ToggleSyncPanes => {
+            if let Some(tab) = app.active_tab() {
+                let window_id = tab.window_id.clone();
+                let on = !tab.synchronized;
+                tmux.send_command(&Commands::set_synchronize_panes(TmuxTarget::new(&window_id)?, on)).await?;
+                app.set_synchronized(&window_id, on);
+                app.set_status(format!("synchronize-panes {}", if on { "on" } else { "off" }));
+            }
+        }
+
+        Action::ToggleSortByActivity => {
+            if app.toggle_sort_by_activity() {
+                app.set_status("Tabs sorted by recent activity");
+            } else {
+                app.set_status("Tabs in tmux order");
+            }
+        }
+
+        Action::ToggleDebugOverlay => {
+            app.toggle_debug_overlay();
+        }
+
+        Action::ToggleBroadcast => {
+            if app.toggle_broadcast() {
+                app.set_status("Typing into all tabs");
+            } else {
+                app.set_status("Typing into the active tab only");
+            }
+        }
+
+        Action::Screenshot => {
+            if let Some(tab) = app.active_tab() {
+                let saved = screenshot::default_dir()
+                    .context("No data directory for screenshots")
+                    .and_then(|dir| screenshot::save(&dir, &screenshot::timestamp(), &tab.buffer));
+                match saved {
+                    Ok(path) => app.set_status(format!("Screenshot saved to {}", path.display())),
+                    Err(e) => {
+                        tracing::warn!("Screenshot failed: {:#}", e);
+                        app.set_status(format!("Screenshot failed: {:#}", e));
+                    }
+                }
+            }
+        }
+
+        Action::SendCtrlB => {
+            for pane_id in app.input_pane_ids() {
+                tmux.send_command(&Commands::send_keys(TmuxTarget::new(&pane_id)?, "C-b"))
                     .await?;
             }
         }
 
+        Action::SendEscape => {
+            for pane_id in app.input_pane_ids() {
+                tmux.send_command(&Commands::send_keys(TmuxTarget::new(&pane_id)?, "Escape")).await?;
+            }
+        }
+
         Action::SendKey(key_str) => {
-            if let Some(pane_id) = app.active_pane_id() {
-                tmux.send_command(&format!("send-keys -t {} {}", pane_id, key_str))
+            for pane_id in app.input_pane_ids() {
+                tmux.send_command(&Commands::send_keys(TmuxTarget::new(&pane_id)?, &key_str))
                     .await?;
             }
         }
+
+        Action::SendText(text) => {
+            for pane_id in app.input_pane_ids() {
+                let version = tmux.tmux_version();
+                tmux.send_command(&Commands::send_bytes(TmuxTarget::new(&pane_id)?, text.as_bytes(), version))
+                    .await?;
+            }
+        }
+
+        Action::EnterScrollMode => {}
+
+        Action::CopyLastOutput => {
+            let output = app.active_tab().and_then(|tab| {
+                let rows = tab.buffer.last_output_rows()?;
+                Some(tab.buffer.history_text(rows))
+            });
+            match output {
+                Some(text) => {
+                    let lines = text.lines().count();
+                    app.copy_to_clipboard(text);
+                    app.set_status(format!("Copied {} lines of output", lines));
+                }
+                None => app.set_status("No command output marked (needs OSC 133 shell integration)"),
+            }
+        }
+
+        Action::CopyScreen => match app.active_screen_text().filter(|text| !text.is_empty()) {
+            Some(text) => {
+                let lines = text.lines().count();
+                app.copy_to_clipboard(text);
+                app.set_status(format!("Copied {} lines of screen", lines));
+            }
+            None => app.set_status("Nothing on screen to copy"),
+        },
+
+        Action::ExitScrollMode => {
+            app.clear_search();
+            if let Some(tab) = app.active_tab_mut() {
+                tab.buffer.reset_scroll();
+            }
+        }
+
+        Action::Search(query) => {
+            if app.start_search(&query) {
+                input.enter_scroll();
+                set_search_status(app);
+            } else {
+                app.set_status(format!("No matches for \"{}\"", query));
+            }
+        }
+
+        Action::SearchNext | Action::SearchPrev => {
+            if app.step_search(action == Action::SearchNext) {
+                set_search_status(app);
+            } else if app.search().is_some() {
+                app.set_status("No more matches");
+            }
+        }
+
+        Action::PrevPrompt | Action::NextPrompt => {
+            let has_prompts = app
+                .active_tab()
+                .is_some_and(|tab| !tab.buffer.prompt_rows().is_empty());
+            if !has_prompts {
+                app.set_status("No prompts marked (needs OSC 133 shell integration)");
+            } else if app.step_prompt(action == Action::PrevPrompt) {
+                if app.active_tab().is_some_and(|tab| tab.buffer.is_scrolled()) {
+                    input.enter_scroll();
+                }
+            } else {
+                app.set_status("No more prompts");
+            }
+        }
+
+        Action::ScrollUp(amount) => {
+            if let Some(tab) = app.active_tab_mut() {
+                let lines = scroll_lines(amount, &tab.buffer);
+                tab.buffer.scroll_view_up(lines);
+            }
+        }
+
+        Action::ScrollDown(amount) => {
+            if let Some(tab) = app.active_tab_mut() {
+                let lines = scroll_lines(amount, &tab.buffer);
+                tab.buffer.scroll_view_down(lines);
+            }
+        }
     }
 
     Ok(LoopAction::Continue)
 }
 
+/// Resolve a scroll amount against the height of the given buffer
+fn scroll_lines(amount: ScrollAmount, buffer: &TerminalBuffer) -> usize {
+    let (_, height) = buffer.size();
+    amount.lines(height)
+}
+
+/// Show which search match the view is on
+fn set_search_status(app: &mut App) {
+    if let Some(search) = app.search() {
+        let message = format!("Match {} of {}", search.current_index(), search.matches.len());
+        app.set_status(message);
+    }
+}
+
 /// Handle a tmux event
-async fn handle_tmux_event(
-    event: TmuxEvent,
-    app: &mut App,
-    tmux: &mut TmuxConnection,
-) -> anyhow::Result<()> {
+fn handle_tmux_event(event: TmuxEvent, app: &mut App) -> anyhow::Result<()> {
     match event {
         TmuxEvent::Output { pane_id, data } => {
             // If we don't have tabs yet, this output might tell us about the initial pane
@@ -330,14 +991,14 @@ async fn handle_tmux_event(
         TmuxEvent::WindowAdd { window_id } => {
             log_debug(&format!("Window added: {}", window_id));
             // Query updated window list to get full info
-            tmux.send_command(&Commands::list_windows()).await?;
+            app.request_resync();
         }
 
         TmuxEvent::WindowClose { window_id } => {
             log_debug(&format!("Window closed: {}", window_id));
             app.remove_tab(&window_id);
             // Re-sync to ensure consistency
-            tmux.send_command(&Commands::list_windows()).await?;
+            app.request_resync();
         }
 
         TmuxEvent::WindowRenamed { window_id, name } => {
@@ -347,7 +1008,7 @@ async fn handle_tmux_event(
 
         TmuxEvent::SessionChanged { .. } => {
             // Session changed - refresh window list
-            tmux.send_command(&Commands::list_windows()).await?;
+            app.request_resync();
         }
 
         TmuxEvent::WindowChanged { window_id } => {
@@ -355,16 +1016,33 @@ async fn handle_tmux_event(
             app.set_active(&window_id);
         }
 
-        TmuxEvent::CommandResponse { data, .. } => {
-            // Check if this looks like a window list response
-            if data.contains(':') && (data.contains('@') || data.contains('%')) {
+        TmuxEvent::CommandResponse { id, data } => match app.take_command(id) {
+            Some(CommandKind::ListWindows) => {
                 app.process_window_list(&data);
                 log_debug(&format!("Loaded {} tabs", app.tab_count()));
             }
-        }
+            Some(CommandKind::CapturePane { pane_id }) => {
+                app.seed_pane(&pane_id, &data);
+            }
+            Some(CommandKind::ShowOption { name }) => {
+                app.apply_option(&name, &data);
+            }
+            Some(CommandKind::PaneDead { window_id }) => {
+                app.resolve_pane_dead(&window_id, data.trim() == "1");
+            }
+            Some(CommandKind::DisplayMessage) | Some(CommandKind::Other) | None => {
+                if let Some(line) = data.lines().find(|l| !l.trim().is_empty()) {
+                    app.set_status(line);
+                }
+            }
+        },
 
         TmuxEvent::CommandError { id, message } => {
+            app.take_command(id);
             log_debug(&format!("Command {} error: {}", id, message));
+            if !message.is_empty() {
+                app.set_status(format!("tmux: {}", message));
+            }
         }
 
         TmuxEvent::Exit { reason } => {
@@ -375,72 +1053,96 @@ async fn handle_tmux_event(
     Ok(())
 }
 
-/// Result of handling a mouse event
-struct MouseResult {
-    /// Whether to start rename mode (double-click on tab)
-    start_rename: bool,
-}
-
 /// Handle a mouse event
-async fn handle_mouse_event(
+/// Sidebar clicks are translated into actions for the caller to dispatch;
+/// viewport events are forwarded straight to the active pane
+async fn handle_mouse_event<T: TmuxClient>(
     mouse: crossterm::event::MouseEvent,
     app: &mut App,
-    tmux: &mut TmuxConnection,
+    tmux: &mut T,
     layout: &Layout,
-    input: &InputHandler,
-    last_tab_click: &mut Option<(usize, Instant)>,
-    double_click_ms: u128,
-) -> anyhow::Result<MouseResult> {
+    input: &mut InputHandler,
+    clicks: &mut MouseClicks,
+) -> anyhow::Result<Action> {
     let x = mouse.column;
     let y = mouse.row;
-    let mut result = MouseResult { start_rename: false };
+    let mut action = Action::None;
 
     match layout.hit_test(x, y) {
         HitRegion::Sidebar { row } => {
-            // Only handle clicks in sidebar
-            if let MouseEventKind::Down(MouseButton::Left) = mouse.kind {
-                let sidebar_area = layout.sidebar_area();
-                let num_tabs = app.tab_count();
+            let sidebar_area = layout.sidebar_area();
+            let num_tabs = app.tab_count();
 
-                // Calculate header rows (1 if in prefix mode, 0 otherwise)
-                let header_rows = if matches!(input.mode(), InputMode::Prefix) { 1 } else { 0 };
+            // Calculate header rows (1 if a mode indicator is shown, 0 otherwise)
+            let header_rows = if matches!(input.mode(), InputMode::Normal) { 0 } else { 1 };
 
+            // Middle-click closes a tab, as in a browser; the [+] button ignores it
+            if mouse.kind == MouseEventKind::Down(MouseButton::Middle) {
+                clicks.tab.reset();
+                if is_new_tab_button(row, sidebar_area.height) {
+                    return Ok(action);
+                }
+                let window_id = row_to_tab_index(row, num_tabs, sidebar_area.height, header_rows)
+                    .and_then(|tab_index| app.number_at_display_position(tab_index))
+                    .and_then(|pos| app.window_id_by_number(pos));
+                if let Some(window_id) = window_id {
+                    let close = Action::CloseWindow(window_id.to_string());
+                    if input.confirms_kill() {
+                        input.start_confirm(close, "Kill window? (y/n)");
+                    } else {
+                        action = close;
+                    }
+                }
+            }
+
+            // Only left clicks do anything else in the sidebar
+            if let MouseEventKind::Down(MouseButton::Left) = mouse.kind {
                 if is_new_tab_button(row, sidebar_area.height) {
                     // Click on [+] button - create new tab
-                    tmux.send_command(&Commands::new_window(None)).await?;
-                    *last_tab_click = None;
+                    action = Action::NewTab;
+                    clicks.tab.reset();
                 } else if let Some(tab_index) = row_to_tab_index(row, num_tabs, sidebar_area.height, header_rows) {
-                    // Check for double-click
-                    let now = Instant::now();
-                    if let Some((last_index, last_time)) = last_tab_click {
-                        if *last_index == tab_index && now.duration_since(*last_time).as_millis() < double_click_ms {
-                            // Double-click on same tab - trigger rename
-                            result.start_rename = true;
-                            *last_tab_click = None;
-                        } else {
-                            // Different tab or too slow - single click
-                            *last_tab_click = Some((tab_index, now));
-                            if let Some(window_id) = app.window_id_by_index(tab_index + 1) {
-                                tmux.send_command(&Commands::select_window(window_id)).await?;
-                            }
-                        }
+                    if clicks.tab.register(tab_index, Instant::now()) == ClickCount::Double {
+                        // Double-click on same tab - trigger rename
+                        action = Action::StartRename;
                     } else {
-                        // First click
-                        *last_tab_click = Some((tab_index, now));
-                        if let Some(window_id) = app.window_id_by_index(tab_index + 1) {
-                            tmux.send_command(&Commands::select_window(window_id)).await?;
-                        }
+                        // Rows can be sorted by activity; select by the number shown
+                        action = app.number_at_display_position(tab_index).map_or(Action::None, Action::SelectTab);
                     }
                 } else {
-                    *last_tab_click = None;
+                    clicks.tab.reset();
                 }
             }
         }
         HitRegion::Viewport { row, col } => {
+            clicks.tab.reset();
+            // A click on a link opens it, unless the program handles the mouse itself
+            if let Some(url) = clicked_link(app, mouse.kind, mouse.modifiers, row, col) {
+                match opener::open(&url) {
+                    Ok(()) => app.set_status(format!("Opened {}", url)),
+                    Err(e) => app.set_status(format!("Couldn't open {}: {}", url, e)),
+                }
+                return Ok(action);
+            }
+            // Double-click copies a word and triple-click a line, when the
+            // program isn't using the mouse itself
+            let tracking = app.active_tab().is_some_and(|tab| tab.buffer.mouse_tracking());
+            if mouse.kind == MouseEventKind::Down(MouseButton::Left) && !tracking {
+                let count = clicks.viewport.register((row, col), Instant::now());
+                if count != ClickCount::Single {
+                    let line = count == ClickCount::Triple;
+                    if let Some(text) = selection_text(app, row, col, line) {
+                        app.set_status(if line { "Copied line" } else { "Copied word" });
+                        app.copy_to_clipboard(text);
+                        return Ok(action);
+                    }
+                }
+            } else if !matches!(mouse.kind, MouseEventKind::Up(_)) {
+                clicks.viewport.reset();
+            }
             // Forward mouse events to tmux pane
-            *last_tab_click = None;
             if let Some(pane_id) = app.active_pane_id() {
-                let mouse_cmd = mouse_event_to_tmux(pane_id, mouse.kind, col, row);
+                let mouse_cmd = mouse_event_to_tmux(TmuxTarget::new(pane_id)?, mouse.kind, col, row);
                 if let Some(cmd) = mouse_cmd {
                     tmux.send_command(&cmd).await?;
                 }
@@ -448,16 +1150,78 @@ async fn handle_mouse_event(
         }
         HitRegion::None => {
             // Click outside any region - reset double-click tracking
-            *last_tab_click = None;
+            clicks.tab.reset();
         }
     }
 
-    Ok(result)
+    Ok(action)
+}
+
+/// Whether a click closes the open prompt: any left click, except more clicks
+/// on the sidebar in the burst whose double-click opened rename
+fn click_cancels_prompt(
+    mouse: &crossterm::event::MouseEvent,
+    layout: &Layout,
+    input: &InputHandler,
+    clicks: &MouseClicks,
+    now: Instant,
+) -> bool {
+    if mouse.kind != MouseEventKind::Down(MouseButton::Left) {
+        return false;
+    }
+    let in_sidebar = matches!(layout.hit_test(mouse.column, mouse.row), HitRegion::Sidebar { .. });
+    !(input.is_renaming() && in_sidebar && clicks.tab.follows_last(now))
+}
+
+/// Recent clicks, for spotting double and triple clicks
+#[derive(Debug)]
+struct MouseClicks {
+    /// Tabs in the sidebar, by display position
+    tab: ClickTracker<usize>,
+    /// Cells in the viewport, by (row, col)
+    viewport: ClickTracker<(u16, u16)>,
+}
+
+impl MouseClicks {
+    fn new(timeout: Duration) -> Self {
+        Self { tab: ClickTracker::new(timeout), viewport: ClickTracker::new(timeout) }
+    }
+}
+
+/// Text under a multi-click in the viewport: the word there, or the whole
+/// logical line (across wraps) when `line` is set
+fn selection_text(app: &App, row: u16, col: u16, line: bool) -> Option<String> {
+    let buffer = &app.active_tab()?.buffer;
+    if line {
+        let text = buffer.history_text(buffer.line_bounds(row));
+        return (!text.is_empty()).then_some(text);
+    }
+    let (start, end) = buffer.word_bounds(row, col, app.word_chars())?;
+    let cells = &buffer.display_row(row)?[start as usize..=end as usize];
+    Some(cells.iter().map(|cell| cell.character).collect())
+}
+
+/// Target of the link under a left click in the viewport
+/// A plain click opens OSC 8 links when the active program isn't tracking the
+/// mouse; Ctrl-click always does, and also finds URLs printed as plain text
+fn clicked_link(app: &App, kind: MouseEventKind, modifiers: KeyModifiers, row: u16, col: u16) -> Option<String> {
+    if kind != MouseEventKind::Down(MouseButton::Left) {
+        return None;
+    }
+    let buffer = &app.active_tab()?.buffer;
+    let link = buffer.link_at(row, col).map(str::to_string);
+    if modifiers.contains(KeyModifiers::CONTROL) {
+        link.or_else(|| buffer.url_at(row, col))
+    } else if buffer.mouse_tracking() {
+        None
+    } else {
+        link
+    }
 }
 
 /// Convert a mouse event to a tmux send-keys command
 /// Uses SGR (1006) mouse encoding format
-fn mouse_event_to_tmux(pane_id: &str, kind: MouseEventKind, col: u16, row: u16) -> Option<String> {
+fn mouse_event_to_tmux(pane_id: TmuxTarget, kind: MouseEventKind, col: u16, row: u16) -> Option<String> {
     // tmux expects 1-based coordinates for mouse events
     let x = col + 1;
     let y = row + 1;
@@ -484,9 +1248,1006 @@ fn mouse_event_to_tmux(pane_id: &str, kind: MouseEventKind, col: u16, row: u16)
     let suffix = if press { 'M' } else { 'm' };
 
     // Send the escape sequence using send-keys -l (literal mode)
-    // We need to escape the escape character for tmux
-    Some(format!(
-        "send-keys -t {} -l $'\\e[<{};{};{}{}'",
-        pane_id, button_code, x, y, suffix
-    ))
+    let sequence = format!("[<{};{};{}{}", button_code, x, y, suffix);
+    Some(Commands::send_escape_sequence(pane_id, &sequence))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
+    use helmux::tmux::{Direction, MockTmux, Placement, TmuxVersion};
+    use tokio::sync::mpsc;
+
+    /// Build an App with two tabs (@1 active, @2) and a layout
+    fn setup() -> (App, InputHandler, Layout) {
+        let mut app = App::new(80, 24);
+        app.process_window_list("@1:one:1:%1\n@2:two:0:%2");
+        let layout = Layout::new(ratatui::layout::Rect::new(0, 0, 100, 24));
+        (app, InputHandler::new(), layout)
+    }
+
+    /// Dispatch an action against a fresh three-tab app (@1 active) and return what was sent
+    async fn commands_for(action: Action) -> (LoopAction, Vec<String>) {
+        let (mut app, mut input, mut layout) = setup();
+        app.process_window_list("@1:one:1:%1\n@2:two:0:%2\n@3:three:0:%3");
+        let mut tmux = MockTmux::new();
+        let result = handle_action(action, &mut app, &mut tmux, &mut input, &mut layout)
+            .await
+            .unwrap();
+        (result, tmux.sent)
+    }
+
+    #[tokio::test]
+    async fn test_every_action_sends_expected_commands() {
+        let cases: Vec<(Action, Vec<&str>)> = vec![
+            (Action::None, vec![]),
+            (Action::NewTab, vec!["new-window"]),
+            (Action::RestartWindow, vec!["respawn-window -k -t @1"]),
+            (Action::NewNamedTab("build logs".to_string()), vec!["new-window -n 'build logs'"]),
+            (Action::CloseTab, vec!["kill-window -t @1"]),
+            (Action::NextTab, vec!["select-window -t @2"]),
+            (Action::PrevTab, vec!["select-window -t @3"]),
+            (Action::LastTab, vec!["select-window -t @2"]),
+            (Action::FindWindow("THR".to_string()), vec!["select-window -t @3"]),
+            (Action::FindWindow("nothing".to_string()), vec![]),
+            (Action::SelectTab(0), vec!["select-window -t @1"]),
+            (Action::SelectTab(2), vec!["select-window -t @3"]),
+            (Action::SelectTab(3), vec![]),
+            (Action::ToggleSidebar, vec!["refresh-client -C 97,24"]),
+            (Action::FlipSidebar, vec!["refresh-client -C 80,24"]),
+            (Action::StartRename, vec![]),
+            (Action::ToggleAutoRename, vec!["set-window-option -t @1 automatic-rename on"]),
+            (Action::ToggleMark, vec![]),
+            (Action::SwapWindow, vec!["swap-window -s @1 -t @2", "list-windows"]),
+            (Action::JoinMarked, vec![]),
+            (Action::MoveTabRight, vec!["swap-window -s @1 -t @2", "select-window -t @1", "list-windows"]),
+            (Action::MoveTabLeft, vec![]),
+            (Action::RenumberWindows, vec!["move-window -r", "list-windows"]),
+            (Action::ResizePane(Direction::Up, 5), vec!["resize-pane -t %1 -U 5"]),
+            (Action::CloseWindow("@3".to_string()), vec!["kill-window -t @3"]),
+            (Action::CloseOtherTabs, vec!["kill-window -a -t @1", "list-windows"]),
+            (Action::ClearHistory, vec!["clear-history -t %1"]),
+            (Action::ToggleMouse, vec!["set-option -g mouse on", "show-options -gv mouse"]),
+            (Action::SendCtrlB, vec!["send-keys -t %1 C-b"]),
+            (Action::SendKey("Enter".to_string()), vec!["send-keys -t %1 Enter"]),
+            (Action::SendEscape, vec!["send-keys -t %1 Escape"]),
+            (Action::SendText("é".to_string()), vec!["send-keys -t %1 -H c3 a9"]),
+            (Action::EnterScrollMode, vec![]),
+            (Action::ExitScrollMode, vec![]),
+            (Action::Search("one".to_string()), vec![]),
+            (Action::CopyLastOutput, vec![]),
+            (Action::CopyScreen, vec![]),
+            (Action::SearchNext, vec![]),
+            (Action::PrevPrompt, vec![]),
+            (Action::ToggleBroadcast, vec![]),
+            (Action::ToggleDebugOverlay, vec![]),
+            (Action::ToggleSortByActivity, vec![]),
+            (Action::ToggleSyncPanes, vec!["set-window-option -t @1 synchronize-panes on"]),
+            (Action::ScrollUp(ScrollAmount::Page), vec![]),
+            (Action::ScrollDown(ScrollAmount::Page), vec![]),
+        ];
+
+        for (action, expected) in cases {
+            let (result, sent) = commands_for(action.clone()).await;
+            assert_eq!(result, LoopAction::Continue, "{:?}", action);
+            assert_eq!(sent.len(), expected.len(), "{:?} sent {:?}", action, sent);
+            for (cmd, prefix) in sent.iter().zip(&expected) {
+                assert!(cmd.starts_with(prefix), "{:?} sent {:?}", action, sent);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_detach_sends_detach_and_exits() {
+        let (result, sent) = commands_for(Action::Detach).await;
+        assert_eq!(result, LoopAction::Exit);
+        assert_eq!(sent, vec!["detach-client"]);
+    }
+
+    #[tokio::test]
+    async fn test_detach_restores_monitor_activity() {
+        let (mut app, mut input, mut layout) = setup();
+        app.apply_option("monitor-activity", "off");
+        let mut tmux = MockTmux::new();
+        let result = handle_action(Action::Detach, &mut app, &mut tmux, &mut input, &mut layout)
+            .await
+            .unwrap();
+        assert_eq!(result, LoopAction::Exit);
+        assert_eq!(tmux.sent, vec!["set-window-option -g monitor-activity off", "detach-client"]);
+    }
+
+    #[tokio::test]
+    async fn test_start_rename_uses_active_tab_name() {
+        let (mut app, mut input, mut layout) = setup();
+        let mut tmux = MockTmux::new();
+        handle_action(Action::StartRename, &mut app, &mut tmux, &mut input, &mut layout)
+            .await
+            .unwrap();
+        assert!(input.is_renaming());
+        assert_eq!(input.text_buffer(), "one");
+    }
+
+    fn click(column: u16, row: u16) -> crossterm::event::MouseEvent {
+        crossterm::event::MouseEvent {
+            kind: MouseEventKind::Down(MouseButton::Left),
+            column,
+            row,
+            modifiers: crossterm::event::KeyModifiers::NONE,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sidebar_click_selects_tab_at_row() {
+        let (mut app, mut input, mut layout) = setup();
+        app.process_window_list("@1:one:1:%1\n@2:two:0:%2\n@3:three:0:%3");
+        let mut tmux = MockTmux::new();
+        let mut clicks = MouseClicks::new(Duration::from_millis(400));
+
+        // Third row (0-based row 2) is the third tab
+        let action = handle_mouse_event(click(5, 2), &mut app, &mut tmux, &layout, &mut input, &mut clicks)
+            .await
+            .unwrap();
+        assert_eq!(action, Action::SelectTab(2));
+        assert!(tmux.sent.is_empty());
+
+        handle_action(action, &mut app, &mut tmux, &mut input, &mut layout)
+            .await
+            .unwrap();
+        assert_eq!(tmux.sent, vec!["select-window -t @3"]);
+    }
+
+    #[tokio::test]
+    async fn test_sidebar_middle_click_closes_tab_at_row() {
+        let (mut app, mut input, mut layout) = setup();
+        app.process_window_list("@1:one:1:%1\n@2:two:0:%2\n@3:three:0:%3");
+        let mut tmux = MockTmux::new();
+        let mut clicks = MouseClicks::new(Duration::from_millis(400));
+        let middle = |column, row| crossterm::event::MouseEvent {
+            kind: MouseEventKind::Down(MouseButton::Middle),
+            ..click(column, row)
+        };
+
+        // Second row is the second tab, not the active one
+        let action = handle_mouse_event(middle(5, 1), &mut app, &mut tmux, &layout, &mut input, &mut clicks)
+            .await
+            .unwrap();
+        assert_eq!(action, Action::CloseWindow("@2".to_string()));
+        assert!(tmux.sent.is_empty());
+        handle_action(action, &mut app, &mut tmux, &mut input, &mut layout)
+            .await
+            .unwrap();
+        assert_eq!(tmux.sent, vec!["kill-window -t @2"]);
+
+        // The [+] button and the empty rows below the tabs ignore it
+        let new_tab_row = layout.sidebar_area().height - 1;
+        for row in [new_tab_row, 10] {
+            let action = handle_mouse_event(middle(5, row), &mut app, &mut tmux, &layout, &mut input, &mut clicks)
+                .await
+                .unwrap();
+            assert_eq!(action, Action::None);
+        }
+
+        // With confirm_kill, the close waits for a yes
+        let mut input = InputHandler::new().with_confirm_kill(true);
+        let action = handle_mouse_event(middle(5, 2), &mut app, &mut tmux, &layout, &mut input, &mut clicks)
+            .await
+            .unwrap();
+        assert_eq!(action, Action::None);
+        assert_eq!(input.confirm_prompt(), Some("Kill window? (y/n)"));
+        let key = crossterm::event::KeyEvent::new(crossterm::event::KeyCode::Char('y'), KeyModifiers::NONE);
+        assert_eq!(input.handle_key(key), Action::CloseWindow("@3".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_click_and_prefix_digit_select_same_window() {
+        let (mut app, mut input, mut layout) = setup();
+        app.process_window_list("@1:one:1:%1\n@2:two:0:%2\n@3:three:0:%3");
+        let mut clicks = MouseClicks::new(Duration::from_millis(400));
+
+        // Click the third row of the sidebar
+        let mut clicked = MockTmux::new();
+        let action = handle_mouse_event(click(5, 2), &mut app, &mut clicked, &layout, &mut input, &mut clicks)
+            .await
+            .unwrap();
+        handle_action(action, &mut app, &mut clicked, &mut input, &mut layout)
+            .await
+            .unwrap();
+
+        // Press prefix-3
+        let mut pressed = MockTmux::new();
+        input.handle_key(crossterm::event::KeyEvent::new(
+            KeyCode::Char('b'),
+            crossterm::event::KeyModifiers::CONTROL,
+        ));
+        let action = input.handle_key(crossterm::event::KeyEvent::new(
+            KeyCode::Char('3'),
+            crossterm::event::KeyModifiers::NONE,
+        ));
+        handle_action(action, &mut app, &mut pressed, &mut input, &mut layout)
+            .await
+            .unwrap();
+
+        assert_eq!(clicked.sent, vec!["select-window -t @3"]);
+        assert_eq!(clicked.sent, pressed.sent);
+    }
+
+    #[tokio::test]
+    async fn test_sidebar_double_click_starts_rename() {
+        let (mut app, mut input, mut layout) = setup();
+        let mut tmux = MockTmux::new();
+        let mut clicks = MouseClicks::new(Duration::from_millis(400));
+
+        let first = handle_mouse_event(click(5, 0), &mut app, &mut tmux, &layout, &mut input, &mut clicks)
+            .await
+            .unwrap();
+        let second = handle_mouse_event(click(5, 0), &mut app, &mut tmux, &layout, &mut input, &mut clicks)
+            .await
+            .unwrap();
+        assert_eq!(first, Action::SelectTab(0));
+        assert_eq!(second, Action::StartRename);
+
+        // A third click in the same burst leaves the rename open; later ones,
+        // or any in the viewport, close it
+        handle_action(second, &mut app, &mut tmux, &mut input, &mut layout).await.unwrap();
+        assert!(input.is_renaming());
+        let now = Instant::now();
+        assert!(!click_cancels_prompt(&click(5, 0), &layout, &input, &clicks, now));
+        assert!(click_cancels_prompt(&click(50, 5), &layout, &input, &clicks, now));
+        assert!(click_cancels_prompt(&click(5, 0), &layout, &input, &clicks, now + Duration::from_secs(1)));
+    }
+
+    #[tokio::test]
+    async fn test_sidebar_new_tab_button() {
+        let (mut app, mut input, layout) = setup();
+        let mut tmux = MockTmux::new();
+        let mut clicks = MouseClicks::new(Duration::from_millis(400));
+
+        // Last row of the sidebar is the [+] button
+        let action = handle_mouse_event(click(5, 23), &mut app, &mut tmux, &layout, &mut input, &mut clicks)
+            .await
+            .unwrap();
+        assert_eq!(action, Action::NewTab);
+    }
+
+    #[tokio::test]
+    async fn test_clicks_follow_flipped_sidebar() {
+        let (mut app, mut input, mut layout) = setup();
+        let mut tmux = MockTmux::new();
+        handle_action(Action::FlipSidebar, &mut app, &mut tmux, &mut input, &mut layout)
+            .await
+            .unwrap();
+        let mut clicks = MouseClicks::new(Duration::from_millis(400));
+
+        // The sidebar now takes the last 20 columns, and the viewport starts at 0
+        let action = handle_mouse_event(click(85, 1), &mut app, &mut tmux, &layout, &mut input, &mut clicks)
+            .await
+            .unwrap();
+        assert_eq!(action, Action::SelectTab(1));
+        tmux.sent.clear();
+        handle_mouse_event(click(5, 3), &mut app, &mut tmux, &layout, &mut input, &mut clicks)
+            .await
+            .unwrap();
+        assert_eq!(tmux.sent, vec!["send-keys -t %1 -l $'\\e[<0;6;4M'"]);
+    }
+
+    #[tokio::test]
+    async fn test_sidebar_cycles_to_hidden_and_back() {
+        let (mut app, mut input, mut layout) = setup();
+        let mut tmux = MockTmux::new();
+        for _ in 0..2 {
+            handle_action(Action::ToggleSidebar, &mut app, &mut tmux, &mut input, &mut layout)
+                .await
+                .unwrap();
+        }
+        assert!(layout.is_sidebar_hidden());
+        assert_eq!(tmux.sent.last().unwrap(), "refresh-client -C 100,24");
+        assert_eq!(app.status_message(), Some("Sidebar hidden; Ctrl-b b shows it"));
+
+        // Column 5 is now the viewport's
+        let mut clicks = MouseClicks::new(Duration::from_millis(400));
+        tmux.sent.clear();
+        let action = handle_mouse_event(click(5, 3), &mut app, &mut tmux, &layout, &mut input, &mut clicks)
+            .await
+            .unwrap();
+        assert_eq!(action, Action::None);
+        assert_eq!(tmux.sent, vec!["send-keys -t %1 -l $'\\e[<0;6;4M'"]);
+
+        // The same key brings it back
+        handle_action(Action::ToggleSidebar, &mut app, &mut tmux, &mut input, &mut layout)
+            .await
+            .unwrap();
+        assert_eq!(tmux.sent.last().unwrap(), "refresh-client -C 80,24");
+    }
+
+    #[tokio::test]
+    async fn test_viewport_click_forwards_to_pane() {
+        let (mut app, mut input, layout) = setup();
+        let mut tmux = MockTmux::new();
+        let mut clicks = MouseClicks::new(Duration::from_millis(400));
+
+        let action = handle_mouse_event(click(25, 3), &mut app, &mut tmux, &layout, &mut input, &mut clicks)
+            .await
+            .unwrap();
+        assert_eq!(action, Action::None);
+        assert_eq!(tmux.sent, vec!["send-keys -t %1 -l $'\\e[<0;6;4M'"]);
+    }
+
+    #[test]
+    fn test_clicked_link() {
+        let (mut app, _, _) = setup();
+        app.process_output("%1", b"go \x1b]8;;https://example.com\x1b\\here\x1b]8;;\x1b\\");
+        let left = MouseEventKind::Down(MouseButton::Left);
+        let (plain, ctrl) = (KeyModifiers::NONE, KeyModifiers::CONTROL);
+        assert_eq!(clicked_link(&app, left, plain, 0, 3).as_deref(), Some("https://example.com"));
+        assert_eq!(clicked_link(&app, left, plain, 0, 1), None);
+        assert_eq!(clicked_link(&app, MouseEventKind::Down(MouseButton::Right), plain, 0, 3), None);
+
+        // A program tracking the mouse gets a plain click instead, but not a Ctrl-click
+        app.process_output("%1", b"\x1b[?1000h");
+        assert_eq!(clicked_link(&app, left, plain, 0, 3), None);
+        assert_eq!(clicked_link(&app, left, ctrl, 0, 3).as_deref(), Some("https://example.com"));
+    }
+
+    #[tokio::test]
+    async fn test_viewport_multi_click_copies_word_then_line() {
+        let (app, mut input, layout) = setup();
+        let mut app = app.with_word_chars("-/.");
+        app.process_output("%1", b"\r\n\r\n\r\nls /tmp/my-dir here");
+        let mut tmux = MockTmux::new();
+        let mut clicks = MouseClicks::new(Duration::from_millis(400));
+        // Column 28 is viewport column 8, inside "/tmp/my-dir"
+        let mut click_at = async |app: &mut App| {
+            handle_mouse_event(click(28, 3), app, &mut tmux, &layout, &mut input, &mut clicks)
+                .await
+                .unwrap()
+        };
+
+        click_at(&mut app).await;
+        assert_eq!(app.take_clipboard(), None);
+        click_at(&mut app).await;
+        assert_eq!(app.take_clipboard().as_deref(), Some("/tmp/my-dir"));
+        assert_eq!(app.status_message(), Some("Copied word"));
+        click_at(&mut app).await;
+        assert_eq!(app.take_clipboard().as_deref(), Some("ls /tmp/my-dir here"));
+        // A fourth click starts over
+        click_at(&mut app).await;
+        assert_eq!(app.take_clipboard(), None);
+    }
+
+    #[test]
+    fn test_ctrl_click_finds_bare_url() {
+        let (mut app, _, _) = setup();
+        app.process_output("%1", b"see https://example.com/x now");
+        let left = MouseEventKind::Down(MouseButton::Left);
+        assert_eq!(clicked_link(&app, left, KeyModifiers::NONE, 0, 10), None);
+        assert_eq!(
+            clicked_link(&app, left, KeyModifiers::CONTROL, 0, 10).as_deref(),
+            Some("https://example.com/x")
+        );
+        assert_eq!(clicked_link(&app, left, KeyModifiers::CONTROL, 0, 2), None);
+    }
+
+    #[tokio::test]
+    async fn test_new_tab_sends_new_window() {
+        let (mut app, mut input, mut layout) = setup();
+        let mut tmux = MockTmux::new();
+        let result = handle_action(Action::NewTab, &mut app, &mut tmux, &mut input, &mut layout)
+            .await
+            .unwrap();
+        assert_eq!(result, LoopAction::Continue);
+        assert_eq!(tmux.sent, vec!["new-window"]);
+    }
+
+    #[tokio::test]
+    async fn test_close_tab_kills_active_window() {
+        let (mut app, mut input, mut layout) = setup();
+        let mut tmux = MockTmux::new();
+        handle_action(Action::CloseTab, &mut app, &mut tmux, &mut input, &mut layout)
+            .await
+            .unwrap();
+        assert_eq!(tmux.sent, vec!["kill-window -t @1"]);
+    }
+
+    #[tokio::test]
+    async fn test_exit_detaches() {
+        let (mut app, mut input, mut layout) = setup();
+        let mut tmux = MockTmux::new();
+        let result = handle_action(Action::Exit, &mut app, &mut tmux, &mut input, &mut layout)
+            .await
+            .unwrap();
+        assert_eq!(result, LoopAction::Exit);
+        assert_eq!(tmux.sent, vec!["detach-client"]);
+    }
+
+    #[test]
+    fn test_exit_policy_leaves_session_running() {
+        assert_eq!(exit_policy(&Action::Exit), ExitPolicy::Detach);
+        assert_eq!(exit_policy(&Action::Detach), ExitPolicy::Detach);
+        assert_eq!(exit_policy(&Action::KillSession), ExitPolicy::KillSession);
+    }
+
+    #[tokio::test]
+    async fn test_kill_session_sends_kill_and_exits() {
+        let (result, sent) = commands_for(Action::KillSession).await;
+        assert_eq!(result, LoopAction::Exit);
+        assert_eq!(sent, vec!["kill-session"]);
+    }
+
+    #[tokio::test]
+    async fn test_half_page_scroll_moves_active_buffer() {
+        let (mut app, mut input, mut layout) = setup();
+        let mut output = Vec::new();
+        for i in 0..60 {
+            output.extend_from_slice(format!("line {}\r\n", i).as_bytes());
+        }
+        app.process_output("%1", &output);
+        let mut tmux = MockTmux::new();
+
+        let action = Action::ScrollUp(ScrollAmount::HalfPage);
+        handle_action(action, &mut app, &mut tmux, &mut input, &mut layout)
+            .await
+            .unwrap();
+        assert_eq!(app.active_tab().unwrap().buffer.scroll_offset(), 12);
+
+        let action = Action::ScrollDown(ScrollAmount::HalfPage);
+        handle_action(action, &mut app, &mut tmux, &mut input, &mut layout)
+            .await
+            .unwrap();
+        assert_eq!(app.active_tab().unwrap().buffer.scroll_offset(), 0);
+        assert!(tmux.sent.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_swap_uses_marked_target() {
+        let (mut app, mut input, mut layout) = setup();
+        app.process_window_list("@1:one:0:%1\n@2:two:0:%2\n@3:three:1:%3");
+        app.toggle_mark();
+        app.set_active("@1");
+        let mut tmux = MockTmux::new();
+
+        handle_action(Action::SwapWindow, &mut app, &mut tmux, &mut input, &mut layout)
+            .await
+            .unwrap();
+        assert_eq!(tmux.sent[0], "swap-window -s @1 -t @3");
+        assert_eq!(tmux.sent[1], Commands::list_windows());
+    }
+
+    #[tokio::test]
+    async fn test_join_marked_joins_and_clears_mark() {
+        let (mut app, mut input, mut layout) = setup();
+        app.set_active("@2");
+        app.toggle_mark();
+        app.set_active("@1");
+        let mut tmux = MockTmux::new();
+
+        handle_action(Action::JoinMarked, &mut app, &mut tmux, &mut input, &mut layout)
+            .await
+            .unwrap();
+        assert_eq!(tmux.sent, vec!["join-pane -s %2 -t @1"]);
+        assert_eq!(app.marked_window_id(), None);
+    }
+
+    #[tokio::test]
+    async fn test_window_add_requests_window_list() {
+        let (mut app, _, _) = setup();
+        let mut tmux = MockTmux::new();
+        handle_tmux_event(TmuxEvent::WindowAdd { window_id: "@3".to_string() }, &mut app).unwrap();
+        app.send_pending_resync(&mut tmux).await.unwrap();
+        assert_eq!(tmux.sent, vec![Commands::list_windows()]);
+
+        // More windows arriving before the list does reuse it
+        handle_tmux_event(TmuxEvent::WindowAdd { window_id: "@4".to_string() }, &mut app).unwrap();
+        assert_eq!(tmux.sent.len(), 1);
+
+        // Once it's answered, the next addition asks again
+        let response = TmuxEvent::CommandResponse { id: 1, data: "@1:one:1:%1".to_string() };
+        handle_tmux_event(response, &mut app).unwrap();
+        handle_tmux_event(TmuxEvent::WindowAdd { window_id: "@5".to_string() }, &mut app).unwrap();
+        app.send_pending_resync(&mut tmux).await.unwrap();
+        assert_eq!(tmux.sent.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_window_event_burst_sends_one_window_list() {
+        let (mut app, _, _) = setup();
+        let mut tmux = MockTmux::new();
+        for id in 3..8 {
+            let event = TmuxEvent::WindowAdd { window_id: format!("@{}", id) };
+            handle_tmux_event(event, &mut app).unwrap();
+        }
+        let event = TmuxEvent::WindowClose { window_id: "@2".to_string() };
+        handle_tmux_event(event, &mut app).unwrap();
+        assert!(tmux.sent.is_empty());
+
+        app.send_pending_resync(&mut tmux).await.unwrap();
+        app.send_pending_resync(&mut tmux).await.unwrap();
+        assert_eq!(tmux.sent, vec![Commands::list_windows()]);
+        assert!(!app.resync_pending());
+
+        // A failed list stops counting as in flight
+        let error = TmuxEvent::CommandError { id: 1, message: String::new() };
+        handle_tmux_event(error, &mut app).unwrap();
+        app.request_resync();
+        assert!(app.resync_pending());
+    }
+
+    #[test]
+    fn test_background_rename_updates_sidebar_without_resync() {
+        let (mut app, _, _) = setup();
+        app.process_window_list("@1:one:1:%1\n@2:two:0:%2\n@3:three:0:%3");
+        // %window-renamed @3 newname
+        let event = TmuxEvent::WindowRenamed {
+            window_id: "@3".to_string(),
+            name: "newname".to_string(),
+        };
+        handle_tmux_event(event, &mut app).unwrap();
+
+        assert!(!app.resync_pending());
+        let tabs = app.tab_infos();
+        assert_eq!(tabs[2].name, "newname");
+        assert!(!tabs[2].active);
+    }
+
+    #[tokio::test]
+    async fn test_only_window_list_responses_update_tabs() {
+        let mut app = App::new(80, 24);
+        let mut tmux = MockTmux::new();
+
+        // An unrelated command whose output happens to look like a window list
+        let other = tmux.send_command(&Commands::display_message("#{window_id}:#{pane_id}")).await.unwrap();
+        app.sync_from_tmux(&mut tmux).await.unwrap();
+        // The mock hands out sequential IDs
+        let list = other + 1;
+
+        let response = TmuxEvent::CommandResponse { id: other, data: "@9:bogus:1:%9".to_string() };
+        handle_tmux_event(response, &mut app).unwrap();
+        assert!(!app.has_tabs());
+
+        let response = TmuxEvent::CommandResponse { id: list, data: "@1:one:1:%1".to_string() };
+        handle_tmux_event(response, &mut app).unwrap();
+        assert_eq!(app.active_window_id(), Some("@1"));
+
+        // The same response ID isn't treated as a window list twice
+        let response = TmuxEvent::CommandResponse { id: list, data: "@2:two:1:%2".to_string() };
+        handle_tmux_event(response, &mut app).unwrap();
+        assert_eq!(app.active_window_id(), Some("@1"));
+    }
+
+    #[tokio::test]
+    async fn test_toggle_mouse_tracks_state() {
+        let (mut app, mut input, mut layout) = setup();
+        let mut tmux = MockTmux::new();
+        app.apply_option("mouse", "on");
+
+        handle_action(Action::ToggleMouse, &mut app, &mut tmux, &mut input, &mut layout)
+            .await
+            .unwrap();
+        assert_eq!(tmux.sent[0], "set-option -g mouse off");
+        assert_eq!(app.tmux_mouse(), Some(false));
+        assert_eq!(app.status_message(), Some("tmux mouse off"));
+
+        // The read-back response is routed to the option state
+        let response = TmuxEvent::CommandResponse { id: 2, data: "on".to_string() };
+        handle_tmux_event(response, &mut app).unwrap();
+        assert_eq!(app.tmux_mouse(), Some(true));
+    }
+
+    #[tokio::test]
+    async fn test_send_text_falls_back_to_literal_on_old_tmux() {
+        let (mut app, mut input, mut layout) = setup();
+        let mut tmux = MockTmux::new();
+        tmux.version = Some(TmuxVersion::new(2, 9));
+
+        handle_action(Action::SendText("é".into()), &mut app, &mut tmux, &mut input, &mut layout)
+            .await
+            .unwrap();
+        assert_eq!(tmux.sent, vec!["send-keys -t %1 -l \"é\""]);
+    }
+
+    #[tokio::test]
+    async fn test_copy_last_output_needs_marks() {
+        let (mut app, mut input, mut layout) = setup();
+        let mut tmux = MockTmux::new();
+
+        handle_action(Action::CopyLastOutput, &mut app, &mut tmux, &mut input, &mut layout)
+            .await
+            .unwrap();
+        assert_eq!(app.take_clipboard(), None);
+        assert!(app.status_message().unwrap().contains("OSC 133"));
+    }
+
+    #[tokio::test]
+    async fn test_copy_last_output_queues_clipboard() {
+        let (mut app, mut input, mut layout) = setup();
+        let mut tmux = MockTmux::new();
+
+        app.process_output("%1", b"\x1b]133;C\x07line 1\r\nline 2\r\n\x1b]133;D;0\x07");
+        handle_action(Action::CopyLastOutput, &mut app, &mut tmux, &mut input, &mut layout)
+            .await
+            .unwrap();
+        assert_eq!(app.take_clipboard().as_deref(), Some("line 1\nline 2"));
+        assert_eq!(app.status_message(), Some("Copied 2 lines of output"));
+    }
+
+    #[tokio::test]
+    async fn test_copy_screen_queues_clipboard() {
+        let (mut app, mut input, mut layout) = setup();
+        let mut tmux = MockTmux::new();
+
+        handle_action(Action::CopyScreen, &mut app, &mut tmux, &mut input, &mut layout)
+            .await
+            .unwrap();
+        assert_eq!(app.take_clipboard(), None);
+        assert_eq!(app.status_message(), Some("Nothing on screen to copy"));
+
+        app.process_output("%1", "$ ls   \r\n日本  notes.txt\r\n$ ".as_bytes());
+        handle_action(Action::CopyScreen, &mut app, &mut tmux, &mut input, &mut layout)
+            .await
+            .unwrap();
+        let expected = app.active_tab().unwrap().buffer.to_text();
+        assert_eq!(expected, "$ ls\n日本  notes.txt\n$");
+        assert_eq!(app.take_clipboard(), Some(expected));
+        assert_eq!(app.status_message(), Some("Copied 3 lines of screen"));
+    }
+
+    #[test]
+    fn test_unknown_response_goes_to_status_line() {
+        let (mut app, _, _) = setup();
+        let response = TmuxEvent::CommandResponse { id: 42, data: "\nsome output\n".to_string() };
+        handle_tmux_event(response, &mut app).unwrap();
+        assert_eq!(app.status_message(), Some("some output"));
+    }
+
+    #[tokio::test]
+    async fn test_mock_yields_scripted_events() {
+        let (mut app, _, _) = setup();
+        let mut tmux = MockTmux::with_events([TmuxEvent::WindowChanged {
+            window_id: "@2".to_string(),
+        }]);
+        let event = tmux.next_event().await.unwrap();
+        handle_tmux_event(event, &mut app).unwrap();
+        assert_eq!(app.active_window_id(), Some("@2"));
+        assert!(tmux.next_event().await.is_err());
+    }
+
+    /// tmux client whose events come from a channel, blocking while it's empty
+    struct ChannelTmux {
+        events: mpsc::UnboundedReceiver<TmuxEvent>,
+    }
+
+    impl TmuxClient for ChannelTmux {
+        async fn send_command(&mut self, _cmd: &str) -> Result<u64, ConnectionError> {
+            Ok(0)
+        }
+
+        async fn flush(&mut self) -> Result<(), ConnectionError> {
+            Ok(())
+        }
+
+        async fn next_event(&mut self) -> Result<TmuxEvent, ConnectionError> {
+            self.events.recv().await.ok_or(ConnectionError::Closed)
+        }
+    }
+
+    type TestTerminalEvents = TerminalEvents<UnboundedReceiver<io::Result<Event>>>;
+
+    /// Terminal events fed by the returned sender
+    fn channel_terminal() -> (UnboundedSender<io::Result<Event>>, TestTerminalEvents) {
+        let (tx, rx) = unbounded();
+        (tx, rx.peekable())
+    }
+
+    /// A tmux client fed by the returned sender
+    fn channel_tmux() -> (mpsc::UnboundedSender<TmuxEvent>, ChannelTmux) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        (tx, ChannelTmux { events: rx })
+    }
+
+    #[tokio::test]
+    async fn test_loop_wakes_for_either_source() {
+        let (term_tx, mut term_rx) = channel_terminal();
+        let (tmux_tx, mut tmux) = channel_tmux();
+
+        term_tx.unbounded_send(Ok(Event::FocusGained)).unwrap();
+        let event = next_loop_event(&mut term_rx, &mut tmux, None).await.unwrap();
+        assert!(matches!(event, LoopEvent::Terminal(Event::FocusGained)));
+
+        tmux_tx.send(TmuxEvent::WindowAdd { window_id: "@4".to_string() }).unwrap();
+        let event = next_loop_event(&mut term_rx, &mut tmux, None).await.unwrap();
+        assert!(matches!(&event, LoopEvent::Tmux(events) if matches!(events[..], [TmuxEvent::WindowAdd { .. }])));
+    }
+
+    #[tokio::test]
+    async fn test_loop_idles_until_an_event_or_deadline() {
+        let (_term_tx, mut term_rx) = channel_terminal();
+        let (tmux_tx, mut tmux) = channel_tmux();
+
+        // Nothing to do: stays asleep
+        let idle = tokio::time::timeout(
+            std::time::Duration::from_millis(50),
+            next_loop_event(&mut term_rx, &mut tmux, None),
+        )
+        .await;
+        assert!(idle.is_err());
+
+        // A pending redraw deadline wakes it up
+        let deadline = Instant::now() + std::time::Duration::from_millis(10);
+        let event = next_loop_event(&mut term_rx, &mut tmux, Some(deadline)).await.unwrap();
+        assert!(matches!(event, LoopEvent::Redraw));
+
+        // tmux going away is reported rather than treated as an error
+        drop(tmux_tx);
+        let event = next_loop_event(&mut term_rx, &mut tmux, None).await.unwrap();
+        assert!(matches!(event, LoopEvent::TmuxClosed(ConnectionError::Closed)));
+    }
+
+    #[tokio::test]
+    async fn test_prompt_jumps() {
+        let (mut app, mut input, mut layout) = setup();
+        let mut tmux = MockTmux::new();
+        handle_action(Action::PrevPrompt, &mut app, &mut tmux, &mut input, &mut layout)
+            .await
+            .unwrap();
+        assert!(app.status_message().unwrap().contains("OSC 133"));
+
+        let rows = app.active_tab().unwrap().buffer.size().1 as usize;
+        let mut output = b"\x1b]133;A\x07$ make\r\n".to_vec();
+        output.extend(b"out\r\n".repeat(rows));
+        app.process_output("%1", &output);
+
+        handle_action(Action::PrevPrompt, &mut app, &mut tmux, &mut input, &mut layout)
+            .await
+            .unwrap();
+        assert!(input.is_scrolling());
+        assert_eq!(app.active_tab().unwrap().buffer.history_row(0), 0);
+
+        handle_action(Action::PrevPrompt, &mut app, &mut tmux, &mut input, &mut layout)
+            .await
+            .unwrap();
+        assert_eq!(app.status_message(), Some("No more prompts"));
+    }
+
+    #[tokio::test]
+    async fn test_broadcast_sends_to_every_tab() {
+        let (mut app, mut input, mut layout) = setup();
+        app.process_window_list("@1:one:1:%1\n@2:two:0:%2\n@3:three:0:%3");
+        let mut tmux = MockTmux::new();
+        handle_action(Action::SendText("ls".into()), &mut app, &mut tmux, &mut input, &mut layout)
+            .await
+            .unwrap();
+        assert_eq!(tmux.sent, vec!["send-keys -t %1 -H 6c 73"]);
+
+        handle_action(Action::ToggleBroadcast, &mut app, &mut tmux, &mut input, &mut layout)
+            .await
+            .unwrap();
+        tmux.sent.clear();
+        handle_action(Action::SendKey("Enter".into()), &mut app, &mut tmux, &mut input, &mut layout)
+            .await
+            .unwrap();
+        assert_eq!(
+            tmux.sent,
+            vec!["send-keys -t %1 Enter", "send-keys -t %2 Enter", "send-keys -t %3 Enter"]
+        );
+
+        // Toggling again goes back to the active tab only
+        handle_action(Action::ToggleBroadcast, &mut app, &mut tmux, &mut input, &mut layout)
+            .await
+            .unwrap();
+        assert_eq!(app.input_pane_ids(), vec!["%1".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_focus_reports_only_when_enabled() {
+        let (mut app, _, _) = setup();
+        let mut tmux = MockTmux::new();
+        report_focus(true, &app, &mut tmux).await.unwrap();
+        assert!(tmux.sent.is_empty());
+
+        app.process_output("%1", b"\x1b[?1004h");
+        report_focus(true, &app, &mut tmux).await.unwrap();
+        report_focus(false, &app, &mut tmux).await.unwrap();
+        assert_eq!(tmux.sent, vec!["send-keys -t %1 -H 1b 5b 49", "send-keys -t %1 -H 1b 5b 4f"]);
+    }
+
+    /// Whether the rendered screen contains the given text on some row
+    fn screen_contains(term: &Terminal<ratatui::backend::TestBackend>, text: &str) -> bool {
+        let buffer = term.backend().buffer();
+        (0..buffer.area.height).any(|y| {
+            let row: String = (0..buffer.area.width).map(|x| buffer[(x, y)].symbol()).collect();
+            row.contains(text)
+        })
+    }
+
+    #[test]
+    fn test_connecting_splash_until_tabs_load() {
+        let mut term = Terminal::new(ratatui::backend::TestBackend::new(100, 24)).unwrap();
+        let layout = Layout::new(ratatui::layout::Rect::new(0, 0, 100, 24));
+        let input = InputHandler::new();
+        let config = Config::default();
+
+        let mut app = App::new(80, 24);
+        render(&mut term, &layout, &app, &input, &config, ColorDepth::TrueColor, None).unwrap();
+        assert!(screen_contains(&term, CONNECTING_MESSAGE));
+
+        app.process_window_list("@1:one:1:%1");
+        render(&mut term, &layout, &app, &input, &config, ColorDepth::TrueColor, None).unwrap();
+        assert!(!screen_contains(&term, CONNECTING_MESSAGE));
+    }
+
+    #[test]
+    fn test_debug_overlay_shows_terminal_state() {
+        let mut term = Terminal::new(ratatui::backend::TestBackend::new(100, 24)).unwrap();
+        let layout = Layout::new(ratatui::layout::Rect::new(0, 0, 100, 24));
+        let input = InputHandler::new();
+        let config = Config::default();
+        let (mut app, _, _) = setup();
+        app.process_output("%1", b"\x1b[2;10r\x1b[?6h\x1b[4h\x1b[3;5H");
+
+        let cells = render(&mut term, &layout, &app, &input, &config, ColorDepth::TrueColor, None).unwrap();
+        assert_eq!(cells, 80 * 24);
+        app.record_frame(cells);
+        assert!(!screen_contains(&term, "scroll region"));
+
+        app.toggle_debug_overlay();
+        let last = Some("list-windows");
+        render(&mut term, &layout, &app, &input, &config, ColorDepth::TrueColor, last).unwrap();
+        for text in [
+            " window: @1 ",
+            " pane: %1 ",
+            " cursor: 3,4 ",
+            " scroll region: 1-9 ",
+            " modes: origin autowrap insert ",
+            " scrollback: 0 ",
+            " last command: list-windows ",
+            " frames: 1 ",
+        ] {
+            assert!(screen_contains(&term, text), "{:?} not on screen", text);
+        }
+    }
+
+    #[test]
+    fn test_describe_command_decodes_hex_keys() {
+        assert_eq!(describe_command("send-keys -t %1 -H 6c 73 0d"), "send-keys -t %1 \"ls\\r\"");
+        assert_eq!(describe_command("send-keys -t %1 -H e2 82 ac"), "send-keys -t %1 \"€\"");
+        assert_eq!(describe_command("list-windows -F '#{window_id}'"), "list-windows -F '#{window_id}'");
+    }
+
+    #[tokio::test]
+    async fn test_new_tab_in_cwd() {
+        let (app, mut input, mut layout) = setup();
+        let mut app = app.with_new_tab_in_cwd(true);
+        let mut tmux = MockTmux::new();
+
+        handle_action(Action::NewNamedTab("web".to_string()), &mut app, &mut tmux, &mut input, &mut layout)
+            .await
+            .unwrap();
+        assert_eq!(tmux.sent, vec!["new-window -c '#{pane_current_path}' -n 'web'"]);
+
+        let mut app = app.with_new_tab_in_cwd(false);
+        tmux.sent.clear();
+        handle_action(Action::NewTab, &mut app, &mut tmux, &mut input, &mut layout)
+            .await
+            .unwrap();
+        assert_eq!(tmux.sent, vec!["new-window"]);
+    }
+
+    #[tokio::test]
+    async fn test_new_tab_beside_active() {
+        let (app, mut input, mut layout) = setup();
+        let mut app = app.with_new_tab_placement(Some(Placement::After)).with_new_tab_in_cwd(true);
+        let mut tmux = MockTmux::new();
+        handle_action(Action::NewTab, &mut app, &mut tmux, &mut input, &mut layout)
+            .await
+            .unwrap();
+        assert_eq!(tmux.sent[0], "new-window -a -t @1 -c '#{pane_current_path}'");
+        assert!(tmux.sent[1].starts_with("list-windows"));
+
+        let mut app = app.with_new_tab_placement(Some(Placement::Before));
+        tmux.sent.clear();
+        handle_action(Action::NewNamedTab("web".to_string()), &mut app, &mut tmux, &mut input, &mut layout)
+            .await
+            .unwrap();
+        assert_eq!(tmux.sent[0], "new-window -b -t @1 -c '#{pane_current_path}' -n 'web'");
+
+        // Older servers have no -b
+        tmux.version = Some(TmuxVersion::new(3, 1));
+        tmux.sent.clear();
+        handle_action(Action::NewTab, &mut app, &mut tmux, &mut input, &mut layout)
+            .await
+            .unwrap();
+        assert_eq!(tmux.sent[..2], ["new-window -a -t @1 -c '#{pane_current_path}'", "swap-window -d -t @1"]);
+    }
+
+    #[tokio::test]
+    async fn test_move_tab_wraps_when_enabled() {
+        let (app, mut input, mut layout) = setup();
+        let mut app = app.with_wrap_tab_moves(true);
+        let mut tmux = MockTmux::new();
+        handle_action(Action::MoveTabLeft, &mut app, &mut tmux, &mut input, &mut layout)
+            .await
+            .unwrap();
+        assert_eq!(tmux.sent[..2], ["move-window -a -s @1 -t @2", "select-window -t @1"]);
+        assert!(tmux.sent[2].starts_with("list-windows"));
+
+        app.process_window_list("@1:one:0:%1\n@2:two:0:%2\n@3:three:1:%3");
+        tmux.sent.clear();
+        handle_action(Action::MoveTabRight, &mut app, &mut tmux, &mut input, &mut layout)
+            .await
+            .unwrap();
+        assert_eq!(tmux.sent[0], "move-window -b -s @3 -t @1");
+
+        // Older servers have no -b
+        tmux.version = Some(TmuxVersion::new(3, 1));
+        tmux.sent.clear();
+        handle_action(Action::MoveTabRight, &mut app, &mut tmux, &mut input, &mut layout)
+            .await
+            .unwrap();
+        assert_eq!(
+            tmux.sent[..3],
+            ["swap-window -s @3 -t @2", "swap-window -s @3 -t @1", "select-window -t @3"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_respawn_dead_tab_or_confirm() {
+        let (mut app, mut input, mut layout) = setup();
+        let mut tmux = MockTmux::new();
+
+        // tmux is asked first, whatever the last window list said
+        handle_action(Action::RespawnWindow, &mut app, &mut tmux, &mut input, &mut layout)
+            .await
+            .unwrap();
+        assert_eq!(tmux.sent, vec!["display-message -p -t @1 '#{pane_dead}'"]);
+        finish_respawn(&mut app, &mut tmux, &mut input).await.unwrap();
+        assert_eq!(tmux.sent.len(), 1);
+
+        // Still running: ask before killing it
+        handle_tmux_event(TmuxEvent::CommandResponse { id: 1, data: "0\n".to_string() }, &mut app).unwrap();
+        finish_respawn(&mut app, &mut tmux, &mut input).await.unwrap();
+        assert_eq!(tmux.sent.len(), 1);
+        assert_eq!(input.confirm_prompt(), Some("Kill the running command and restart it? (y/n)"));
+        input.cancel_confirm();
+
+        // Exited since the last window list: restart it straight away
+        tmux.sent.clear();
+        handle_action(Action::RespawnWindow, &mut app, &mut tmux, &mut input, &mut layout)
+            .await
+            .unwrap();
+        handle_tmux_event(TmuxEvent::CommandResponse { id: 2, data: "1\n".to_string() }, &mut app).unwrap();
+        finish_respawn(&mut app, &mut tmux, &mut input).await.unwrap();
+        assert!(app.active_tab().unwrap().dead);
+        assert_eq!(tmux.sent[1], "respawn-window -k -t @1");
+        assert!(!input.is_confirming());
+    }
+
+    #[tokio::test]
+    async fn test_tmux_events_batched_per_frame() {
+        let (term_tx, mut term_rx) = channel_terminal();
+        let (tmux_tx, mut tmux) = channel_tmux();
+        let output = |n: usize| TmuxEvent::Output { pane_id: "%1".to_string(), data: vec![b'0' + (n % 10) as u8] };
+        for n in 0..MAX_TMUX_EVENTS_PER_FRAME + 10 {
+            tmux_tx.send(output(n)).unwrap();
+        }
+
+        // A flood is cut off at the cap, with the rest left for the next frame
+        let first = tmux.next_event().await.unwrap();
+        let batch = ready_tmux_events(first, &mut tmux, &mut term_rx).await;
+        assert_eq!(batch.len(), MAX_TMUX_EVENTS_PER_FRAME);
+        let first = tmux.next_event().await.unwrap();
+        assert_eq!(ready_tmux_events(first, &mut tmux, &mut term_rx).await.len(), 10);
+
+        // Only what's already there is taken
+        tmux_tx.send(output(0)).unwrap();
+        let first = tmux.next_event().await.unwrap();
+        assert_eq!(ready_tmux_events(first, &mut tmux, &mut term_rx).await.len(), 1);
+
+        // Waiting input gets its turn first, and is still there afterwards
+        tmux_tx.send(output(0)).unwrap();
+        tmux_tx.send(output(1)).unwrap();
+        term_tx.unbounded_send(Ok(Event::FocusGained)).unwrap();
+        let first = tmux.next_event().await.unwrap();
+        assert_eq!(ready_tmux_events(first, &mut tmux, &mut term_rx).await.len(), 1);
+        assert!(matches!(term_rx.next().await, Some(Ok(Event::FocusGained))));
+    }
 }
@@ -0,0 +1,54 @@
+use std::io;
+use std::process::{Command, Stdio};
+
+/// URL schemes handed to the system opener; anything else in a link (e.g. a
+/// program's own `ssh:` or `javascript:` target) is refused
+const OPENABLE_SCHEMES: &[&str] = &["http", "https", "mailto", "file", "ftp"];
+
+/// Whether a link target is something helmux will open
+pub fn is_openable(url: &str) -> bool {
+    url.split_once(':').is_some_and(|(scheme, _)| {
+        OPENABLE_SCHEMES.iter().any(|s| s.eq_ignore_ascii_case(scheme))
+    })
+}
+
+/// Open a URL with the desktop's default handler, without waiting for it
+pub fn open(url: &str) -> io::Result<()> {
+    if !is_openable(url) {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "unsupported link type"));
+    }
+    let mut command = if cfg!(target_os = "macos") {
+        Command::new("open")
+    } else if cfg!(windows) {
+        let mut command = Command::new("rundll32");
+        command.arg("url.dll,FileProtocolHandler");
+        command
+    } else {
+        Command::new("xdg-open")
+    };
+    // Keep the opener's output off the screen helmux is drawing
+    command
+        .arg(url)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    let mut child = command.spawn()?;
+    // Reap it once it exits so it doesn't linger as a zombie
+    std::thread::spawn(move || child.wait());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_openable() {
+        assert!(is_openable("https://example.com"));
+        assert!(is_openable("HTTP://example.com"));
+        assert!(is_openable("file:///tmp/report.html"));
+        assert!(!is_openable("javascript:alert(1)"));
+        assert!(!is_openable("/etc/passwd"));
+        assert!(!is_openable(""));
+    }
+}
@@ -0,0 +1,68 @@
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::Context;
+
+use crate::terminal::TerminalBuffer;
+
+/// Directory screenshots are written to (`~/.local/share/helmux/screenshots` on Linux)
+pub fn default_dir() -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join("helmux").join("screenshots"))
+}
+
+/// Timestamp used to name a screenshot taken now (seconds since the epoch and
+/// milliseconds, so two shots in the same second don't overwrite each other)
+pub fn timestamp() -> String {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    format_timestamp(now)
+}
+
+fn format_timestamp(since_epoch: Duration) -> String {
+    format!("{}-{:03}", since_epoch.as_secs(), since_epoch.subsec_millis())
+}
+
+/// Path of the screenshot file for the given timestamp
+pub fn path_for(dir: &Path, timestamp: &str) -> PathBuf {
+    dir.join(format!("{}.txt", timestamp))
+}
+
+/// Write the visible screen of `buffer` as plain text into `dir`, creating it if needed
+/// Returns the path of the written file
+pub fn save(dir: &Path, timestamp: &str, buffer: &TerminalBuffer) -> anyhow::Result<PathBuf> {
+    std::fs::create_dir_all(dir)
+        .with_context(|| format!("Failed to create {}", dir.display()))?;
+    let path = path_for(dir, timestamp);
+    let mut text = buffer.to_text();
+    text.push('\n');
+    std::fs::write(&path, text).with_context(|| format!("Failed to write {}", path.display()))?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_save_writes_timestamped_file() {
+        let root = std::env::temp_dir().join(format!("helmux-screenshot-{}", std::process::id()));
+        let dir = root.join("nested").join("screenshots");
+
+        let mut buffer = TerminalBuffer::new(10, 3);
+        buffer.process(b"$ ls\r\nfoo  bar");
+
+        let path = save(&dir, "1700000000", &buffer).unwrap();
+        assert_eq!(path, dir.join("1700000000.txt"));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "$ ls\nfoo  bar\n");
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_timestamp_has_milliseconds() {
+        assert_eq!(format_timestamp(Duration::from_millis(1_700_000_000_042)), "1700000000-042");
+        assert_ne!(
+            format_timestamp(Duration::from_millis(1_700_000_000_001)),
+            format_timestamp(Duration::from_millis(1_700_000_000_002))
+        );
+    }
+}
@@ -1,10 +1,18 @@
 use ratatui::style::{Color, Modifier};
-use std::collections::VecDeque;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::ops::{Range, RangeInclusive};
 use vte::{Params, Perform};
 
 /// Default scrollback buffer size (number of lines)
 const DEFAULT_SCROLLBACK: usize = 1000;
 
+/// Deepest the kitty keyboard flag stack gets; the oldest entries go first
+const MAX_KITTY_KEYBOARD_STACK: usize = 16;
+
+/// Most distinct OSC 8 link targets kept per buffer at once; text linked when all of
+/// them are still on screen or in scrollback is plain
+const MAX_LINKS: usize = 4096;
+
 /// Attributes that can be applied to a cell
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 pub struct CellAttributes {
@@ -18,7 +26,7 @@ pub struct CellAttributes {
 }
 
 impl CellAttributes {
-    pub fn to_modifier(&self) -> Modifier {
+    pub fn to_modifier(self) -> Modifier {
         let mut m = Modifier::empty();
         if self.bold {
             m |= Modifier::BOLD;
@@ -52,6 +60,8 @@ pub struct Cell {
     pub fg: Color,
     pub bg: Color,
     pub attrs: CellAttributes,
+    /// OSC 8 hyperlink the text belongs to, as an id for `TerminalBuffer::link`
+    pub link: Option<u32>,
 }
 
 impl Default for Cell {
@@ -61,6 +71,7 @@ impl Default for Cell {
             fg: Color::Reset,
             bg: Color::Reset,
             attrs: CellAttributes::default(),
+            link: None,
         }
     }
 }
@@ -79,11 +90,80 @@ impl Cell {
             fg,
             bg,
             attrs,
+            link: None,
         }
     }
 }
 
+/// A stretch of a row whose cells all share one style
+#[derive(Debug, Clone, PartialEq)]
+pub struct StyledRun {
+    pub text: String,
+    pub fg: Color,
+    pub bg: Color,
+    pub attrs: CellAttributes,
+}
+
+impl StyledRun {
+    /// Whether a cell can be added to this run
+    fn matches(&self, cell: &Cell) -> bool {
+        self.fg == cell.fg && self.bg == cell.bg && self.attrs == cell.attrs
+    }
+}
+
+/// Per-line size set by DECDWL/DECDHL (`ESC # 3`..`ESC # 6`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LineSize {
+    #[default]
+    Single,
+    /// Every character takes two columns
+    DoubleWidth,
+    /// Top half of double-height, double-width text
+    DoubleHeightTop,
+    /// Bottom half of double-height, double-width text
+    DoubleHeightBottom,
+}
+
+impl LineSize {
+    /// Whether characters on this line take two columns
+    pub fn is_double_width(self) -> bool {
+        self != LineSize::Single
+    }
+}
+
+/// Shell-integration boundaries reported with OSC 133
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PromptMarkKind {
+    /// `A`: the prompt is about to be drawn
+    PromptStart,
+    /// `B`: the prompt is done and the user is typing a command
+    CommandStart,
+    /// `C`: the command is running and its output starts here
+    OutputStart,
+    /// `D`: the command finished
+    CommandEnd,
+}
+
+/// An OSC 133 mark and the history row the cursor was on when it arrived
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PromptMark {
+    pub kind: PromptMarkKind,
+    /// History row (see `TerminalBuffer::history_row`)
+    pub row: usize,
+}
+
 /// The terminal screen buffer
+///
+/// ```
+/// use helmux::TerminalBuffer;
+/// use ratatui::style::Color;
+///
+/// let mut buf = TerminalBuffer::new(80, 24);
+/// buf.process(b"\x1b[31mhi");
+/// let cell = buf.get_cell(0, 0).unwrap();
+/// assert_eq!(cell.character, 'h');
+/// assert_eq!(cell.fg, Color::Red);
+/// ```
 pub struct TerminalBuffer {
     /// Buffer width in columns
     width: u16,
@@ -91,6 +171,10 @@ pub struct TerminalBuffer {
     height: u16,
     /// The visible screen area (height rows of width cells each)
     cells: Vec<Vec<Cell>>,
+    /// Size attribute of each visible row (parallel to `cells`)
+    line_sizes: Vec<LineSize>,
+    /// Whether each visible row wrapped onto the next one (parallel to `cells`)
+    wrapped: Vec<bool>,
     /// Cursor position (row, col) - 0-indexed
     cursor_row: u16,
     cursor_col: u16,
@@ -98,8 +182,15 @@ pub struct TerminalBuffer {
     cursor_visible: bool,
     /// Scrollback buffer (lines that scrolled off the top)
     scrollback: VecDeque<Vec<Cell>>,
+    /// Wrap flag of each scrollback line (parallel to `scrollback`)
+    scrollback_wrapped: VecDeque<bool>,
     /// Maximum scrollback lines
     scrollback_limit: usize,
+    /// Lines dropped from the front of a full scrollback so far; every history
+    /// row moves up one for each
+    evicted_lines: usize,
+    /// How many lines the view is scrolled back into history (0 = live screen)
+    scroll_offset: usize,
     /// Current text attributes for new characters
     current_fg: Color,
     current_bg: Color,
@@ -107,10 +198,50 @@ pub struct TerminalBuffer {
     /// Scroll region (top, bottom) - 0-indexed, inclusive
     scroll_top: u16,
     scroll_bottom: u16,
+    /// Left/right margins (left, right) - 0-indexed, inclusive. Only narrower
+    /// than the screen while DECLRMM is on
+    scroll_left: u16,
+    scroll_right: u16,
+    /// DEC mode 69 (DECLRMM) - `CSI s` sets left/right margins instead of
+    /// saving the cursor
+    lr_margin_mode: bool,
+    /// Where printing left the cursor after filling the right margin's column,
+    /// so the next character wraps to the left margin. Kept as a position, so
+    /// moving the cursor cancels it
+    wrap_pending_at: Option<(u16, u16)>,
     /// Saved cursor position for save/restore
     saved_cursor: Option<(u16, u16)>,
     /// Origin mode - cursor positions relative to scroll region
     origin_mode: bool,
+    /// DEC mode 7 (DECAWM) - printing past the last column wraps to the next line
+    autowrap: bool,
+    /// Mode 4 (IRM) - printed characters push the rest of the line right
+    insert_mode: bool,
+    /// DEC mode 1 (DECCKM) - arrow keys send SS3 rather than CSI sequences
+    application_cursor_keys: bool,
+    /// DEC mode 2004 - the program wants pastes wrapped in ESC [200~ / ESC [201~
+    bracketed_paste: bool,
+    /// DEC mode 1004 - the program wants focus in/out reports
+    focus_reporting: bool,
+    /// DEC mouse tracking mode (9, 1000, 1002 or 1003) the program turned on, if any
+    mouse_mode: Option<u16>,
+    /// Targets of OSC 8 hyperlinks, by the id cells carry
+    links: HashMap<u32, String>,
+    /// Ids of the targets in `links`, so a repeated target is stored once
+    link_ids: HashMap<String, u32>,
+    /// Id the next new link target gets
+    next_link_id: u32,
+    /// Link that printed text currently belongs to
+    current_link: Option<u32>,
+    /// kitty keyboard protocol flags pushed by the program, innermost last
+    kitty_keyboard: Vec<u16>,
+    /// OSC 133 shell-integration marks, oldest first
+    prompt_marks: Vec<PromptMark>,
+    /// RGB overrides for the 16 base ANSI colors, if the user configured a palette
+    palette: Option<Palette>,
+    /// Escape sequence parser, kept between calls so a sequence split across
+    /// two chunks of output is still recognised
+    parser: vte::Parser,
 }
 
 impl TerminalBuffer {
@@ -121,27 +252,67 @@ impl TerminalBuffer {
             width,
             height,
             cells,
+            line_sizes: vec![LineSize::Single; height as usize],
+            wrapped: vec![false; height as usize],
             cursor_row: 0,
             cursor_col: 0,
             cursor_visible: true,
             scrollback: VecDeque::with_capacity(DEFAULT_SCROLLBACK),
+            scrollback_wrapped: VecDeque::with_capacity(DEFAULT_SCROLLBACK),
             scrollback_limit: DEFAULT_SCROLLBACK,
+            evicted_lines: 0,
+            scroll_offset: 0,
             current_fg: Color::Reset,
             current_bg: Color::Reset,
             current_attrs: CellAttributes::default(),
             scroll_top: 0,
             scroll_bottom: height.saturating_sub(1),
+            scroll_left: 0,
+            scroll_right: width.saturating_sub(1),
+            lr_margin_mode: false,
+            wrap_pending_at: None,
             saved_cursor: None,
             origin_mode: false,
+            autowrap: true,
+            insert_mode: false,
+            application_cursor_keys: false,
+            bracketed_paste: false,
+            focus_reporting: false,
+            mouse_mode: None,
+            links: HashMap::new(),
+            link_ids: HashMap::new(),
+            next_link_id: 0,
+            current_link: None,
+            kitty_keyboard: Vec::new(),
+            prompt_marks: Vec::new(),
+            palette: None,
+            parser: vte::Parser::new(),
+        }
+    }
+
+    /// Use the given colors for ANSI indices 0-15 in output processed from now on,
+    /// instead of the terminal's named colors
+    pub fn set_palette(&mut self, palette: Option<Palette>) {
+        self.palette = palette;
+    }
+
+    /// Resolve an ANSI/256-color index, honoring the configured palette
+    fn ansi_color(&self, code: u16) -> Color {
+        match &self.palette {
+            Some(palette) if code < 16 => palette[code as usize],
+            _ => ansi_to_color(code),
         }
     }
 
     /// Process raw bytes from terminal output
     pub fn process(&mut self, data: &[u8]) {
-        let mut parser = vte::Parser::new();
+        let _span = tracing::trace_span!("process", bytes = data.len()).entered();
+        // The parser is taken out while it runs so it can borrow the buffer
+        let mut parser = std::mem::take(&mut self.parser);
         for byte in data {
             parser.advance(self, *byte);
         }
+        self.parser = parser;
     }
 
     /// Get buffer dimensions
@@ -159,11 +330,223 @@ impl TerminalBuffer {
         self.cursor_visible
     }
 
+    /// Scroll region rows (top, bottom), 0-indexed and inclusive
+    pub fn scroll_region(&self) -> (u16, u16) {
+        (self.scroll_top, self.scroll_bottom)
+    }
+
+    /// Whether cursor positions are relative to the scroll region (DECOM)
+    pub fn origin_mode(&self) -> bool {
+        self.origin_mode
+    }
+
+    /// Whether printing past the last column wraps (DECAWM)
+    pub fn autowrap(&self) -> bool {
+        self.autowrap
+    }
+
+    /// Whether printed characters are inserted rather than overwriting (IRM)
+    pub fn insert_mode(&self) -> bool {
+        self.insert_mode
+    }
+
+    /// Whether arrow keys should be sent in application mode (DECCKM)
+    pub fn application_cursor_keys(&self) -> bool {
+        self.application_cursor_keys
+    }
+
+    /// Whether the program asked for bracketed paste (DEC mode 2004)
+    pub fn bracketed_paste(&self) -> bool {
+        self.bracketed_paste
+    }
+
+    /// Check if the program asked for focus in/out reports (DEC mode 1004)
+    pub fn focus_reporting(&self) -> bool {
+        self.focus_reporting
+    }
+
+    /// Whether the program is tracking the mouse itself (DEC modes 9/1000/1002/1003)
+    pub fn mouse_tracking(&self) -> bool {
+        self.mouse_mode.is_some()
+    }
+
+    /// Target of the OSC 8 link with the given id
+    pub fn link(&self, id: u32) -> Option<&str> {
+        self.links.get(&id).map(String::as_str)
+    }
+
+    /// Target of the OSC 8 link under a displayed cell, if it's part of one
+    pub fn link_at(&self, row: u16, col: u16) -> Option<&str> {
+        let cell = self.display_row(row)?.get(col as usize)?;
+        self.link(cell.link?)
+    }
+
+    /// First and last columns of the word around a displayed cell: a run of
+    /// alphanumerics and `word_chars`. None when the cell isn't in a word
+    pub fn word_bounds(&self, row: u16, col: u16, word_chars: &str) -> Option<(u16, u16)> {
+        let cells = self.display_row(row)?;
+        let is_word = |cell: &Cell| cell.character.is_alphanumeric() || word_chars.contains(cell.character);
+        if !cells.get(col as usize).is_some_and(is_word) {
+            return None;
+        }
+        let col = col as usize;
+        let start = cells[..col].iter().rposition(|c| !is_word(c)).map_or(0, |i| i + 1);
+        let end = cells[col..].iter().position(|c| !is_word(c)).map_or(cells.len(), |i| col + i);
+        Some((start as u16, end as u16 - 1))
+    }
+
+    /// History rows of the logical line a displayed row is part of, joining
+    /// the rows it wrapped from and onto
+    pub fn line_bounds(&self, row: u16) -> Range<usize> {
+        let row = self.history_row(row);
+        let wraps = |r: usize| self.history_line(r).is_some_and(|(_, wrapped)| wrapped);
+        let mut start = row;
+        while start > 0 && wraps(start - 1) {
+            start -= 1;
+        }
+        let mut end = row;
+        while wraps(end) {
+            end += 1;
+        }
+        start..end + 1
+    }
+
+    /// A bare `http://` or `https://` URL printed on a displayed row that spans
+    /// the given column, for programs that don't mark links with OSC 8
+    pub fn url_at(&self, row: u16, col: u16) -> Option<String> {
+        let chars: Vec<char> = self.display_row(row)?.iter().map(|cell| cell.character).collect();
+        let col = col as usize;
+        if !chars.get(col).copied().is_some_and(is_url_char) {
+            return None;
+        }
+        // The run of URL characters around the column
+        let start = chars[..col].iter().rposition(|&c| !is_url_char(c)).map_or(0, |i| i + 1);
+        let end = chars[col..].iter().position(|&c| !is_url_char(c)).map_or(chars.len(), |i| col + i);
+        let token: String = chars[start..end].iter().collect();
+
+        // The last scheme starting at or before the column, so "see:https://x" works
+        let offset = ["https://", "http://"]
+            .iter()
+            .filter_map(|scheme| token.match_indices(scheme).map(|(i, _)| i).filter(|&i| i <= col - start).max())
+            .max()?;
+        let url = trim_url(&token[offset..]);
+        let has_host = url.split_once("://").is_some_and(|(_, rest)| !rest.is_empty());
+        (has_host && col - start < offset + url.len()).then(|| url.to_string())
+    }
+
+    /// Start or (with an empty target) end an OSC 8 link for printed text
+    fn set_link(&mut self, uri: &[u8]) {
+        if uri.is_empty() {
+            self.current_link = None;
+            return;
+        }
+        let uri = String::from_utf8_lossy(uri);
+        if let Some(&id) = self.link_ids.get(uri.as_ref()) {
+            self.current_link = Some(id);
+            return;
+        }
+        if self.links.len() >= MAX_LINKS {
+            self.reclaim_links();
+        }
+        self.current_link = (self.links.len() < MAX_LINKS).then(|| {
+            let id = self.next_link_id;
+            self.next_link_id = self.next_link_id.wrapping_add(1);
+            self.links.insert(id, uri.to_string());
+            self.link_ids.insert(uri.into_owned(), id);
+            id
+        });
+    }
+
+    /// Forget link targets no cell carries any more: their text has been
+    /// overwritten, cleared or dropped from scrollback
+    fn reclaim_links(&mut self) {
+        let mut used: HashSet<u32> =
+            self.cells.iter().chain(&self.scrollback).flatten().filter_map(|cell| cell.link).collect();
+        used.extend(self.current_link);
+        self.links.retain(|id, _| used.contains(id));
+        self.link_ids.retain(|_, id| used.contains(id));
+    }
+
+    /// kitty keyboard protocol flags currently in effect (0 = legacy key encoding)
+    pub fn kitty_keyboard_flags(&self) -> u16 {
+        self.kitty_keyboard.last().copied().unwrap_or(0)
+    }
+
+    /// Handle the kitty keyboard protocol's CSI > u (push), CSI < u (pop) and
+    /// CSI = u (set), after the private marker
+    fn kitty_keyboard_dispatch(&mut self, marker: u8, params: &Params) {
+        match marker {
+            b'>' => {
+                if self.kitty_keyboard.len() >= MAX_KITTY_KEYBOARD_STACK {
+                    self.kitty_keyboard.remove(0);
+                }
+                self.kitty_keyboard.push(param(params, 0, 0));
+            }
+            b'<' => {
+                let count = param(params, 0, 1) as usize;
+                let keep = self.kitty_keyboard.len().saturating_sub(count);
+                self.kitty_keyboard.truncate(keep);
+            }
+            b'=' => {
+                let flags = param(params, 0, 0);
+                let current = self.kitty_keyboard_flags();
+                let updated = match param(params, 1, 1) {
+                    2 => current | flags,
+                    3 => current & !flags,
+                    _ => flags,
+                };
+                match self.kitty_keyboard.last_mut() {
+                    Some(top) => *top = updated,
+                    None => self.kitty_keyboard.push(updated),
+                }
+            }
+            _ => {}
+        }
+    }
+
     /// Get a reference to the cells grid
     pub fn cells(&self) -> &Vec<Vec<Cell>> {
         &self.cells
     }
 
+    /// Copy of the visible rows, for rendering or comparing elsewhere
+    pub fn snapshot(&self) -> Vec<Vec<Cell>> {
+        self.cells.clone()
+    }
+
+    /// The visible rows, each as runs of identically styled cells
+    pub fn styled_rows(&self) -> impl Iterator<Item = Vec<StyledRun>> + '_ {
+        self.cells.iter().map(|row| {
+            let mut runs: Vec<StyledRun> = Vec::new();
+            for cell in row {
+                match runs.last_mut() {
+                    Some(run) if run.matches(cell) => run.text.push(cell.character),
+                    _ => runs.push(StyledRun {
+                        text: cell.character.to_string(),
+                        fg: cell.fg,
+                        bg: cell.bg,
+                        attrs: cell.attrs,
+                    }),
+                }
+            }
+            runs
+        })
+    }
+
+    /// Get the size attribute of a screen row
+    pub fn line_size(&self, row: u16) -> LineSize {
+        self.line_sizes.get(row as usize).copied().unwrap_or_default()
+    }
+
+    /// Get the size attribute of a row as displayed, taking the scroll offset into account
+    /// Scrollback doesn't keep line sizes, so history rows are always single size
+    pub fn display_line_size(&self, row: u16) -> LineSize {
+        match (row as usize).checked_sub(self.scroll_offset) {
+            Some(screen_row) => self.line_size(screen_row as u16),
+            None => LineSize::Single,
+        }
+    }
+
     /// Get a cell at the given position
     pub fn get_cell(&self, row: u16, col: u16) -> Option<&Cell> {
         self.cells
@@ -171,6 +554,270 @@ impl TerminalBuffer {
             .and_then(|r| r.get(col as usize))
     }
 
+    /// Get the number of lines in the scrollback buffer
+    pub fn scrollback_len(&self) -> usize {
+        self.scrollback.len()
+    }
+
+    /// How many lines have been dropped from the front of a full scrollback, so
+    /// history rows saved elsewhere can be moved up to match
+    pub fn evicted_lines(&self) -> usize {
+        self.evicted_lines
+    }
+
+    /// Get how many lines the view is scrolled back (0 = showing the live screen)
+    pub fn scroll_offset(&self) -> usize {
+        self.scroll_offset
+    }
+
+    /// Check if the view is scrolled back into history
+    pub fn is_scrolled(&self) -> bool {
+        self.scroll_offset > 0
+    }
+
+    /// Whether the view follows new output, like `less +F`: true while it's at
+    /// the bottom, false once scrolled up until scrolled back down
+    pub fn is_following(&self) -> bool {
+        self.scroll_offset == 0
+    }
+
+    /// Scroll the view back into history by the given number of lines
+    pub fn scroll_view_up(&mut self, lines: usize) {
+        self.scroll_offset = (self.scroll_offset + lines).min(self.scrollback.len());
+    }
+
+    /// Scroll the view forward towards the live screen by the given number of lines
+    pub fn scroll_view_down(&mut self, lines: usize) {
+        self.scroll_offset = self.scroll_offset.saturating_sub(lines);
+    }
+
+    /// Snap the view back to the live screen
+    pub fn reset_scroll(&mut self) {
+        self.scroll_offset = 0;
+    }
+
+    /// Drop all scrollback history and snap the view back to the live screen
+    pub fn clear_scrollback(&mut self) {
+        let dropped = self.scrollback.len();
+        self.scrollback.clear();
+        self.scrollback_wrapped.clear();
+        self.scroll_offset = 0;
+        self.reclaim_links();
+        // Marks on the screen keep their place, now with no history above them
+        self.prompt_marks.retain(|mark| mark.row >= dropped);
+        for mark in &mut self.prompt_marks {
+            mark.row -= dropped;
+        }
+    }
+
+    /// Get a row as displayed, taking the scroll offset into account
+    /// Row 0 is the top of the view; rows above the live screen come from scrollback
+    pub fn display_row(&self, row: u16) -> Option<&[Cell]> {
+        let row = row as usize;
+        if row < self.scroll_offset {
+            let idx = self.scrollback.len() - self.scroll_offset + row;
+            self.scrollback.get(idx).map(|r| r.as_slice())
+        } else {
+            self.cells.get(row - self.scroll_offset).map(|r| r.as_slice())
+        }
+    }
+
+    /// Map a displayed row to its history row
+    /// History rows number the scrollback from its oldest line (0) and continue
+    /// through the live screen, so they don't move as the view scrolls
+    pub fn history_row(&self, display_row: u16) -> usize {
+        self.scrollback.len() - self.scroll_offset + display_row as usize
+    }
+
+    /// Scroll the view so a history row is shown, centered where possible
+    pub fn scroll_to_history_row(&mut self, row: usize) {
+        let half = self.height as usize / 2;
+        self.scroll_offset = (self.scrollback.len() + half)
+            .saturating_sub(row)
+            .min(self.scrollback.len());
+    }
+
+    /// Scroll the view back to the nearest shell prompt above history row `from_row`,
+    /// putting it at the top of the view. Returns the prompt's history row, if any
+    pub fn prev_prompt(&mut self, from_row: usize) -> Option<usize> {
+        let row = self.prompt_rows().into_iter().rev().find(|&row| row < from_row)?;
+        self.scroll_offset = self.scrollback.len().saturating_sub(row);
+        Some(row)
+    }
+
+    /// Scroll the view forward to the nearest shell prompt below history row `from_row`,
+    /// putting it at the top of the view (or snapping to the live screen if it's there)
+    /// Returns None when already showing the live screen, as there's nothing below
+    pub fn next_prompt(&mut self, from_row: usize) -> Option<usize> {
+        if self.scroll_offset == 0 {
+            return None;
+        }
+        let row = self.prompt_rows().into_iter().find(|&row| row > from_row)?;
+        self.scroll_offset = self.scrollback.len().saturating_sub(row);
+        Some(row)
+    }
+
+    /// Find every occurrence of `query` in the scrollback and screen, ignoring case
+    /// Returns (history row, column) of each match's first character, oldest first.
+    /// Lines that wrapped are searched as one, so a match can continue onto the next row
+    pub fn search(&self, query: &str) -> Vec<(usize, usize)> {
+        let needle: Vec<char> = query.chars().map(fold_case).collect();
+        if needle.is_empty() {
+            return Vec::new();
+        }
+
+        let rows: Vec<(&Vec<Cell>, bool)> = self
+            .scrollback
+            .iter()
+            .zip(self.scrollback_wrapped.iter().copied())
+            .chain(self.cells.iter().zip(self.wrapped.iter().copied()))
+            .collect();
+
+        let mut matches = Vec::new();
+        let mut start = 0;
+        while start < rows.len() {
+            // Gather one logical line: this row plus any rows it wrapped onto
+            let mut end = start;
+            while rows[end].1 && end + 1 < rows.len() {
+                end += 1;
+            }
+            let mut text = Vec::new();
+            let mut positions = Vec::new();
+            for (row, (cells, _)) in rows[start..=end].iter().enumerate() {
+                for (col, cell) in cells.iter().enumerate() {
+                    text.push(fold_case(cell.character));
+                    positions.push((start + row, col));
+                }
+            }
+
+            let mut i = 0;
+            while i + needle.len() <= text.len() {
+                if text[i..i + needle.len()] == needle[..] {
+                    matches.push(positions[i]);
+                    i += needle.len();
+                } else {
+                    i += 1;
+                }
+            }
+            start = end + 1;
+        }
+        matches
+    }
+
+    /// Get the OSC 133 marks still on the screen or in scrollback, oldest first
+    /// Rows are kept in step as lines scroll into (and out of) history; marks on
+    /// lines that are erased or scroll out of a partial scroll region are dropped
+    pub fn prompt_marks(&self) -> &[PromptMark] {
+        &self.prompt_marks
+    }
+
+    /// History rows where shell prompts started (OSC 133 `A`), oldest first
+    pub fn prompt_rows(&self) -> Vec<usize> {
+        let mut rows: Vec<usize> = self
+            .prompt_marks
+            .iter()
+            .filter(|mark| mark.kind == PromptMarkKind::PromptStart)
+            .map(|mark| mark.row)
+            .collect();
+        rows.dedup();
+        rows
+    }
+
+    /// History rows holding the output of the most recent finished command,
+    /// from its OSC 133 `C` mark up to (not including) its `D` mark
+    pub fn last_output_rows(&self) -> Option<Range<usize>> {
+        let mut output_start = None;
+        let mut last = None;
+        for mark in &self.prompt_marks {
+            match mark.kind {
+                PromptMarkKind::OutputStart => output_start = Some(mark.row),
+                PromptMarkKind::CommandEnd => {
+                    if let Some(start) = output_start.take() {
+                        last = Some(start..mark.row);
+                    }
+                }
+                // A new prompt without a D means the command never reported finishing
+                PromptMarkKind::PromptStart => output_start = None,
+                PromptMarkKind::CommandStart => {}
+            }
+        }
+        last
+    }
+
+    /// Get history rows as plain text
+    /// Rows that wrapped are joined into one line; trailing spaces and trailing
+    /// blank lines are dropped
+    pub fn history_text(&self, rows: Range<usize>) -> String {
+        let mut lines = Vec::new();
+        let mut line = String::new();
+        for row in rows {
+            let Some((cells, wrapped)) = self.history_line(row) else {
+                break;
+            };
+            line.extend(cells.iter().map(|cell| cell.character));
+            if !wrapped {
+                lines.push(line.trim_end().to_string());
+                line.clear();
+            }
+        }
+        if !line.is_empty() {
+            lines.push(line.trim_end().to_string());
+        }
+        let used = lines.iter().rposition(|l| !l.is_empty()).map_or(0, |i| i + 1);
+        lines[..used].join("\n")
+    }
+
+    /// Get a history row's cells and whether it wrapped onto the next row
+    fn history_line(&self, row: usize) -> Option<(&[Cell], bool)> {
+        if row < self.scrollback.len() {
+            Some((&self.scrollback[row], self.scrollback_wrapped[row]))
+        } else {
+            let row = row - self.scrollback.len();
+            Some((self.cells.get(row)?, self.wrapped[row]))
+        }
+    }
+
+    /// Record an OSC 133 mark at the cursor's row
+    fn add_prompt_mark(&mut self, kind: PromptMarkKind) {
+        let row = self.scrollback.len() + self.cursor_row as usize;
+        self.prompt_marks.push(PromptMark { kind, row });
+    }
+
+    /// Forget prompt marks on the given screen rows, whose content is gone or moved
+    fn drop_prompt_marks(&mut self, screen_rows: RangeInclusive<usize>) {
+        let first = self.scrollback.len() + screen_rows.start();
+        let last = self.scrollback.len() + screen_rows.end();
+        self.prompt_marks.retain(|mark| mark.row < first || mark.row > last);
+    }
+
+    /// Check whether the scrollback or visible screen contains `query`, ignoring case
+    /// Each row is matched on its own
+    pub fn contains_text(&self, query: &str) -> bool {
+        let query = query.to_lowercase();
+        if query.is_empty() {
+            return false;
+        }
+        self.scrollback
+            .iter()
+            .chain(self.cells.iter())
+            .any(|row| {
+                let line: String = row.iter().map(|cell| cell.character).collect();
+                line.to_lowercase().contains(&query)
+            })
+    }
+
+    /// Get the visible screen as plain text, one line per row
+    /// Trailing spaces on each row and trailing blank rows are dropped
+    pub fn to_text(&self) -> String {
+        rows_to_text(self.cells.iter())
+    }
+
+    /// Get the scrollback followed by the visible screen as plain text, one line
+    /// per row, trimmed like `to_text`
+    pub fn to_text_with_scrollback(&self) -> String {
+        rows_to_text(self.scrollback.iter().chain(self.cells.iter()))
+    }
+
     /// Resize the buffer
     pub fn resize(&mut self, new_width: u16, new_height: u16) {
         if new_width == self.width && new_height == self.height {
@@ -185,6 +832,10 @@ impl TerminalBuffer {
         // Add or remove rows
         self.cells
             .resize(new_height as usize, vec![Cell::default(); new_width as usize]);
+        self.line_sizes.resize(new_height as usize, LineSize::Single);
+        self.wrapped.resize(new_height as usize, false);
+        let rows_kept = self.scrollback.len() + new_height as usize;
+        self.prompt_marks.retain(|mark| mark.row < rows_kept);
 
         self.width = new_width;
         self.height = new_height;
@@ -194,6 +845,7 @@ impl TerminalBuffer {
         if self.scroll_top >= new_height {
             self.scroll_top = 0;
         }
+        self.reset_horizontal_margins();
 
         // Clamp cursor
         self.cursor_row = self.cursor_row.min(new_height.saturating_sub(1));
@@ -207,6 +859,9 @@ impl TerminalBuffer {
                 *cell = Cell::default();
             }
         }
+        self.line_sizes.fill(LineSize::Single);
+        self.wrapped.fill(false);
+        self.drop_prompt_marks(0..=self.height.saturating_sub(1) as usize);
         self.cursor_row = 0;
         self.cursor_col = 0;
     }
@@ -270,93 +925,182 @@ impl TerminalBuffer {
 
     /// Write a character at the current cursor position
     fn write_char(&mut self, c: char) {
-        if self.cursor_col >= self.width {
+        // Lines end at the right margin, or at the screen edge for text printed
+        // past it
+        let at_line_end = self.wrap_pending_at.take() == Some((self.cursor_row, self.cursor_col))
+            || self.cursor_col >= self.width;
+        if at_line_end && !self.autowrap {
+            // Keep overwriting the last column
+            self.cursor_col = self.cursor_col.saturating_sub(1).min(self.width.saturating_sub(1));
+        } else if at_line_end {
             // Wrap to next line
-            self.cursor_col = 0;
-            self.move_cursor_down(1);
+            if let Some(wrapped) = self.wrapped.get_mut(self.cursor_row as usize) {
+                *wrapped = true;
+            }
+            self.cursor_col = self.scroll_left;
+            self.linefeed();
         }
 
+        if self.insert_mode {
+            self.insert_chars(1);
+        }
         if let Some(row) = self.cells.get_mut(self.cursor_row as usize) {
             if let Some(cell) = row.get_mut(self.cursor_col as usize) {
                 *cell = Cell::with_style(c, self.current_fg, self.current_bg, self.current_attrs);
+                cell.link = self.current_link;
             }
         }
 
+        if self.cursor_col == self.scroll_right {
+            self.wrap_pending_at = Some((self.cursor_row, self.cursor_col + 1));
+        }
         self.cursor_col += 1;
     }
 
-    /// Move cursor down, scrolling if necessary
+    /// Move cursor down without scrolling. It stops at the bottom margin when
+    /// starting inside the scroll region or in origin mode, else at the last row
     fn move_cursor_down(&mut self, count: u16) {
-        for _ in 0..count {
-            if self.cursor_row >= self.scroll_bottom {
-                self.scroll_up(1);
-            } else {
-                self.cursor_row += 1;
+        let max_row = if self.origin_mode || self.cursor_row <= self.scroll_bottom {
+            self.scroll_bottom
+        } else {
+            self.height.saturating_sub(1)
+        };
+        self.cursor_row = self.cursor_row.saturating_add(count).min(max_row);
+    }
+
+    /// Move cursor up. It stops at the top margin when starting inside the
+    /// scroll region or in origin mode, else at the first row
+    fn move_cursor_up(&mut self, count: u16) {
+        let min_row = if self.origin_mode || self.cursor_row >= self.scroll_top {
+            self.scroll_top
+        } else {
+            0
+        };
+        self.cursor_row = self.cursor_row.saturating_sub(count).max(min_row);
+    }
+
+    /// Swap two screen rows along with their line sizes
+    fn swap_rows(&mut self, a: usize, b: usize) {
+        self.cells.swap(a, b);
+        self.line_sizes.swap(a, b);
+        self.wrapped.swap(a, b);
+    }
+
+    /// Reset a screen row to blank, single-size cells
+    fn blank_row(&mut self, row: usize) {
+        if let Some(cells) = self.cells.get_mut(row) {
+            for cell in cells {
+                *cell = Cell::default();
             }
         }
+        if let Some(size) = self.line_sizes.get_mut(row) {
+            *size = LineSize::Single;
+        }
+        if let Some(wrapped) = self.wrapped.get_mut(row) {
+            *wrapped = false;
+        }
     }
 
-    /// Move cursor up
-    fn move_cursor_up(&mut self, count: u16) {
-        self.cursor_row = self.cursor_row.saturating_sub(count).max(self.scroll_top);
+    /// Set the size attribute of the cursor's row
+    fn set_line_size(&mut self, size: LineSize) {
+        if let Some(line) = self.line_sizes.get_mut(self.cursor_row as usize) {
+            *line = size;
+        }
     }
 
-    /// Scroll the screen up (content moves up, new blank line at bottom)
+    /// Scroll the screen up (content moves up, new blank line at bottom).
+    /// Lines leaving a region that starts at the top row go to scrollback
     fn scroll_up(&mut self, count: u16) {
+        // Past the region height every further line would be a blank one
+        let count = count.min(self.scroll_bottom - self.scroll_top + 1);
         for _ in 0..count {
-            // Move top line of scroll region to scrollback
+            // Move top line of scroll region to scrollback. Marks on the moved
+            // lines keep their history row; any below a partial region move down
+            // a row, as history grows above them
             if self.scroll_top == 0 {
                 let line = self.cells[0].clone();
                 if self.scrollback.len() >= self.scrollback_limit {
                     self.scrollback.pop_front();
+                    self.scrollback_wrapped.pop_front();
+                    self.evicted_lines += 1;
+                    // Everything moves up a history row; marks on the evicted line go
+                    self.prompt_marks.retain(|mark| mark.row > 0);
+                    for mark in &mut self.prompt_marks {
+                        mark.row -= 1;
+                    }
                 }
                 self.scrollback.push_back(line);
+                self.scrollback_wrapped.push_back(self.wrapped[0]);
+                // A view scrolled back into history stays on the same lines
+                // rather than drifting with new output
+                if !self.is_following() {
+                    self.scroll_offset = (self.scroll_offset + 1).min(self.scrollback.len());
+                }
+                let below_region = self.scrollback.len() + self.scroll_bottom as usize;
+                for mark in &mut self.prompt_marks {
+                    if mark.row >= below_region {
+                        mark.row += 1;
+                    }
+                }
+            } else {
+                self.drop_prompt_marks(self.scroll_top as usize..=self.scroll_bottom as usize);
             }
 
             // Shift lines up within scroll region
             for row in self.scroll_top as usize..self.scroll_bottom as usize {
-                self.cells.swap(row, row + 1);
+                self.swap_rows(row, row + 1);
             }
 
             // Clear the bottom line of scroll region
-            if let Some(row) = self.cells.get_mut(self.scroll_bottom as usize) {
-                for cell in row {
-                    *cell = Cell::default();
-                }
-            }
+            self.blank_row(self.scroll_bottom as usize);
         }
     }
 
     /// Scroll the screen down (content moves down, new blank line at top)
     fn scroll_down(&mut self, count: u16) {
+        let count = count.min(self.scroll_bottom - self.scroll_top + 1);
+        if count > 0 {
+            self.drop_prompt_marks(self.scroll_top as usize..=self.scroll_bottom as usize);
+        }
         for _ in 0..count {
             // Shift lines down within scroll region
             for row in ((self.scroll_top as usize + 1)..=self.scroll_bottom as usize).rev() {
-                self.cells.swap(row, row - 1);
+                self.swap_rows(row, row - 1);
             }
 
             // Clear the top line of scroll region
-            if let Some(row) = self.cells.get_mut(self.scroll_top as usize) {
-                for cell in row {
-                    *cell = Cell::default();
-                }
-            }
+            self.blank_row(self.scroll_top as usize);
         }
     }
 
     /// Set cursor position (1-indexed input, converted to 0-indexed)
     fn set_cursor_position(&mut self, row: u16, col: u16) {
-        let row = row.saturating_sub(1); // Convert from 1-indexed
-        let col = col.saturating_sub(1);
+        self.set_cursor_row(row);
+        self.set_cursor_col(col);
+    }
 
-        let (min_row, max_row) = if self.origin_mode {
-            (self.scroll_top, self.scroll_bottom)
+    /// Move the cursor to a 1-indexed column, leaving its row alone. In origin
+    /// mode the column counts from the left margin and stays inside the margins
+    fn set_cursor_col(&mut self, col: u16) {
+        let col = col.saturating_sub(1); // Convert from 1-indexed
+
+        self.cursor_col = if self.origin_mode {
+            self.scroll_left.saturating_add(col).min(self.scroll_right)
         } else {
-            (0, self.height.saturating_sub(1))
+            col.min(self.width.saturating_sub(1))
         };
+    }
+
+    /// Move the cursor to a 1-indexed row, leaving its column alone. In origin
+    /// mode the row counts from the top of the scroll region and stays inside it
+    fn set_cursor_row(&mut self, row: u16) {
+        let row = row.saturating_sub(1); // Convert from 1-indexed
 
-        self.cursor_row = row.clamp(min_row, max_row);
-        self.cursor_col = col.min(self.width.saturating_sub(1));
+        self.cursor_row = if self.origin_mode {
+            self.scroll_top.saturating_add(row).min(self.scroll_bottom)
+        } else {
+            row.min(self.height.saturating_sub(1))
+        };
     }
 
     /// Insert blank lines at cursor position
@@ -368,15 +1112,11 @@ impl TerminalBuffer {
         for _ in 0..count {
             // Shift lines down from cursor to bottom of scroll region
             for row in ((self.cursor_row as usize + 1)..=self.scroll_bottom as usize).rev() {
-                self.cells.swap(row, row - 1);
+                self.swap_rows(row, row - 1);
             }
 
             // Clear the line at cursor
-            if let Some(row) = self.cells.get_mut(self.cursor_row as usize) {
-                for cell in row {
-                    *cell = Cell::default();
-                }
-            }
+            self.blank_row(self.cursor_row as usize);
         }
     }
 
@@ -389,78 +1129,118 @@ impl TerminalBuffer {
         for _ in 0..count {
             // Shift lines up from cursor to bottom of scroll region
             for row in self.cursor_row as usize..self.scroll_bottom as usize {
-                self.cells.swap(row, row + 1);
+                self.swap_rows(row, row + 1);
             }
 
             // Clear the bottom line of scroll region
-            if let Some(row) = self.cells.get_mut(self.scroll_bottom as usize) {
-                for cell in row {
-                    *cell = Cell::default();
-                }
-            }
+            self.blank_row(self.scroll_bottom as usize);
         }
     }
 
-    /// Delete characters at cursor position
+    /// Columns from the cursor to the right margin, or None when the cursor is
+    /// outside the left/right margins
+    fn cursor_to_right_margin(&self) -> Option<Range<usize>> {
+        let inside = self.cursor_col >= self.scroll_left && self.cursor_col <= self.scroll_right;
+        inside.then(|| self.cursor_col as usize..self.scroll_right as usize + 1)
+    }
+
+    /// Delete characters at cursor position, pulling in blanks at the right margin
     fn delete_chars(&mut self, count: u16) {
+        let Some(cols) = self.cursor_to_right_margin() else {
+            return;
+        };
         if let Some(row) = self.cells.get_mut(self.cursor_row as usize) {
-            let start = self.cursor_col as usize;
-            let count = count as usize;
-            let width = self.width as usize;
-
-            // Shift characters left
-            for col in start..width {
-                let src = col + count;
-                row[col] = if src < width {
-                    row[src].clone()
-                } else {
-                    Cell::default()
-                };
-            }
+            shift_cells_left(&mut row[cols], count as usize);
         }
     }
 
-    /// Insert blank characters at cursor position
+    /// Insert blank characters at cursor position, dropping any pushed past the
+    /// right margin
     fn insert_chars(&mut self, count: u16) {
+        let Some(cols) = self.cursor_to_right_margin() else {
+            return;
+        };
         if let Some(row) = self.cells.get_mut(self.cursor_row as usize) {
-            let start = self.cursor_col as usize;
-            let count = count as usize;
-            let width = self.width as usize;
-
-            // Shift characters right
-            for col in (start..width).rev() {
-                let dst = col + count;
-                if dst < width {
-                    row.swap(col, dst);
-                }
+            shift_cells_right(&mut row[cols], count as usize);
+        }
+    }
+
+    /// Erase characters up to the right margin (replace with blanks, don't shift)
+    fn erase_chars(&mut self, count: u16) {
+        let Some(cols) = self.cursor_to_right_margin() else {
+            return;
+        };
+        let end = cols.end.min(cols.start + count as usize);
+        if let Some(row) = self.cells.get_mut(self.cursor_row as usize) {
+            for cell in &mut row[cols.start..end] {
+                *cell = Cell::default();
             }
+        }
+    }
 
-            // Clear inserted positions
-            for col in start..(start + count).min(width) {
-                row[col] = Cell::default();
+    /// Shift the columns from `first_col` to the right margin of every row in
+    /// the scroll region left (`left == true`) or right by `count`, blanking
+    /// the columns uncovered
+    fn scroll_columns(&mut self, first_col: u16, count: u16, left: bool) {
+        if first_col < self.scroll_left || first_col > self.scroll_right {
+            return;
+        }
+        let cols = first_col as usize..self.scroll_right as usize + 1;
+        for row in &mut self.cells[self.scroll_top as usize..=self.scroll_bottom as usize] {
+            if left {
+                shift_cells_left(&mut row[cols.clone()], count as usize);
+            } else {
+                shift_cells_right(&mut row[cols.clone()], count as usize);
             }
         }
     }
 
-    /// Erase characters (replace with blanks, don't shift)
-    fn erase_chars(&mut self, count: u16) {
-        if let Some(row) = self.cells.get_mut(self.cursor_row as usize) {
-            for col in self.cursor_col as usize..(self.cursor_col + count) as usize {
-                if let Some(cell) = row.get_mut(col) {
-                    *cell = Cell::default();
-                }
+    /// Turn ANSI (non-DEC) modes on or off for SM/RM
+    fn set_ansi_modes(&mut self, params: &Params, on: bool) {
+        for mode in params.iter().filter_map(|p| p.first()) {
+            if *mode == 4 {
+                self.insert_mode = on; // IRM - Insert Mode
             }
         }
     }
 
-    /// Handle carriage return
+    /// Set the left/right margins (1-indexed, inclusive) while DECLRMM is on
+    fn set_horizontal_margins(&mut self, left: u16, right: u16) {
+        let left = left.saturating_sub(1).min(self.width.saturating_sub(1));
+        let right = right.saturating_sub(1).min(self.width.saturating_sub(1));
+
+        if left < right {
+            self.scroll_left = left;
+            self.scroll_right = right;
+            // Move cursor to home position
+            self.set_cursor_position(1, 1);
+        }
+    }
+
+    /// Put the left/right margins back at the screen edges
+    fn reset_horizontal_margins(&mut self) {
+        self.scroll_left = 0;
+        self.scroll_right = self.width.saturating_sub(1);
+    }
+
+    /// Handle carriage return: back to the left margin, or to the first column
+    /// from left of it
     fn carriage_return(&mut self) {
-        self.cursor_col = 0;
+        if self.cursor_col < self.scroll_left {
+            self.cursor_col = 0;
+        } else {
+            self.cursor_col = self.scroll_left;
+        }
     }
 
-    /// Handle newline/line feed
+    /// Handle newline/line feed, scrolling at the bottom of the scroll region.
+    /// Below the region the cursor just stops at the last row
     fn linefeed(&mut self) {
-        self.move_cursor_down(1);
+        if self.cursor_row == self.scroll_bottom {
+            self.scroll_up(1);
+        } else if self.cursor_row + 1 < self.height {
+            self.cursor_row += 1;
+        }
     }
 
     /// Handle backspace
@@ -472,9 +1252,15 @@ impl TerminalBuffer {
 
     /// Handle tab
     fn tab(&mut self) {
-        // Move to next tab stop (every 8 columns)
+        let last_col = self.width.saturating_sub(1);
+        if self.cursor_col >= last_col {
+            // Already at the right margin, or past it with a wrap pending; the
+            // next printed character decides whether to wrap
+            return;
+        }
+        // Move to next tab stop (every 8 columns), stopping at the margin
         let next_tab = ((self.cursor_col / 8) + 1) * 8;
-        self.cursor_col = next_tab.min(self.width.saturating_sub(1));
+        self.cursor_col = next_tab.min(last_col);
     }
 
     /// Reset all attributes to defaults
@@ -498,20 +1284,27 @@ impl TerminalBuffer {
     }
 
     /// Handle SGR (Select Graphic Rendition) parameters
-    fn handle_sgr(&mut self, params: &[u16]) {
-        if params.is_empty() {
+    fn handle_sgr(&mut self, params: &Params) {
+        // Each parameter with its colon-separated sub-parameters
+        let groups: Vec<&[u16]> = params.iter().collect();
+        if groups.is_empty() {
             self.reset_attributes();
             return;
         }
 
-        let mut iter = params.iter().peekable();
-        while let Some(&param) = iter.next() {
+        let mut i = 0;
+        while i < groups.len() {
+            let group = groups[i];
+            i += 1;
+            let param = group.first().copied().unwrap_or(0);
             match param {
                 0 => self.reset_attributes(),
                 1 => self.current_attrs.bold = true,
                 2 => {} // Dim (not widely supported)
                 3 => self.current_attrs.italic = true,
-                4 => self.current_attrs.underline = true,
+                // `4:0` turns underline off; other styles (`4:3` curly, ...) are
+                // drawn as a plain underline
+                4 => self.current_attrs.underline = group.get(1) != Some(&0),
                 5 | 6 => self.current_attrs.blink = true,
                 7 => self.current_attrs.reverse = true,
                 8 => self.current_attrs.hidden = true,
@@ -527,72 +1320,141 @@ impl TerminalBuffer {
                 29 => self.current_attrs.strikethrough = false,
 
                 // Standard foreground colors
-                30..=37 => self.current_fg = ansi_to_color(param - 30),
-                38 => {
-                    // Extended foreground color
-                    if let Some(&&mode) = iter.peek() {
-                        iter.next();
-                        match mode {
-                            5 => {
-                                // 256-color mode
-                                if let Some(&&color) = iter.peek() {
-                                    iter.next();
-                                    self.current_fg = ansi_to_color(color);
-                                }
-                            }
-                            2 => {
-                                // RGB mode
-                                let r = iter.next().copied().unwrap_or(0) as u8;
-                                let g = iter.next().copied().unwrap_or(0) as u8;
-                                let b = iter.next().copied().unwrap_or(0) as u8;
-                                self.current_fg = Color::Rgb(r, g, b);
-                            }
-                            _ => {}
-                        }
-                    }
-                }
-                39 => self.current_fg = Color::Reset, // Default foreground
-
+                30..=37 => self.current_fg = self.ansi_color(param - 30),
                 // Standard background colors
-                40..=47 => self.current_bg = ansi_to_color(param - 40),
-                48 => {
-                    // Extended background color
-                    if let Some(&&mode) = iter.peek() {
-                        iter.next();
-                        match mode {
-                            5 => {
-                                // 256-color mode
-                                if let Some(&&color) = iter.peek() {
-                                    iter.next();
-                                    self.current_bg = ansi_to_color(color);
-                                }
-                            }
-                            2 => {
-                                // RGB mode
-                                let r = iter.next().copied().unwrap_or(0) as u8;
-                                let g = iter.next().copied().unwrap_or(0) as u8;
-                                let b = iter.next().copied().unwrap_or(0) as u8;
-                                self.current_bg = Color::Rgb(r, g, b);
-                            }
-                            _ => {}
+                40..=47 => self.current_bg = self.ansi_color(param - 40),
+
+                // Extended colors, either `38:5:n` with sub-parameters or the
+                // older `38;5;n` spread over the following parameters
+                38 | 48 => {
+                    let color = if group.len() > 1 {
+                        self.extended_color(&group[1..], true).0
+                    } else {
+                        let rest: Vec<u16> = groups[i..]
+                            .iter()
+                            .take(4)
+                            .map(|g| g.first().copied().unwrap_or(0))
+                            .collect();
+                        let (color, used) = self.extended_color(&rest, false);
+                        i += used;
+                        color
+                    };
+                    if let Some(color) = color {
+                        if param == 38 {
+                            self.current_fg = color;
+                        } else {
+                            self.current_bg = color;
                         }
                     }
                 }
+                39 => self.current_fg = Color::Reset, // Default foreground
                 49 => self.current_bg = Color::Reset, // Default background
 
                 // Bright foreground colors
-                90..=97 => self.current_fg = ansi_to_color(param - 90 + 8),
+                90..=97 => self.current_fg = self.ansi_color(param - 90 + 8),
                 // Bright background colors
-                100..=107 => self.current_bg = ansi_to_color(param - 100 + 8),
+                100..=107 => self.current_bg = self.ansi_color(param - 100 + 8),
 
                 _ => {}
             }
         }
     }
+
+    /// Parse the values after a 38/48: `5;n` for a 256-color index or
+    /// `2;r;g;b` for RGB. The colon form may put a colorspace id before the
+    /// RGB values (`2::r:g:b`). Returns the color and how many values it used
+    fn extended_color(&self, values: &[u16], colon: bool) -> (Option<Color>, usize) {
+        let channel = |i: usize| values.get(i).copied().unwrap_or(0).min(255) as u8;
+        match values.first() {
+            Some(5) => match values.get(1) {
+                Some(&index) => (Some(self.ansi_color(index.min(255))), 2),
+                None => (None, 1),
+            },
+            Some(2) => {
+                let first = if colon && values.len() > 4 { 2 } else { 1 };
+                let color = Color::Rgb(channel(first), channel(first + 1), channel(first + 2));
+                (Some(color), values.len().min(4))
+            }
+            Some(_) => (None, 1),
+            None => (None, 0),
+        }
+    }
+}
+
+/// Channel intensities of xterm's 6x6x6 color cube
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+/// Colors for the 16 base ANSI indices (black, red, ... bright white)
+pub type Palette = [Color; 16];
+
+/// Join rows of cells into lines, trimming trailing spaces and trailing blank lines
+fn rows_to_text<'a>(rows: impl Iterator<Item = &'a Vec<Cell>>) -> String {
+    let lines: Vec<String> = rows
+        .map(|row| {
+            let line: String = row.iter().map(|cell| cell.character).collect();
+            line.trim_end().to_string()
+        })
+        .collect();
+    let used = lines.iter().rposition(|l| !l.is_empty()).map_or(0, |i| i + 1);
+    lines[..used].join("\n")
+}
+
+/// First value of CSI parameter `index`, or `default` when it's missing or
+/// empty. An empty parameter reaches us as 0, which means the default for
+/// every sequence that has one
+fn param(params: &Params, index: usize, default: u16) -> u16 {
+    match params.iter().nth(index).and_then(|p| p.first()) {
+        Some(&value) if value != 0 => value,
+        _ => default,
+    }
+}
+
+/// Whether a character can be part of a bare URL (printable ASCII that isn't
+/// a space, quote or angle bracket, which usually surround one)
+fn is_url_char(c: char) -> bool {
+    c.is_ascii_graphic() && !matches!(c, '"' | '\'' | '<' | '>' | '`')
+}
+
+/// Drop punctuation that ends the sentence a URL sits in rather than the URL,
+/// including a closing bracket with no opening one inside the URL
+fn trim_url(url: &str) -> &str {
+    let mut url = url;
+    loop {
+        let Some(last) = url.chars().last() else {
+            return url;
+        };
+        let unbalanced = |open: char| url.matches(open).count() < url.matches(last).count();
+        let trailing = match last {
+            '.' | ',' | ';' | ':' | '!' | '?' => true,
+            ')' => unbalanced('('),
+            ']' => unbalanced('['),
+            '}' => unbalanced('{'),
+            _ => false,
+        };
+        if !trailing {
+            return url;
+        }
+        url = &url[..url.len() - 1];
+    }
+}
+
+/// Move `cells` left by `count`, blanking the cells uncovered at the end
+fn shift_cells_left(cells: &mut [Cell], count: usize) {
+    let count = count.min(cells.len());
+    cells.rotate_left(count);
+    let len = cells.len();
+    cells[len - count..].fill(Cell::default());
+}
+
+/// Move `cells` right by `count`, blanking the cells uncovered at the start
+fn shift_cells_right(cells: &mut [Cell], count: usize) {
+    let count = count.min(cells.len());
+    cells.rotate_right(count);
+    cells[..count].fill(Cell::default());
 }
 
 /// Convert ANSI color code to ratatui Color
-fn ansi_to_color(code: u16) -> Color {
+pub fn ansi_to_color(code: u16) -> Color {
     match code {
         0 => Color::Black,
         1 => Color::Red,
@@ -611,12 +1473,9 @@ fn ansi_to_color(code: u16) -> Color {
         14 => Color::LightCyan,
         15 => Color::Gray,
         16..=231 => {
-            // 216 color cube: 16 + 36*r + 6*g + b
-            let c = code - 16;
-            let r = (c / 36) * 51;
-            let g = ((c / 6) % 6) * 51;
-            let b = (c % 6) * 51;
-            Color::Rgb(r as u8, g as u8, b as u8)
+            // 216 color cube: 16 + 36*r + 6*g + b, on xterm's (uneven) level ramp
+            let c = (code - 16) as usize;
+            Color::Rgb(CUBE_LEVELS[c / 36], CUBE_LEVELS[(c / 6) % 6], CUBE_LEVELS[c % 6])
         }
         232..=255 => {
             // Grayscale: 24 shades
@@ -627,6 +1486,15 @@ fn ansi_to_color(code: u16) -> Color {
     }
 }
 
+/// Lowercase a character for case-insensitive search, keeping one char per char
+fn fold_case(c: char) -> char {
+    let mut lower = c.to_lowercase();
+    match (lower.next(), lower.next()) {
+        (Some(l), None) => l,
+        _ => c,
+    }
+}
+
 // Implement VTE Perform trait for terminal emulation
 impl Perform for TerminalBuffer {
     fn print(&mut self, c: char) {
@@ -646,7 +1514,7 @@ impl Perform for TerminalBuffer {
                 // HT - Horizontal Tab
                 self.tab();
             }
-            0x0A | 0x0B | 0x0C => {
+            0x0A..=0x0C => {
                 // LF, VT, FF - Line Feed
                 self.linefeed();
             }
@@ -668,73 +1536,111 @@ impl Perform for TerminalBuffer {
         // OSC sequences we care about:
         // OSC 0 ; title BEL - Set icon name and window title
         // OSC 2 ; title BEL - Set window title
+        // OSC 133 ; A|B|C|D [; ...] BEL - Shell integration prompt/command marks
+        // OSC 8 ; params ; URI BEL - Hyperlink the following text (empty URI ends it)
         if let Some(&code) = params.first() {
+            if code == b"8" {
+                // The URI can itself contain semicolons
+                let uri = params.get(2..).unwrap_or_default().join(&b';');
+                self.set_link(&uri);
+                return;
+            }
             if code == b"0" || code == b"2" {
                 if let Some(_title) = params.get(1) {
                     // TODO: Emit event for title change
                 }
+            } else if code == b"133" {
+                let kind = match params.get(1).and_then(|p| p.first()) {
+                    Some(b'A') => Some(PromptMarkKind::PromptStart),
+                    Some(b'B') => Some(PromptMarkKind::CommandStart),
+                    Some(b'C') => Some(PromptMarkKind::OutputStart),
+                    Some(b'D') => Some(PromptMarkKind::CommandEnd),
+                    _ => None,
+                };
+                if let Some(kind) = kind {
+                    self.add_prompt_mark(kind);
+                }
             }
         }
     }
 
     fn csi_dispatch(&mut self, params: &Params, intermediates: &[u8], _ignore: bool, action: char) {
-        let params: Vec<u16> = params.iter().flat_map(|p| p.first().copied()).collect();
-
         match action {
+            // Column operations
+            '@' if intermediates == b" " => {
+                // SL - Scroll Left
+                let n = param(params, 0, 1);
+                self.scroll_columns(self.scroll_left, n, true);
+            }
+            'A' if intermediates == b" " => {
+                // SR - Scroll Right
+                let n = param(params, 0, 1);
+                self.scroll_columns(self.scroll_left, n, false);
+            }
+            '}' if intermediates == b"'" => {
+                // DECIC - Insert Columns
+                let n = param(params, 0, 1);
+                self.scroll_columns(self.cursor_col, n, false);
+            }
+            '~' if intermediates == b"'" => {
+                // DECDC - Delete Columns
+                let n = param(params, 0, 1);
+                self.scroll_columns(self.cursor_col, n, true);
+            }
+
             // Cursor movement
             'A' => {
                 // CUU - Cursor Up
-                let n = params.first().copied().unwrap_or(1).max(1);
+                let n = param(params, 0, 1);
                 self.move_cursor_up(n);
             }
             'B' | 'e' => {
                 // CUD - Cursor Down, VPR - Vertical Position Relative
-                let n = params.first().copied().unwrap_or(1).max(1);
+                let n = param(params, 0, 1);
                 self.move_cursor_down(n);
             }
             'C' | 'a' => {
                 // CUF - Cursor Forward, HPR - Horizontal Position Relative
-                let n = params.first().copied().unwrap_or(1).max(1);
+                let n = param(params, 0, 1);
                 self.cursor_col = (self.cursor_col + n).min(self.width.saturating_sub(1));
             }
             'D' => {
                 // CUB - Cursor Back
-                let n = params.first().copied().unwrap_or(1).max(1);
+                let n = param(params, 0, 1);
                 self.cursor_col = self.cursor_col.saturating_sub(n);
             }
             'E' => {
                 // CNL - Cursor Next Line
-                let n = params.first().copied().unwrap_or(1).max(1);
+                let n = param(params, 0, 1);
                 self.move_cursor_down(n);
                 self.carriage_return();
             }
             'F' => {
                 // CPL - Cursor Previous Line
-                let n = params.first().copied().unwrap_or(1).max(1);
+                let n = param(params, 0, 1);
                 self.move_cursor_up(n);
                 self.carriage_return();
             }
             'G' | '`' => {
                 // CHA - Cursor Horizontal Absolute, HPA
-                let col = params.first().copied().unwrap_or(1).max(1);
-                self.cursor_col = (col - 1).min(self.width.saturating_sub(1));
+                self.set_cursor_col(param(params, 0, 1));
             }
             'H' | 'f' => {
                 // CUP - Cursor Position, HVP
-                let row = params.first().copied().unwrap_or(1);
-                let col = params.get(1).copied().unwrap_or(1);
+                let row = param(params, 0, 1);
+                let col = param(params, 1, 1);
                 self.set_cursor_position(row, col);
             }
             'd' => {
                 // VPA - Vertical Position Absolute
-                let row = params.first().copied().unwrap_or(1);
-                self.set_cursor_position(row, self.cursor_col + 1);
+                let row = param(params, 0, 1);
+                self.set_cursor_row(row);
             }
 
             // Erasing
             'J' => {
                 // ED - Erase Display
-                match params.first().copied().unwrap_or(0) {
+                match param(params, 0, 0) {
                     0 => self.clear_to_end_of_screen(),
                     1 => self.clear_to_start_of_screen(),
                     2 | 3 => self.clear(),
@@ -743,7 +1649,7 @@ impl Perform for TerminalBuffer {
             }
             'K' => {
                 // EL - Erase Line
-                match params.first().copied().unwrap_or(0) {
+                match param(params, 0, 0) {
                     0 => self.clear_to_end_of_line(),
                     1 => self.clear_to_start_of_line(),
                     2 => self.clear_line(),
@@ -754,88 +1660,120 @@ impl Perform for TerminalBuffer {
             // Line operations
             'L' => {
                 // IL - Insert Lines
-                let n = params.first().copied().unwrap_or(1).max(1);
+                let n = param(params, 0, 1);
                 self.insert_lines(n);
             }
             'M' => {
                 // DL - Delete Lines
-                let n = params.first().copied().unwrap_or(1).max(1);
+                let n = param(params, 0, 1);
                 self.delete_lines(n);
             }
 
             // Character operations
             'P' => {
                 // DCH - Delete Characters
-                let n = params.first().copied().unwrap_or(1).max(1);
+                let n = param(params, 0, 1);
                 self.delete_chars(n);
             }
             '@' => {
                 // ICH - Insert Characters
-                let n = params.first().copied().unwrap_or(1).max(1);
+                let n = param(params, 0, 1);
                 self.insert_chars(n);
             }
             'X' => {
                 // ECH - Erase Characters
-                let n = params.first().copied().unwrap_or(1).max(1);
+                let n = param(params, 0, 1);
                 self.erase_chars(n);
             }
 
             // Scrolling
             'S' => {
                 // SU - Scroll Up
-                let n = params.first().copied().unwrap_or(1).max(1);
+                let n = param(params, 0, 1);
                 self.scroll_up(n);
             }
             'T' => {
                 // SD - Scroll Down
-                let n = params.first().copied().unwrap_or(1).max(1);
+                let n = param(params, 0, 1);
                 self.scroll_down(n);
             }
             'r' => {
                 // DECSTBM - Set Scrolling Region
-                let top = params.first().copied().unwrap_or(1);
-                let bottom = params.get(1).copied().unwrap_or(self.height);
+                let top = param(params, 0, 1);
+                let bottom = param(params, 1, self.height);
                 self.set_scroll_region(top, bottom);
             }
 
             // SGR - Select Graphic Rendition
             'm' => {
-                self.handle_sgr(&params);
+                self.handle_sgr(params);
             }
 
             // Mode setting
-            'h' => {
-                // SM - Set Mode
-                if intermediates.first() == Some(&b'?') {
-                    // DEC Private Mode Set
-                    for param in &params {
-                        match param {
-                            25 => self.cursor_visible = true,   // DECTCEM - Show Cursor
-                            6 => self.origin_mode = true,       // DECOM
-                            _ => {}
+            'h' if intermediates.first() == Some(&b'?') => {
+                // DECSET - DEC Private Mode Set
+                for mode in params.iter().filter_map(|p| p.first()) {
+                    match mode {
+                        1 => self.application_cursor_keys = true, // DECCKM
+                        25 => self.cursor_visible = true,   // DECTCEM - Show Cursor
+                        6 => {
+                            // DECOM - Origin Mode, which also homes the cursor
+                            self.origin_mode = true;
+                            self.set_cursor_position(1, 1);
                         }
+                        7 => self.autowrap = true, // DECAWM - Autowrap
+                        69 => self.lr_margin_mode = true, // DECLRMM - Left/Right Margins
+                        1004 => self.focus_reporting = true, // Focus in/out reports
+                        9 | 1000 | 1002 | 1003 => self.mouse_mode = Some(*mode), // Mouse tracking
+                        2004 => self.bracketed_paste = true, // Bracketed paste
+                        _ => {}
                     }
                 }
             }
-            'l' => {
-                // RM - Reset Mode
-                if intermediates.first() == Some(&b'?') {
-                    // DEC Private Mode Reset
-                    for param in &params {
-                        match param {
-                            25 => self.cursor_visible = false,  // DECTCEM - Hide Cursor
-                            6 => self.origin_mode = false,      // DECOM
-                            _ => {}
+            'l' if intermediates.first() == Some(&b'?') => {
+                // DECRST - DEC Private Mode Reset
+                for mode in params.iter().filter_map(|p| p.first()) {
+                    match mode {
+                        1 => self.application_cursor_keys = false,
+                        25 => self.cursor_visible = false,  // DECTCEM - Hide Cursor
+                        6 => {
+                            self.origin_mode = false;
+                            self.set_cursor_position(1, 1);
+                        }
+                        7 => self.autowrap = false,
+                        69 => {
+                            self.lr_margin_mode = false;
+                            self.reset_horizontal_margins();
                         }
+                        1004 => self.focus_reporting = false,
+                        // Turning off the mode in effect stops tracking
+                        9 | 1000 | 1002 | 1003 if self.mouse_mode == Some(*mode) => self.mouse_mode = None,
+                        2004 => self.bracketed_paste = false,
+                        _ => {}
                     }
                 }
             }
+            'h' | 'l' if intermediates.is_empty() => {
+                // SM - Set Mode, RM - Reset Mode
+                self.set_ansi_modes(params, action == 'h');
+            }
+
+            's' if self.lr_margin_mode && intermediates.is_empty() => {
+                // DECSLRM - Set Left and Right Margins
+                let left = param(params, 0, 1);
+                let right = param(params, 1, self.width);
+                self.set_horizontal_margins(left, right);
+            }
 
             // Cursor save/restore
             's' => {
                 // SCP - Save Cursor Position
                 self.saved_cursor = Some((self.cursor_row, self.cursor_col));
             }
+            'u' if matches!(intermediates.first(), Some(b'>' | b'<' | b'=')) => {
+                self.kitty_keyboard_dispatch(intermediates[0], params);
+            }
+            'u' if !intermediates.is_empty() => {}
             'u' => {
                 // RCP - Restore Cursor Position
                 if let Some((row, col)) = self.saved_cursor {
@@ -882,7 +1820,15 @@ impl Perform for TerminalBuffer {
                 // RIS - Reset to Initial State
                 self.clear();
                 self.reset_attributes();
+                self.focus_reporting = false;
+                self.kitty_keyboard.clear();
+                self.current_link = None;
+                self.reclaim_links();
             }
+            ([b'#'], b'3') => self.set_line_size(LineSize::DoubleHeightTop), // DECDHL top half
+            ([b'#'], b'4') => self.set_line_size(LineSize::DoubleHeightBottom), // DECDHL bottom half
+            ([b'#'], b'5') => self.set_line_size(LineSize::Single), // DECSWL
+            ([b'#'], b'6') => self.set_line_size(LineSize::DoubleWidth), // DECDWL
             _ => {}
         }
     }
@@ -1002,10 +1948,843 @@ mod tests {
         assert_eq!(buf.get_cell(0, 0).unwrap().character, 'R');
     }
 
+    /// Build a buffer of the given height with `lines` lines pushed into scrollback
+    fn buffer_with_history(height: u16, lines: usize) -> TerminalBuffer {
+        let mut buf = TerminalBuffer::new(10, height);
+        for i in 0..lines + height as usize {
+            buf.process(format!("{}\r\n", i % 10).as_bytes());
+        }
+        buf
+    }
+
+    #[test]
+    fn test_scroll_view_half_page() {
+        let mut buf = buffer_with_history(10, 30);
+        assert!(buf.scrollback_len() >= 30);
+
+        // Half of a 10-row page
+        let half = 5;
+        buf.scroll_view_up(half);
+        assert_eq!(buf.scroll_offset(), 5);
+        buf.scroll_view_up(half);
+        assert_eq!(buf.scroll_offset(), 10);
+        buf.scroll_view_down(half);
+        assert_eq!(buf.scroll_offset(), 5);
+        buf.scroll_view_down(half);
+        buf.scroll_view_down(half);
+        assert_eq!(buf.scroll_offset(), 0);
+    }
+
+    #[test]
+    fn test_scroll_view_clamps_to_history() {
+        let mut buf = buffer_with_history(10, 3);
+        let history = buf.scrollback_len();
+        buf.scroll_view_up(50);
+        assert_eq!(buf.scroll_offset(), history);
+    }
+
+    #[test]
+    fn test_clear_scrollback() {
+        let mut buf = buffer_with_history(10, 20);
+        let screen = buf.to_text();
+        buf.scroll_view_up(5);
+        buf.clear_scrollback();
+        assert_eq!(buf.scrollback_len(), 0);
+        assert!(!buf.is_scrolled());
+        // The live screen is untouched
+        assert_eq!(buf.to_text(), screen);
+    }
+
+    #[test]
+    fn test_display_row_with_offset() {
+        let mut buf = TerminalBuffer::new(10, 2);
+        buf.process(b"a\r\nb\r\nc");
+        // "a" scrolled off; screen shows "b", "c"
+        assert_eq!(buf.display_row(0).unwrap()[0].character, 'b');
+        buf.scroll_view_up(1);
+        assert_eq!(buf.display_row(0).unwrap()[0].character, 'a');
+        assert_eq!(buf.display_row(1).unwrap()[0].character, 'b');
+    }
+
+    #[test]
+    fn test_decdwl_marks_line() {
+        let mut buf = TerminalBuffer::new(20, 4);
+        buf.process(b"\x1b#6Wide\r\n\x1b#3Tall\r\n\x1b#4Tall\r\nNormal");
+        assert_eq!(buf.line_size(0), LineSize::DoubleWidth);
+        assert_eq!(buf.line_size(1), LineSize::DoubleHeightTop);
+        assert_eq!(buf.line_size(2), LineSize::DoubleHeightBottom);
+        assert_eq!(buf.line_size(3), LineSize::Single);
+
+        // DECSWL resets the line
+        buf.process(b"\x1b[1;1H\x1b#5");
+        assert_eq!(buf.line_size(0), LineSize::Single);
+    }
+
+    #[test]
+    fn test_line_size_follows_scrolling() {
+        let mut buf = TerminalBuffer::new(20, 2);
+        buf.process(b"\x1b[2;1H\x1b#6Wide\r\n");
+        // The wide line moved up and the new bottom line is single size
+        assert_eq!(buf.line_size(0), LineSize::DoubleWidth);
+        assert_eq!(buf.line_size(1), LineSize::Single);
+
+        buf.process(b"\x1b[2J");
+        assert_eq!(buf.line_size(0), LineSize::Single);
+    }
+
+    #[test]
+    fn test_to_text() {
+        let mut buf = TerminalBuffer::new(10, 4);
+        buf.process(b"one  \r\n\r\n  three");
+        assert_eq!(buf.to_text(), "one\n\n  three");
+    }
+
+    #[test]
+    fn test_snapshot_reflects_sgr() {
+        let mut buf = TerminalBuffer::new(6, 2);
+        buf.process(b"\x1b[1;31mab\x1b[0m\x1b[44mc");
+        let snapshot = buf.snapshot();
+        assert_eq!(snapshot.len(), 2);
+        assert_eq!(snapshot[0][0].character, 'a');
+        assert_eq!(snapshot[0][0].fg, Color::Red);
+        assert!(snapshot[0][1].attrs.bold);
+        assert_eq!(snapshot[0][2].bg, Color::Blue);
+        assert!(!snapshot[0][2].attrs.bold);
+
+        // Later output doesn't reach a snapshot already taken
+        buf.process(b"\x1b[2J");
+        assert_eq!(snapshot[0][0].character, 'a');
+    }
+
+    #[test]
+    fn test_styled_rows_coalesce_runs() {
+        let mut buf = TerminalBuffer::new(6, 2);
+        buf.process(b"\x1b[1;31mab\x1b[0m\x1b[44mc\x1b[0m\r\nplain");
+        let rows: Vec<Vec<StyledRun>> = buf.styled_rows().collect();
+        let run = |text: &str, fg, bg, bold| StyledRun {
+            text: text.to_string(),
+            fg,
+            bg,
+            attrs: CellAttributes { bold, ..CellAttributes::default() },
+        };
+        assert_eq!(
+            rows[0],
+            vec![
+                run("ab", Color::Red, Color::Reset, true),
+                run("c", Color::Reset, Color::Blue, false),
+                run("   ", Color::Reset, Color::Reset, false),
+            ]
+        );
+        assert_eq!(rows[1].len(), 1);
+        assert_eq!(rows[1][0].text, "plain ");
+    }
+
+    #[test]
+    fn test_to_text_wide_chars() {
+        // Each character takes one cell, so nothing is padded between wide ones
+        let mut buf = TerminalBuffer::new(10, 2);
+        buf.process("日本語 ok  ".as_bytes());
+        assert_eq!(buf.to_text(), "日本語 ok");
+    }
+
+    #[test]
+    fn test_to_text_with_scrollback() {
+        let mut buf = TerminalBuffer::new(10, 2);
+        buf.process(b"first   \r\nsecond\r\nthird\r\n");
+        assert_eq!(buf.scrollback_len(), 2);
+        assert_eq!(buf.to_text(), "third");
+        assert_eq!(buf.to_text_with_scrollback(), "first\nsecond\nthird");
+
+        // Wrapped rows stay separate lines, as they're shown
+        let mut buf = TerminalBuffer::new(4, 3);
+        buf.process(b"abcdef");
+        assert_eq!(buf.to_text_with_scrollback(), "abcd\nef");
+    }
+
     #[test]
     fn test_process_bold() {
         let mut buf = TerminalBuffer::new(80, 24);
         buf.process(b"\x1b[1mBold\x1b[0m");
         assert!(buf.get_cell(0, 0).unwrap().attrs.bold);
     }
+
+    #[test]
+    fn test_contains_text_searches_scrollback() {
+        let mut term = TerminalBuffer::new(20, 2);
+        term.process(b"Build FAILED\r\nline two\r\nline three");
+        assert_eq!(term.scrollback_len(), 1);
+        assert!(term.contains_text("build failed"));
+        assert!(term.contains_text("THREE"));
+        assert!(!term.contains_text("missing"));
+        assert!(!term.contains_text(""));
+    }
+
+    #[test]
+    fn test_search_finds_matches_in_scrollback() {
+        let mut term = TerminalBuffer::new(20, 3);
+        term.process(b"error: one\r\nok\r\nERROR two\r\nok\r\nfine\r\nlast error");
+        assert_eq!(term.scrollback_len(), 3);
+        // History rows: 0 "error: one", 1 "ok", 2 "ERROR two", 3 "ok", 4 "fine", 5 "last error"
+        assert_eq!(term.search("Error"), vec![(0, 0), (2, 0), (5, 5)]);
+        assert!(term.search("missing").is_empty());
+        assert!(term.search("").is_empty());
+    }
+
+    #[test]
+    fn test_search_spans_wrapped_lines() {
+        let mut term = TerminalBuffer::new(5, 3);
+        // "abcdefgh" wraps after "abcde"; the next line is separate
+        term.process(b"abcdefgh\r\nfg");
+        assert_eq!(term.search("def"), vec![(0, 3)]);
+        // A hard line break isn't bridged
+        assert!(term.search("ghf").is_empty());
+    }
+
+    #[test]
+    fn test_scroll_to_history_row() {
+        let mut buf = buffer_with_history(10, 30);
+        let history = buf.scrollback_len();
+        buf.scroll_to_history_row(10);
+        assert_eq!(buf.history_row(5), 10);
+        // Rows on the live screen need no scrolling
+        buf.scroll_to_history_row(history + 9);
+        assert_eq!(buf.scroll_offset(), 0);
+        buf.scroll_to_history_row(0);
+        assert_eq!(buf.scroll_offset(), history);
+    }
+
+    #[test]
+    fn test_last_command_output_from_marks() {
+        let mut term = TerminalBuffer::new(20, 10);
+        term.process(b"$ ls\r\na.txt\r\nb.txt\r\n$ echo hi\r\nhi\r\n$ ");
+        let mark = |kind, row| PromptMark { kind, row };
+        term.prompt_marks = vec![
+            mark(PromptMarkKind::PromptStart, 0),
+            mark(PromptMarkKind::CommandStart, 0),
+            mark(PromptMarkKind::OutputStart, 1),
+            mark(PromptMarkKind::CommandEnd, 3),
+            mark(PromptMarkKind::PromptStart, 3),
+            mark(PromptMarkKind::CommandStart, 3),
+            mark(PromptMarkKind::OutputStart, 4),
+            mark(PromptMarkKind::CommandEnd, 5),
+            mark(PromptMarkKind::PromptStart, 5),
+        ];
+
+        assert_eq!(term.last_output_rows(), Some(4..5));
+        assert_eq!(term.history_text(term.last_output_rows().unwrap()), "hi");
+        assert_eq!(term.history_text(1..3), "a.txt\nb.txt");
+    }
+
+    #[test]
+    fn test_unfinished_command_has_no_output() {
+        let mut term = TerminalBuffer::new(20, 5);
+        term.process(b"$ ls\r\na.txt\r\n$ ");
+        assert_eq!(term.last_output_rows(), None);
+
+        // Output that never got a D mark doesn't count
+        let mark = |kind, row| PromptMark { kind, row };
+        term.prompt_marks = vec![
+            mark(PromptMarkKind::OutputStart, 1),
+            mark(PromptMarkKind::PromptStart, 2),
+        ];
+        assert_eq!(term.last_output_rows(), None);
+    }
+
+    #[test]
+    fn test_osc133_marks_record_rows() {
+        let mut term = TerminalBuffer::new(20, 10);
+        term.process(b"\x1b]133;A\x07$ \x1b]133;B\x07make\r\n\x1b]133;C\x07");
+        term.process(b"one\r\ntwo\r\n\x1b]133;D;1\x07\x1b]133;A\x07$ ");
+
+        let marks: Vec<(PromptMarkKind, usize)> =
+            term.prompt_marks().iter().map(|m| (m.kind, m.row)).collect();
+        assert_eq!(
+            marks,
+            vec![
+                (PromptMarkKind::PromptStart, 0),
+                (PromptMarkKind::CommandStart, 0),
+                (PromptMarkKind::OutputStart, 1),
+                (PromptMarkKind::CommandEnd, 3),
+                (PromptMarkKind::PromptStart, 3),
+            ]
+        );
+        assert_eq!(term.prompt_rows(), vec![0, 3]);
+        assert_eq!(term.last_output_rows(), Some(1..3));
+        // Unknown OSC 133 kinds are ignored
+        term.process(b"\x1b]133;P;k=i\x07");
+        assert_eq!(term.prompt_marks().len(), 5);
+    }
+
+    #[test]
+    fn test_osc133_marks_follow_scrollback() {
+        let mut term = TerminalBuffer::new(10, 3);
+        term.scrollback_limit = 2;
+        term.process(b"\x1b]133;A\x07a\r\nb\r\n\x1b]133;A\x07c");
+        assert_eq!(term.prompt_rows(), vec![0, 2]);
+
+        // Scrolling into history keeps rows; evicting history shifts them
+        term.process(b"\r\nd\r\ne");
+        assert_eq!(term.scrollback_len(), 2);
+        assert_eq!(term.prompt_rows(), vec![0, 2]);
+        term.process(b"\r\nf");
+        assert_eq!(term.history_text(1..2), "c");
+        assert_eq!(term.prompt_rows(), vec![1]);
+        assert_eq!(term.evicted_lines(), 1);
+
+        // Clearing history renumbers what's left; clearing the screen drops it
+        term.clear_scrollback();
+        assert!(term.prompt_rows().is_empty());
+        term.process(b"\x1b]133;A\x07");
+        assert_eq!(term.prompt_rows(), vec![2]);
+        term.process(b"\x1b[2J");
+        assert!(term.prompt_rows().is_empty());
+    }
+
+    #[test]
+    fn test_region_scroll_drops_marks_in_region() {
+        let mut term = TerminalBuffer::new(10, 5);
+        term.process(b"\x1b]133;A\x07top\r\n\x1b]133;A\x07mid");
+        // Scroll region rows 2-5 (1-based); scrolling it takes the mark on row 1 (0-based)
+        term.process(b"\x1b[2;5r\x1b[5;1H\n");
+        assert_eq!(term.prompt_rows(), vec![0]);
+    }
+
+    #[test]
+    fn test_prev_next_prompt() {
+        let mut term = TerminalBuffer::new(10, 3);
+        // Prompts on rows 0, 3 and 6, with two output lines after each
+        for _ in 0..3 {
+            term.process(b"\x1b]133;A\x07$ ls\r\nout\r\nout\r\n");
+        }
+        term.process(b"\x1b]133;A\x07$ ");
+        assert_eq!(term.prompt_rows(), vec![0, 3, 6, 9]);
+        assert_eq!(term.scrollback_len(), 7);
+
+        // Nothing below the live screen
+        assert_eq!(term.next_prompt(term.history_row(0)), None);
+
+        // Going back puts each prompt at the top of the view
+        assert_eq!(term.prev_prompt(term.history_row(0)), Some(6));
+        assert_eq!(term.history_row(0), 6);
+        assert_eq!(term.prev_prompt(term.history_row(0)), Some(3));
+        assert_eq!(term.prev_prompt(term.history_row(0)), Some(0));
+        assert_eq!(term.scroll_offset(), 7);
+        assert_eq!(term.prev_prompt(term.history_row(0)), None);
+
+        // And forward again, until the prompt on the live screen
+        assert_eq!(term.next_prompt(term.history_row(0)), Some(3));
+        assert_eq!(term.history_row(0), 3);
+        assert_eq!(term.next_prompt(term.history_row(0)), Some(6));
+        assert_eq!(term.next_prompt(term.history_row(0)), Some(9));
+        assert!(!term.is_scrolled());
+    }
+
+    #[test]
+    fn test_palette_overrides_base_colors() {
+        let mut palette = [Color::Reset; 16];
+        palette[1] = Color::Rgb(0xdc, 0x32, 0x2f);
+        palette[9] = Color::Rgb(0xcb, 0x4b, 0x16);
+        let mut term = TerminalBuffer::new(10, 2);
+        term.set_palette(Some(palette));
+        term.process(b"\x1b[31ma\x1b[91;48;5;1mb\x1b[38;5;196mc");
+
+        assert_eq!(term.get_cell(0, 0).unwrap().fg, Color::Rgb(0xdc, 0x32, 0x2f));
+        assert_eq!(term.get_cell(0, 1).unwrap().fg, Color::Rgb(0xcb, 0x4b, 0x16));
+        assert_eq!(term.get_cell(0, 1).unwrap().bg, Color::Rgb(0xdc, 0x32, 0x2f));
+        // Indices past the base 16 aren't affected
+        assert_eq!(term.get_cell(0, 2).unwrap().fg, Color::Rgb(255, 0, 0));
+
+        let mut term = TerminalBuffer::new(10, 2);
+        term.process(b"\x1b[31ma");
+        assert_eq!(term.get_cell(0, 0).unwrap().fg, Color::Red);
+    }
+
+    #[test]
+    fn test_256_color_cube_matches_xterm() {
+        assert_eq!(ansi_to_color(16), Color::Rgb(0, 0, 0));
+        assert_eq!(ansi_to_color(21), Color::Rgb(0, 0, 255));
+        assert_eq!(ansi_to_color(196), Color::Rgb(255, 0, 0));
+        assert_eq!(ansi_to_color(67), Color::Rgb(95, 135, 175));
+        assert_eq!(ansi_to_color(231), Color::Rgb(255, 255, 255));
+    }
+
+    #[test]
+    fn test_grayscale_ramp_matches_xterm() {
+        assert_eq!(ansi_to_color(232), Color::Rgb(8, 8, 8));
+        assert_eq!(ansi_to_color(244), Color::Rgb(128, 128, 128));
+        assert_eq!(ansi_to_color(255), Color::Rgb(238, 238, 238));
+    }
+
+    #[test]
+    fn test_focus_reporting_mode() {
+        let mut term = TerminalBuffer::new(10, 2);
+        assert!(!term.focus_reporting());
+        term.process(b"\x1b[?1004h");
+        assert!(term.focus_reporting());
+        term.process(b"\x1b[?1004l");
+        assert!(!term.focus_reporting());
+        term.process(b"\x1b[?1004h\x1bc");
+        assert!(!term.focus_reporting());
+    }
+
+    #[test]
+    fn test_kitty_keyboard_flags() {
+        let mut term = TerminalBuffer::new(10, 2);
+        assert_eq!(term.kitty_keyboard_flags(), 0);
+        term.process(b"\x1b[>1u");
+        assert_eq!(term.kitty_keyboard_flags(), 1);
+        term.process(b"\x1b[>3u");
+        assert_eq!(term.kitty_keyboard_flags(), 3);
+        term.process(b"\x1b[=8;2u");
+        assert_eq!(term.kitty_keyboard_flags(), 11);
+        term.process(b"\x1b[<u");
+        assert_eq!(term.kitty_keyboard_flags(), 1);
+        term.process(b"\x1b[<5u");
+        assert_eq!(term.kitty_keyboard_flags(), 0);
+
+        // A query changes nothing, and a plain CSI u still restores the cursor
+        term.process(b"\x1b[?u\x1b[1;3H\x1b[s\x1b[2;1H\x1b[u");
+        assert_eq!(term.kitty_keyboard_flags(), 0);
+        assert_eq!(term.cursor(), (0, 2));
+    }
+
+    #[test]
+    fn test_sequence_split_across_chunks() {
+        let mut buf = TerminalBuffer::new(10, 2);
+        buf.process(b"\x1b[3");
+        buf.process(b"1mX");
+        let cell = buf.get_cell(0, 0).unwrap();
+        assert_eq!(cell.character, 'X');
+        assert_eq!(cell.fg, Color::Red);
+        assert_eq!(buf.to_text(), "X");
+
+        // Split right after ESC, and inside a UTF-8 character
+        buf.process(b"\x1b");
+        buf.process(b"[2;1H\xc3");
+        buf.process(b"\xa9");
+        assert_eq!(buf.get_cell(1, 0).unwrap().character, '\u{e9}');
+    }
+
+    #[test]
+    fn test_tab_near_right_margin() {
+        // From column 6 a tab stops at the margin rather than the stop at 8
+        let mut buf = TerminalBuffer::new(8, 2);
+        buf.process(b"abcdef\t");
+        assert_eq!(buf.cursor(), (0, 7));
+        buf.process(b"X");
+        assert_eq!(buf.to_text(), "abcdef X");
+
+        // At the last column a tab stays put, and the next character goes there
+        let mut buf = TerminalBuffer::new(8, 2);
+        buf.process(b"abcdefg\t");
+        assert_eq!(buf.cursor(), (0, 7));
+        buf.process(b"\tX");
+        assert_eq!(buf.to_text(), "abcdefgX");
+        assert_eq!(buf.cursor(), (0, 8));
+
+        // With a wrap pending a tab doesn't pull the cursor back onto the row
+        buf.process(b"\tY");
+        assert_eq!(buf.to_text(), "abcdefgX\nY");
+    }
+
+    #[test]
+    fn test_vpa_keeps_column() {
+        let mut buf = TerminalBuffer::new(20, 24);
+        buf.process(b"\x1b[3;6H\x1b[10d");
+        assert_eq!(buf.cursor(), (9, 5));
+        buf.process(b"\x1b[1;1H\x1b[10d");
+        assert_eq!(buf.cursor(), (9, 0));
+        // Rows past the bottom clamp to it
+        buf.process(b"\x1b[99d");
+        assert_eq!(buf.cursor(), (23, 0));
+    }
+
+    #[test]
+    fn test_origin_mode_cursor_movement() {
+        let mut buf = TerminalBuffer::new(10, 12);
+        // Scroll region rows 3-8, then origin mode on, which homes the cursor
+        buf.process(b"\x1b[3;8r\x1b[?6h");
+        assert_eq!(buf.cursor(), (2, 0));
+
+        // CUP and VPA count from the top of the region and stay inside it
+        buf.process(b"\x1b[4;5H");
+        assert_eq!(buf.cursor(), (5, 4));
+        buf.process(b"\x1b[99;1H");
+        assert_eq!(buf.cursor(), (7, 0));
+        buf.process(b"\x1b[2d");
+        assert_eq!(buf.cursor(), (3, 0));
+
+        // CUU and CUD stop at the margins without scrolling
+        buf.process(b"X\x1b[99A");
+        assert_eq!(buf.cursor(), (2, 1));
+        buf.process(b"\x1b[99B");
+        assert_eq!(buf.cursor(), (7, 1));
+        assert_eq!(buf.get_cell(3, 0).unwrap().character, 'X');
+        buf.process(b"\x1b[99F");
+        assert_eq!(buf.cursor(), (2, 0));
+
+        // With origin mode off, positions are absolute again
+        buf.process(b"\x1b[?6l");
+        assert_eq!(buf.cursor(), (0, 0));
+        buf.process(b"\x1b[99B");
+        assert_eq!(buf.cursor(), (7, 0));
+        buf.process(b"\x1b[10;1H\x1b[99B");
+        assert_eq!(buf.cursor(), (11, 0));
+    }
+
+    #[test]
+    fn test_csi_s_saves_cursor_without_margin_mode() {
+        let mut buf = TerminalBuffer::new(8, 4);
+        buf.process(b"abcdefgh\x1b[3;4H\x1b[2;5s\x1b[1;1H\x1b[u");
+        assert_eq!(buf.cursor(), (2, 3));
+        // No margins were set, so DCH shifts the whole row
+        buf.process(b"\x1b[1;2H\x1b[P");
+        assert_eq!(buf.to_text(), "acdefgh");
+    }
+
+    #[test]
+    fn test_left_right_margins() {
+        let mut buf = TerminalBuffer::new(8, 4);
+        buf.process(b"abcdefgh\r\nabcdefgh");
+        // Margins at columns 2-5 home the cursor
+        buf.process(b"\x1b[?69h\x1b[2;5s");
+        assert_eq!(buf.cursor(), (0, 0));
+
+        // DCH, ICH and ECH stay within the margins
+        buf.process(b"\x1b[1;2H\x1b[P");
+        assert_eq!(buf.to_text(), "acde fgh\nabcdefgh");
+        buf.process(b"\x1b[2;2H\x1b[@");
+        assert_eq!(buf.to_text(), "acde fgh\na bcdfgh");
+        buf.process(b"\x1b[2;4H\x1b[9X");
+        assert_eq!(buf.to_text(), "acde fgh\na b  fgh");
+
+        // Scroll left moves every row of the region within the margins (the rows
+        // are written without margins, which would wrap them)
+        buf.process(b"\x1b[?69l\x1b[2J\x1b[1;1Habcdefgh\r\nabcdefgh\x1b[?69h\x1b[2;5s\x1b[ @");
+        assert_eq!(buf.to_text(), "acde fgh\nacde fgh");
+        // Delete columns from the cursor
+        buf.process(b"\x1b[1;3H\x1b[2'~");
+        assert_eq!(buf.to_text(), "ac   fgh\nac   fgh");
+
+        // Outside the margins nothing shifts
+        buf.process(b"\x1b[1;7H\x1b[P");
+        assert_eq!(buf.to_text(), "ac   fgh\nac   fgh");
+
+        // Turning the mode off drops the margins and `CSI s` saves again
+        buf.process(b"\x1b[?69l\x1b[1;2H\x1b[P");
+        assert_eq!(buf.to_text(), "a   fgh\nac   fgh");
+        buf.process(b"\x1b[2;3H\x1b[s\x1b[H\x1b[u");
+        assert_eq!(buf.cursor(), (1, 2));
+    }
+
+    #[test]
+    fn test_left_right_margins_bound_printing() {
+        let mut buf = TerminalBuffer::new(8, 4);
+        // In origin mode, home is the top left of the margins and columns count
+        // from the left one
+        buf.process(b"\x1b[?69h\x1b[3;6s\x1b[?6h");
+        assert_eq!(buf.cursor(), (0, 2));
+        buf.process(b"\x1b[2;9H");
+        assert_eq!(buf.cursor(), (1, 5));
+        buf.process(b"\x1b[2G");
+        assert_eq!(buf.cursor(), (1, 3));
+
+        // Printing wraps from the right margin to the left one, where a
+        // carriage return goes too
+        buf.process(b"\x1b[?6l\x1b[1;3Habcdef");
+        assert_eq!(buf.to_text(), "  abcd\n  ef");
+        buf.process(b"\rX");
+        assert_eq!(buf.to_text(), "  abcd\n  Xf");
+
+        // Without autowrap the right margin's column is overwritten
+        buf.process(b"\x1b[?7l\x1b[3;3Hwxyz12\x1b[?7h");
+        assert_eq!(buf.to_text(), "  abcd\n  Xf\n  wxy2");
+
+        // Text printed past the right margin runs to the screen edge, and a
+        // carriage return from left of the margins goes to the first column
+        buf.process(b"\x1b[4;7Hpq");
+        assert_eq!(buf.to_text(), "  abcd\n  Xf\n  wxy2\n      pq");
+        buf.process(b"\x1b[4;2H\r");
+        assert_eq!(buf.cursor(), (3, 0));
+    }
+
+    #[test]
+    fn test_sgr_truecolor_forms() {
+        let mut buf = TerminalBuffer::new(10, 1);
+        buf.process(b"\x1b[38;2;10;20;30ma\x1b[38:2::10:20:30mb\x1b[38:2:10:20:30mc");
+        for col in 0..3 {
+            assert_eq!(buf.get_cell(0, col).unwrap().fg, Color::Rgb(10, 20, 30));
+        }
+
+        buf.process(b"\x1b[48;2;1;2;3;1md\x1b[0;48:2::1:2:3;1me");
+        for col in 3..5 {
+            let cell = buf.get_cell(0, col).unwrap();
+            assert_eq!(cell.bg, Color::Rgb(1, 2, 3));
+            assert!(cell.attrs.bold);
+        }
+    }
+
+    #[test]
+    fn test_sgr_indexed_color_forms() {
+        let mut buf = TerminalBuffer::new(10, 1);
+        buf.process(b"\x1b[38;5;196ma\x1b[38:5:196mb\x1b[48:5:999mc");
+        assert_eq!(buf.get_cell(0, 0).unwrap().fg, Color::Rgb(255, 0, 0));
+        assert_eq!(buf.get_cell(0, 1).unwrap().fg, Color::Rgb(255, 0, 0));
+        // Out-of-range indices clamp to 255
+        assert_eq!(buf.get_cell(0, 2).unwrap().bg, ansi_to_color(255));
+    }
+
+    #[test]
+    fn test_empty_params_use_defaults() {
+        let mut buf = TerminalBuffer::new(20, 10);
+        buf.process(b"\x1b[3;3H\x1b[;5H");
+        assert_eq!(buf.cursor(), (0, 4));
+        buf.process(b"\x1b[3;3H\x1b[5;H");
+        assert_eq!(buf.cursor(), (4, 0));
+        buf.process(b"\x1b[3;3H\x1b[H");
+        assert_eq!(buf.cursor(), (0, 0));
+        buf.process(b"\x1b[3;3H\x1b[0;0H");
+        assert_eq!(buf.cursor(), (0, 0));
+
+        // An empty bottom margin means the last row
+        buf.process(b"\x1b[3;3H\x1b[2;r");
+        assert_eq!((buf.scroll_top, buf.scroll_bottom), (1, 9));
+        assert_eq!(buf.cursor(), (0, 0));
+    }
+
+    #[test]
+    fn test_sgr_sub_params_stay_grouped() {
+        let mut buf = TerminalBuffer::new(10, 1);
+        // Flattened, `4:0` read as plain `4` and `38:5:1` lost its color
+        buf.process(b"\x1b[4:3ma\x1b[4:0;38:5:1mb");
+        let a = buf.get_cell(0, 0).unwrap();
+        assert!(a.attrs.underline);
+        let b = buf.get_cell(0, 1).unwrap();
+        assert!(!b.attrs.underline);
+        assert_eq!(b.fg, Color::Red);
+    }
+
+    #[test]
+    fn test_scroll_region_and_scrollback() {
+        // Full screen: linefeed and SU both feed scrollback
+        let mut buf = TerminalBuffer::new(5, 3);
+        buf.process(b"a\r\nb\r\nc\r\nd");
+        assert_eq!(buf.scrollback_len(), 1);
+        buf.process(b"\x1b[S");
+        assert_eq!(buf.scrollback_len(), 2);
+        assert_eq!(buf.to_text_with_scrollback(), "a\nb\nc\nd");
+
+        // A region at the top (a status line below it) still feeds scrollback,
+        // and the row outside it stays put
+        let mut buf = TerminalBuffer::new(5, 4);
+        buf.process(b"\x1b[4;1Hstat\x1b[1;3r\x1b[1;1Ha\r\nb\r\nc\r\nd");
+        assert_eq!(buf.scrollback_len(), 1);
+        assert_eq!(buf.to_text(), "b\nc\nd\nstat");
+
+        // A region lower down scrolls on its own, without scrollback
+        let mut buf = TerminalBuffer::new(5, 4);
+        buf.process(b"top\x1b[2;4r\x1b[2;1Ha\r\nb\r\nc\r\nd\x1b[T");
+        assert_eq!(buf.scrollback_len(), 0);
+        assert_eq!(buf.to_text(), "top\n\nb\nc");
+
+        // Below the region a linefeed doesn't scroll anything
+        let mut buf = TerminalBuffer::new(5, 4);
+        buf.process(b"a\x1b[1;2r\x1b[4;1Hx\r\ny");
+        assert_eq!(buf.scrollback_len(), 0);
+        assert_eq!(buf.to_text(), "a\n\n\ny");
+    }
+
+    #[test]
+    fn test_scroll_count_capped_at_region_height() {
+        let mut buf = TerminalBuffer::new(5, 3);
+        buf.process(b"a\r\nb\r\nc\x1b[1000S");
+        // Only the lines that were on screen go to scrollback, no blanks
+        assert_eq!(buf.scrollback_len(), 3);
+        assert_eq!(buf.to_text_with_scrollback(), "a\nb\nc");
+        buf.process(b"\x1b[1000T");
+        assert_eq!(buf.scrollback_len(), 3);
+    }
+
+    #[test]
+    fn test_autowrap_off_overwrites_last_column() {
+        let mut buf = TerminalBuffer::new(5, 2);
+        buf.process(b"\x1b[?7labcdefg");
+        assert_eq!(buf.to_text(), "abcdg");
+        assert_eq!(buf.cursor().0, 0);
+        buf.process(b"\x1b[?7h\r\nabcdefg");
+        assert_eq!(buf.to_text(), "abcde\nfg");
+    }
+
+    #[test]
+    fn test_insert_mode_pushes_line_right() {
+        let mut buf = TerminalBuffer::new(6, 1);
+        buf.process(b"abcd\r\x1b[4hXY\x1b[4lZ");
+        assert_eq!(buf.to_text(), "XYZbcd");
+    }
+
+    #[test]
+    fn test_mode_accessors() {
+        let mut buf = TerminalBuffer::new(10, 10);
+        assert_eq!(buf.scroll_region(), (0, 9));
+        assert!(!buf.origin_mode());
+        assert!(buf.autowrap());
+        assert!(!buf.insert_mode());
+        assert!(!buf.application_cursor_keys());
+        assert!(!buf.bracketed_paste());
+
+        buf.process(b"\x1b[3;8r\x1b[?6;1;2004h\x1b[?7l\x1b[4h");
+        assert_eq!(buf.scroll_region(), (2, 7));
+        assert!(buf.origin_mode());
+        assert!(!buf.autowrap());
+        assert!(buf.insert_mode());
+        assert!(buf.application_cursor_keys());
+        assert!(buf.bracketed_paste());
+
+        buf.process(b"\x1b[r\x1b[?6;1;2004l\x1b[?7h\x1b[4l");
+        assert_eq!(buf.scroll_region(), (0, 9));
+        assert!(!buf.origin_mode());
+        assert!(buf.autowrap());
+        assert!(!buf.insert_mode());
+        assert!(!buf.application_cursor_keys());
+        assert!(!buf.bracketed_paste());
+    }
+
+    #[test]
+    fn test_scrolled_view_holds_still_until_back_at_bottom() {
+        let mut buf = TerminalBuffer::new(10, 3);
+        for i in 0..10 {
+            buf.process(format!("line{}\r\n", i).as_bytes());
+        }
+        assert!(buf.is_following());
+
+        // Scrolled up, new output doesn't move what's shown
+        buf.scroll_view_up(4);
+        assert!(!buf.is_following());
+        let top = |buf: &TerminalBuffer| {
+            buf.display_row(0).unwrap().iter().map(|c| c.character).collect::<String>()
+        };
+        let shown = top(&buf);
+        buf.process(b"more1\r\nmore2\r\n");
+        assert_eq!(top(&buf), shown);
+        assert_eq!(buf.scroll_offset(), 6);
+
+        // Back at the bottom, the view follows output again
+        buf.scroll_view_down(6);
+        assert!(buf.is_following());
+        buf.process(b"more3\r\n");
+        assert_eq!(buf.scroll_offset(), 0);
+        assert_eq!(top(&buf).trim_end(), "more2");
+    }
+
+    #[test]
+    fn test_osc8_link_at() {
+        let mut buf = TerminalBuffer::new(40, 3);
+        buf.process(b"see \x1b]8;id=x;https://example.com/a;b\x1b\\docs\x1b]8;;\x1b\\ here\r\n");
+        buf.process(b"\x1b]8;;https://example.com/a;b\x07again\x1b]8;;\x07");
+        assert_eq!(buf.link_at(0, 3), None);
+        assert_eq!(buf.link_at(0, 4), Some("https://example.com/a;b"));
+        assert_eq!(buf.link_at(0, 7), Some("https://example.com/a;b"));
+        assert_eq!(buf.link_at(0, 8), None);
+        // The same target is stored once
+        assert_eq!(buf.get_cell(1, 0).unwrap().link, buf.get_cell(0, 4).unwrap().link);
+        assert_eq!(buf.link_at(5, 0), None);
+        assert_eq!(buf.link_at(0, 99), None);
+
+        // Lookups follow the view into scrollback
+        buf.process(b"\r\n\r\n\r\n");
+        assert_eq!(buf.link_at(0, 4), None);
+        buf.scroll_view_up(2);
+        assert_eq!(buf.link_at(0, 4), Some("https://example.com/a;b"));
+    }
+
+    #[test]
+    fn test_osc8_links_reclaimed() {
+        let mut buf = TerminalBuffer::new(10, 2);
+        for n in 0..MAX_LINKS {
+            buf.process(format!("\x1b]8;;https://example.com/{}\x07x\x1b]8;;\x07\r\n", n).as_bytes());
+        }
+        assert_eq!(buf.links.len(), MAX_LINKS);
+
+        // A full table drops the targets that have scrolled out of history
+        buf.process(b"\x1b]8;;https://example.com/new\x07x\x1b]8;;\x07");
+        assert_eq!(buf.link_at(1, 0), Some("https://example.com/new"));
+        assert_eq!(buf.links.len(), DEFAULT_SCROLLBACK + 2);
+        assert_eq!(buf.link_ids.len(), buf.links.len());
+
+        // So do a reset, which also ends the open link, and clearing history
+        buf.process(b"\x1b]8;;https://example.com/open\x07\x1bcplain");
+        assert_eq!(buf.link_at(0, 0), None);
+        assert_eq!(buf.links.len(), DEFAULT_SCROLLBACK);
+        buf.clear_scrollback();
+        assert!(buf.links.is_empty() && buf.link_ids.is_empty());
+    }
+
+    #[test]
+    fn test_mouse_tracking_mode() {
+        let mut buf = TerminalBuffer::new(10, 2);
+        assert!(!buf.mouse_tracking());
+        buf.process(b"\x1b[?1002;1006h");
+        assert!(buf.mouse_tracking());
+        // Resetting a different mode leaves the one in effect on
+        buf.process(b"\x1b[?1000l");
+        assert!(buf.mouse_tracking());
+        buf.process(b"\x1b[?1002l");
+        assert!(!buf.mouse_tracking());
+    }
+
+    #[test]
+    fn test_url_at() {
+        let mut buf = TerminalBuffer::new(80, 3);
+        buf.process(b"Docs: https://example.com/a_(b)?q=1. Or (http://x.org/path).");
+        let url = Some("https://example.com/a_(b)?q=1".to_string());
+        // Anywhere inside the URL, first to last character
+        assert_eq!(buf.url_at(0, 6), url);
+        assert_eq!(buf.url_at(0, 20), url);
+        assert_eq!(buf.url_at(0, 34), url);
+        // The trailing full stop and text around it aren't part of it
+        assert_eq!(buf.url_at(0, 35), None);
+        assert_eq!(buf.url_at(0, 2), None);
+        assert_eq!(buf.url_at(0, 5), None);
+        // Surrounding brackets are dropped
+        assert_eq!(buf.url_at(0, 45), Some("http://x.org/path".to_string()));
+        assert_eq!(buf.url_at(0, 40), None);
+
+        // A scheme alone isn't a URL, and blank rows have none
+        buf.process(b"\r\nhttps:// x");
+        assert_eq!(buf.url_at(1, 3), None);
+        assert_eq!(buf.url_at(2, 0), None);
+    }
+
+    #[test]
+    fn test_word_bounds() {
+        let mut buf = TerminalBuffer::new(40, 2);
+        buf.process(b"cd ~/src/my-app; echo caf\xc3\xa9");
+        assert_eq!(buf.word_bounds(0, 0, ""), Some((0, 1)));
+        // Path characters only join a word when configured
+        assert_eq!(buf.word_bounds(0, 10, ""), Some((9, 10)));
+        assert_eq!(buf.word_bounds(0, 10, "-./~"), Some((3, 14)));
+        assert_eq!(buf.word_bounds(0, 24, ""), Some((22, 25)));
+        // Spaces and punctuation aren't words
+        assert_eq!(buf.word_bounds(0, 2, "-./~"), None);
+        assert_eq!(buf.word_bounds(0, 15, "-./~"), None);
+        assert_eq!(buf.word_bounds(0, 39, ""), None);
+        assert_eq!(buf.word_bounds(5, 0, ""), None);
+    }
+
+    #[test]
+    fn test_line_bounds() {
+        let mut buf = TerminalBuffer::new(5, 5);
+        buf.process(b"one\r\nabcdefghijk\r\nz");
+        // "abcdefghijk" wraps over rows 1-3
+        assert_eq!(buf.line_bounds(0), 0..1);
+        assert_eq!(buf.line_bounds(1), 1..4);
+        assert_eq!(buf.line_bounds(2), 1..4);
+        assert_eq!(buf.line_bounds(3), 1..4);
+        assert_eq!(buf.history_text(buf.line_bounds(2)), "abcdefghijk");
+
+        // Bounds are history rows, so they stay put as lines scroll off
+        buf.process(b"\r\n\r\n");
+        assert_eq!(buf.line_bounds(0), 1..4);
+        assert_eq!(buf.line_bounds(2), 4..5);
+    }
 }
@@ -1,3 +1,5 @@
 mod buffer;
 
-pub use buffer::{Cell, CellAttributes, TerminalBuffer};
+pub use buffer::{
+    ansi_to_color, Cell, CellAttributes, Palette, PromptMark, PromptMarkKind, StyledRun, TerminalBuffer,
+};
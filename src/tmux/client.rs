@@ -0,0 +1,44 @@
+use std::future::Future;
+
+use super::connection::{Result, TmuxConnection};
+use super::protocol::TmuxEvent;
+use super::version::TmuxVersion;
+
+/// Abstraction over a tmux control mode client
+///
+/// The event loop only needs to send commands and receive events, so it is
+/// written against this trait rather than the concrete `TmuxConnection`.
+/// This lets tests drive the loop with a scripted mock instead of a real tmux.
+pub trait TmuxClient {
+    /// Send a command to tmux and return its command ID
+    fn send_command(&mut self, cmd: &str) -> impl Future<Output = Result<u64>> + Send;
+
+    /// Write any queued commands to tmux
+    fn flush(&mut self) -> impl Future<Output = Result<()>> + Send;
+
+    /// Read the next event from tmux
+    fn next_event(&mut self) -> impl Future<Output = Result<TmuxEvent>> + Send;
+
+    /// Version of the tmux server, if known
+    fn tmux_version(&self) -> Option<TmuxVersion> {
+        None
+    }
+}
+
+impl TmuxClient for TmuxConnection {
+    async fn send_command(&mut self, cmd: &str) -> Result<u64> {
+        TmuxConnection::send_command(self, cmd).await
+    }
+
+    async fn flush(&mut self) -> Result<()> {
+        TmuxConnection::flush(self).await
+    }
+
+    async fn next_event(&mut self) -> Result<TmuxEvent> {
+        TmuxConnection::next_event(self).await
+    }
+
+    fn tmux_version(&self) -> Option<TmuxVersion> {
+        TmuxConnection::tmux_version(self)
+    }
+}
@@ -1,10 +1,57 @@
+use super::target::TmuxTarget;
+use super::version::TmuxVersion;
+
+/// Direction to resize a pane in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+impl Direction {
+    /// resize-pane flag for this direction
+    fn flag(self) -> &'static str {
+        match self {
+            Direction::Left => "-L",
+            Direction::Right => "-R",
+            Direction::Up => "-U",
+            Direction::Down => "-D",
+        }
+    }
+}
+
+/// Which side of its target new-window puts a window
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Placement {
+    After,
+    Before,
+}
+
+impl Placement {
+    /// new-window flag for this placement
+    fn flag(self) -> &'static str {
+        match self {
+            Placement::After => "-a",
+            Placement::Before => "-b",
+        }
+    }
+}
+
 /// Command builders for common tmux operations
 pub struct Commands;
 
 impl Commands {
-    /// List windows with their IDs, names, and active status
+    /// Separator between list-windows fields (ASCII unit separator), since names and
+    /// paths can contain colons
+    pub const FIELD_SEPARATOR: char = '\x1f';
+
+    /// List windows with their IDs, names, active status, activity/bell flags,
+    /// automatic-rename and synchronize-panes options, whether the pane has exited,
+    /// foreground command and current path
     pub fn list_windows() -> String {
-        "list-windows -F '#{window_id}:#{window_name}:#{window_active}:#{pane_id}'".to_string()
+        "list-windows -F '#{window_id}\x1f#{window_name}\x1f#{window_active}\x1f#{pane_id}\x1f#{window_activity_flag}\x1f#{window_bell_flag}\x1f#{automatic-rename}\x1f#{synchronize-panes}\x1f#{pane_dead}\x1f#{pane_current_command}\x1f#{pane_current_path}'".to_string()
     }
 
     /// Create a new window with optional name
@@ -15,47 +62,178 @@ impl Commands {
         }
     }
 
+    /// Create a new window, with optional name, starting in the given directory
+    pub fn new_window_in(name: Option<&str>, cwd: &str) -> String {
+        let mut cmd = format!("new-window -c '{}'", escape_single_quotes(cwd));
+        if let Some(n) = name {
+            cmd.push_str(&format!(" -n '{}'", escape_single_quotes(n)));
+        }
+        cmd
+    }
+
+    /// Create a new window, with optional name, right after the given one
+    pub fn new_window_after(window_id: TmuxTarget, name: Option<&str>) -> String {
+        Self::new_window_placed(Placement::After, window_id, name, None)
+    }
+
+    /// Create a new window beside the given one, with optional name and starting
+    /// directory. Later windows move up an index to make room (`-b` needs tmux 3.2)
+    pub fn new_window_placed(
+        placement: Placement,
+        window_id: TmuxTarget,
+        name: Option<&str>,
+        cwd: Option<&str>,
+    ) -> String {
+        let mut cmd = format!("new-window {} -t {}", placement.flag(), window_id);
+        if let Some(dir) = cwd {
+            cmd.push_str(&format!(" -c '{}'", escape_single_quotes(dir)));
+        }
+        if let Some(n) = name {
+            cmd.push_str(&format!(" -n '{}'", escape_single_quotes(n)));
+        }
+        cmd
+    }
+
     /// Select (switch to) a window by ID
-    pub fn select_window(window_id: &str) -> String {
+    pub fn select_window(window_id: TmuxTarget) -> String {
         format!("select-window -t {}", window_id)
     }
 
     /// Rename a window
-    pub fn rename_window(window_id: &str, name: &str) -> String {
+    pub fn rename_window(window_id: TmuxTarget, name: &str) -> String {
         // Use double quotes for tmux control mode compatibility with spaces
         format!("rename-window -t {} \"{}\"", window_id, escape_double_quotes(name))
     }
 
     /// Enable automatic window renaming (resets to showing running process)
-    pub fn enable_automatic_rename(window_id: &str) -> String {
+    pub fn enable_automatic_rename(window_id: TmuxTarget) -> String {
         format!("set-window-option -t {} automatic-rename on", window_id)
     }
 
+    /// Disable automatic window renaming, keeping the current name
+    pub fn disable_automatic_rename(window_id: TmuxTarget) -> String {
+        format!("set-window-option -t {} automatic-rename off", window_id)
+    }
+
+    /// Turn tmux's synchronize-panes on or off for a window, so input typed into
+    /// one of its panes goes to all of them (for every attached client)
+    pub fn set_synchronize_panes(window_id: TmuxTarget, on: bool) -> String {
+        format!(
+            "set-window-option -t {} synchronize-panes {}",
+            window_id,
+            if on { "on" } else { "off" }
+        )
+    }
+
+    /// Restart a window's command, killing it first if it's still running
+    pub fn respawn_window(window_id: TmuxTarget) -> String {
+        format!("respawn-window -k -t {}", window_id)
+    }
+
+    /// Ask whether a window's active pane has exited: "1" if so, "0" if its
+    /// command is still running
+    pub fn pane_dead(window_id: TmuxTarget) -> String {
+        format!("display-message -p -t {} '#{{pane_dead}}'", window_id)
+    }
+
+    /// Swap two windows' positions
+    pub fn swap_window(src_window_id: TmuxTarget, dst_window_id: TmuxTarget) -> String {
+        format!("swap-window -s {} -t {}", src_window_id, dst_window_id)
+    }
+
+    /// Swap the current window with another, keeping it current at its new index
+    pub fn swap_with_current(dst_window_id: TmuxTarget) -> String {
+        format!("swap-window -d -t {}", dst_window_id)
+    }
+
+    /// Close the gaps in the session's window indices, keeping their order
+    pub fn renumber_windows() -> String {
+        "move-window -r".to_string()
+    }
+
+    /// Move a window to just after or before another, renumbering the windows in
+    /// between (`-b` needs tmux 3.2)
+    pub fn move_window(src_window_id: TmuxTarget, placement: Placement, dst_window_id: TmuxTarget) -> String {
+        format!("move-window {} -s {} -t {}", placement.flag(), src_window_id, dst_window_id)
+    }
+
+    /// Move a pane into another window as a split
+    pub fn join_pane(src_pane_id: TmuxTarget, dst_target: TmuxTarget) -> String {
+        format!("join-pane -s {} -t {}", src_pane_id, dst_target)
+    }
+
     /// Kill (close) a window
-    pub fn kill_window(window_id: &str) -> String {
+    pub fn kill_window(window_id: TmuxTarget) -> String {
         format!("kill-window -t {}", window_id)
     }
 
+    /// Kill every window except the given one
+    pub fn kill_other_windows(window_id: TmuxTarget) -> String {
+        format!("kill-window -a -t {}", window_id)
+    }
+
     /// Send keys to a pane
     /// Keys can be key names (Space, Enter, Up) or literal characters
-    pub fn send_keys(pane_id: &str, keys: &str) -> String {
+    pub fn send_keys(pane_id: TmuxTarget, keys: &str) -> String {
         // Key names should not be quoted, but special characters need escaping
         let escaped = escape_for_send_keys(keys);
         format!("send-keys -t {} {}", pane_id, escaped)
     }
 
     /// Send literal text to a pane (automatically quoted)
-    pub fn send_text(pane_id: &str, text: &str) -> String {
+    pub fn send_text(pane_id: TmuxTarget, text: &str) -> String {
         format!("send-keys -t {} -l '{}'", pane_id, escape_single_quotes(text))
     }
 
+    /// Send an escape sequence (ESC followed by `sequence`) to a pane as literal input
+    pub fn send_escape_sequence(pane_id: TmuxTarget, sequence: &str) -> String {
+        let escaped = sequence.replace('\\', "\\\\").replace('\'', "\\'");
+        format!("send-keys -t {} -l $'\\e{}'", pane_id, escaped)
+    }
+
+    /// Resize a pane by moving its edge in the given direction
+    pub fn resize_pane(pane_id: TmuxTarget, direction: Direction, amount: u16) -> String {
+        format!("resize-pane -t {} {} {}", pane_id, direction.flag(), amount)
+    }
+
+    /// Clear a pane's scrollback history
+    pub fn clear_history(pane_id: TmuxTarget) -> String {
+        format!("clear-history -t {}", pane_id)
+    }
+
+    /// Send raw bytes to a pane with send-keys -H (tmux 3.0+)
+    /// Each byte goes as a hex pair, so no quoting is involved at all
+    pub fn send_hex(pane_id: TmuxTarget, bytes: &[u8]) -> String {
+        let mut cmd = format!("send-keys -t {} -H", pane_id);
+        for byte in bytes {
+            cmd.push_str(&format!(" {:02x}", byte));
+        }
+        cmd
+    }
+
+    /// Send raw bytes to a pane
+    /// Uses -H unless the server is known to predate it, in which case the bytes
+    /// go as a double-quoted literal with control characters escaped
+    pub fn send_bytes(pane_id: TmuxTarget, bytes: &[u8], version: Option<TmuxVersion>) -> String {
+        if version.is_some_and(|v| !v.supports_send_keys_hex()) {
+            let text = String::from_utf8_lossy(bytes);
+            return format!("send-keys -t {} -l \"{}\"", pane_id, escape_literal(&text));
+        }
+        Self::send_hex(pane_id, bytes)
+    }
+
     /// Refresh client size (set viewport dimensions)
-    pub fn refresh_client_size(width: u16, height: u16) -> String {
-        format!("refresh-client -C {},{}", width, height)
+    /// Uses WxH on servers known to support it, otherwise the W,H form every version accepts
+    pub fn refresh_client_size(width: u16, height: u16, version: Option<TmuxVersion>) -> String {
+        if version.is_some_and(|v| v.supports_wxh_client_size()) {
+            format!("refresh-client -C {}x{}", width, height)
+        } else {
+            format!("refresh-client -C {},{}", width, height)
+        }
     }
 
     /// Capture pane content with escape sequences
-    pub fn capture_pane(pane_id: &str) -> String {
+    pub fn capture_pane(pane_id: TmuxTarget) -> String {
         format!("capture-pane -t {} -p -e", pane_id)
     }
 
@@ -64,11 +242,45 @@ impl Commands {
         format!("display-message -p '{}'", format)
     }
 
+    /// Set a tmux option, globally (-g) or for the current session
+    pub fn set_option(name: &str, value: &str, global: bool) -> String {
+        if global {
+            format!("set-option -g {} {}", name, value)
+        } else {
+            format!("set-option {} {}", name, value)
+        }
+    }
+
+    /// Turn tmux's activity monitoring on or off for all windows, so
+    /// `window_activity_flag` is reported even for output helmux didn't see
+    pub fn monitor_activity(on: bool) -> String {
+        format!("set-window-option -g monitor-activity {}", if on { "on" } else { "off" })
+    }
+
+    /// Show the global value of a single window option
+    pub fn show_window_option(name: &str) -> String {
+        format!("show-options -gwv {}", name)
+    }
+
+    /// Show the value of a single tmux option
+    pub fn show_option(name: &str, global: bool) -> String {
+        if global {
+            format!("show-options -gv {}", name)
+        } else {
+            format!("show-options -v {}", name)
+        }
+    }
+
     /// Detach from session
     pub fn detach() -> String {
         "detach-client".to_string()
     }
 
+    /// Kill the attached session, closing every window in it
+    pub fn kill_session() -> String {
+        "kill-session".to_string()
+    }
+
     /// List panes in current window
     pub fn list_panes() -> String {
         "list-panes -F '#{pane_id}:#{pane_active}:#{pane_width}:#{pane_height}'".to_string()
@@ -85,6 +297,27 @@ fn escape_double_quotes(s: &str) -> String {
     s.replace('\\', "\\\\").replace('"', "\\\"")
 }
 
+/// Escape text for a double-quoted tmux argument, keeping control characters
+/// out of the command line
+fn escape_literal(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '\\' | '"' | '$' => {
+                out.push('\\');
+                out.push(c);
+            }
+            '\x1b' => out.push_str("\\e"),
+            '\r' => out.push_str("\\r"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_ascii_control() => out.push_str(&format!("\\{:03o}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
 /// Check if a string is a tmux key name (not a literal character)
 fn is_key_name(s: &str) -> bool {
     matches!(
@@ -102,9 +335,13 @@ fn is_key_name(s: &str) -> bool {
 /// Key names (Space, Enter, C-a, etc.) are not quoted
 /// Literal characters may need quoting for special chars
 fn escape_for_send_keys(s: &str) -> String {
-    if is_key_name(s) {
+    if is_key_name(s) && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
         // Key names are passed directly without quotes
         s.to_string()
+    } else if is_key_name(s) {
+        // A modified special character (M-; or M- ) would otherwise end the
+        // command or split the argument
+        format!("'{}'", escape_single_quotes(s))
     } else if s.len() == 1 {
         // Single character - quote if it's a special shell character
         let c = s.chars().next().unwrap();
@@ -125,6 +362,10 @@ fn escape_for_send_keys(s: &str) -> String {
 mod tests {
     use super::*;
 
+    fn t(target: &str) -> TmuxTarget<'_> {
+        TmuxTarget::new(target).unwrap()
+    }
+
     #[test]
     fn test_list_windows() {
         assert!(Commands::list_windows().contains("list-windows"));
@@ -136,6 +377,39 @@ mod tests {
         assert_eq!(Commands::new_window(Some("test")), "new-window -n 'test'");
     }
 
+    #[test]
+    fn test_new_window_in() {
+        assert_eq!(Commands::new_window_in(None, "/srv/app"), "new-window -c '/srv/app'");
+        assert_eq!(
+            Commands::new_window_in(Some("logs"), "/home/me/my project's"),
+            "new-window -c '/home/me/my project'\\''s' -n 'logs'"
+        );
+    }
+
+    #[test]
+    fn test_new_window_placed() {
+        assert_eq!(Commands::new_window_after(t("@3"), None), "new-window -a -t @3");
+        assert_eq!(Commands::new_window_after(t("@3"), Some("it's")), "new-window -a -t @3 -n 'it'\\''s'");
+        assert_eq!(
+            Commands::new_window_placed(Placement::Before, t("@1"), None, None),
+            "new-window -b -t @1"
+        );
+        assert_eq!(
+            Commands::new_window_placed(Placement::After, t("@2"), Some("logs"), Some("/srv/app")),
+            "new-window -a -t @2 -c '/srv/app' -n 'logs'"
+        );
+    }
+
+    #[test]
+    fn test_send_keys() {
+        assert_eq!(Commands::send_keys(t("%1"), "Enter"), "send-keys -t %1 Enter");
+        assert_eq!(Commands::send_keys(t("%1"), "M-x"), "send-keys -t %1 M-x");
+        assert_eq!(Commands::send_keys(t("%1"), "M--"), "send-keys -t %1 M--");
+        assert_eq!(Commands::send_keys(t("%1"), ";"), "send-keys -t %1 ';'");
+        assert_eq!(Commands::send_keys(t("%1"), "M-;"), "send-keys -t %1 'M-;'");
+        assert_eq!(Commands::send_keys(t("%1"), "M- "), "send-keys -t %1 'M- '");
+    }
+
     #[test]
     fn test_escape_single_quotes() {
         assert_eq!(escape_single_quotes("it's"), "it'\\''s");
@@ -143,23 +417,109 @@ mod tests {
 
     #[test]
     fn test_select_window() {
-        assert_eq!(Commands::select_window("@1"), "select-window -t @1");
+        assert_eq!(Commands::select_window(t("@1")), "select-window -t @1");
+    }
+
+    #[test]
+    fn test_refresh_client_size() {
+        assert_eq!(Commands::refresh_client_size(80, 24, None), "refresh-client -C 80,24");
+        assert_eq!(
+            Commands::refresh_client_size(80, 24, Some(TmuxVersion::new(2, 8))),
+            "refresh-client -C 80,24"
+        );
+        assert_eq!(
+            Commands::refresh_client_size(80, 24, Some(TmuxVersion::new(3, 4))),
+            "refresh-client -C 80x24"
+        );
+    }
+
+    #[test]
+    fn test_send_hex() {
+        assert_eq!(Commands::send_hex(t("%1"), "€".as_bytes()), "send-keys -t %1 -H e2 82 ac");
+        assert_eq!(Commands::send_hex(t("%1"), b"'"), "send-keys -t %1 -H 27");
+        assert_eq!(Commands::send_hex(t("%2"), b"$`"), "send-keys -t %2 -H 24 60");
+    }
+
+    #[test]
+    fn test_send_bytes() {
+        assert_eq!(Commands::send_bytes(t("%1"), "é".as_bytes(), None), "send-keys -t %1 -H c3 a9");
+        let new = Some(TmuxVersion::new(3, 0));
+        assert_eq!(Commands::send_bytes(t("%1"), b"\x1b[I", new), "send-keys -t %1 -H 1b 5b 49");
+
+        let old = Some(TmuxVersion::new(2, 9));
+        assert_eq!(Commands::send_bytes(t("%1"), b"it's", old), "send-keys -t %1 -l \"it's\"");
+        assert_eq!(Commands::send_bytes(t("%1"), b"a\"$\\b", old), "send-keys -t %1 -l \"a\\\"\\$\\\\b\"");
+        assert_eq!(
+            Commands::send_bytes(t("%1"), b"x\x1b[Iy\r\x03", old),
+            "send-keys -t %1 -l \"x\\e[Iy\\r\\003\""
+        );
+    }
+
+    #[test]
+    fn test_send_escape_sequence() {
+        assert_eq!(Commands::send_escape_sequence(t("%1"), "[<0;6;4M"), "send-keys -t %1 -l $'\\e[<0;6;4M'");
+        assert_eq!(Commands::send_escape_sequence(t("%2"), "a'b\\"), "send-keys -t %2 -l $'\\ea\\'b\\\\'");
+    }
+
+    #[test]
+    fn test_resize_pane() {
+        assert_eq!(Commands::resize_pane(t("%1"), Direction::Left, 1), "resize-pane -t %1 -L 1");
+        assert_eq!(Commands::resize_pane(t("%1"), Direction::Right, 5), "resize-pane -t %1 -R 5");
+        assert_eq!(Commands::resize_pane(t("%2"), Direction::Up, 1), "resize-pane -t %2 -U 1");
+        assert_eq!(Commands::resize_pane(t("%2"), Direction::Down, 3), "resize-pane -t %2 -D 3");
+    }
+
+    #[test]
+    fn test_clear_history() {
+        assert_eq!(Commands::clear_history(t("%3")), "clear-history -t %3");
+    }
+
+    #[test]
+    fn test_kill_other_windows() {
+        assert_eq!(Commands::kill_other_windows(t("@2")), "kill-window -a -t @2");
+    }
+
+    #[test]
+    fn test_pane_dead() {
+        assert_eq!(Commands::pane_dead(t("@2")), "display-message -p -t @2 '#{pane_dead}'");
+    }
+
+    #[test]
+    fn test_swap_window() {
+        assert_eq!(Commands::swap_window(t("@1"), t("@3")), "swap-window -s @1 -t @3");
+        assert_eq!(Commands::swap_with_current(t("@2")), "swap-window -d -t @2");
+    }
+
+    #[test]
+    fn test_renumber_windows() {
+        assert_eq!(Commands::renumber_windows(), "move-window -r");
+    }
+
+    #[test]
+    fn test_move_window() {
+        assert_eq!(Commands::move_window(t("@1"), Placement::After, t("@3")), "move-window -a -s @1 -t @3");
+        assert_eq!(Commands::move_window(t("@3"), Placement::Before, t("@1")), "move-window -b -s @3 -t @1");
+    }
+
+    #[test]
+    fn test_join_pane() {
+        assert_eq!(Commands::join_pane(t("%3"), t("@1")), "join-pane -s %3 -t @1");
     }
 
     #[test]
     fn test_rename_window() {
         assert_eq!(
-            Commands::rename_window("@1", "my-tab"),
+            Commands::rename_window(t("@1"), "my-tab"),
             "rename-window -t @1 \"my-tab\""
         );
         // Test with spaces
         assert_eq!(
-            Commands::rename_window("@1", "my tab name"),
+            Commands::rename_window(t("@1"), "my tab name"),
             "rename-window -t @1 \"my tab name\""
         );
         // Test with quotes
         assert_eq!(
-            Commands::rename_window("@1", "tab \"quoted\""),
+            Commands::rename_window(t("@1"), "tab \"quoted\""),
             "rename-window -t @1 \"tab \\\"quoted\\\"\""
         );
     }
@@ -167,8 +527,64 @@ mod tests {
     #[test]
     fn test_enable_automatic_rename() {
         assert_eq!(
-            Commands::enable_automatic_rename("@1"),
+            Commands::enable_automatic_rename(t("@1")),
             "set-window-option -t @1 automatic-rename on"
         );
+        // The window id is interpolated as-is
+        assert_eq!(
+            Commands::enable_automatic_rename(t("@12")),
+            "set-window-option -t @12 automatic-rename on"
+        );
+    }
+
+    #[test]
+    fn test_monitor_activity() {
+        assert_eq!(Commands::monitor_activity(true), "set-window-option -g monitor-activity on");
+        assert_eq!(Commands::monitor_activity(false), "set-window-option -g monitor-activity off");
+        assert_eq!(Commands::show_window_option("monitor-activity"), "show-options -gwv monitor-activity");
+    }
+
+    #[test]
+    fn test_disable_automatic_rename() {
+        assert_eq!(
+            Commands::disable_automatic_rename(t("@1")),
+            "set-window-option -t @1 automatic-rename off"
+        );
+        // The window id is interpolated as-is
+        assert_eq!(
+            Commands::disable_automatic_rename(t("@12")),
+            "set-window-option -t @12 automatic-rename off"
+        );
+    }
+
+    #[test]
+    fn test_set_synchronize_panes() {
+        assert_eq!(
+            Commands::set_synchronize_panes(t("@3"), true),
+            "set-window-option -t @3 synchronize-panes on"
+        );
+        assert_eq!(
+            Commands::set_synchronize_panes(t("@3"), false),
+            "set-window-option -t @3 synchronize-panes off"
+        );
+        assert!(Commands::list_windows().contains("#{synchronize-panes}"));
+    }
+
+    #[test]
+    fn test_respawn_window() {
+        assert_eq!(Commands::respawn_window(t("@2")), "respawn-window -k -t @2");
+        assert!(Commands::list_windows().contains("#{pane_dead}"));
+    }
+
+    #[test]
+    fn test_set_option() {
+        assert_eq!(Commands::set_option("mouse", "on", true), "set-option -g mouse on");
+        assert_eq!(Commands::set_option("mouse", "off", false), "set-option mouse off");
+    }
+
+    #[test]
+    fn test_show_option() {
+        assert_eq!(Commands::show_option("mouse", true), "show-options -gv mouse");
+        assert_eq!(Commands::show_option("mouse", false), "show-options -v mouse");
     }
 }
@@ -1,13 +1,20 @@
+use std::collections::VecDeque;
 use std::process::Stdio;
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
-use tokio::process::{Child, ChildStdin, ChildStdout, Command};
+use std::sync::{Arc, Mutex, OnceLock};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::process::{Child, ChildStdin, Command};
+use tokio::sync::mpsc;
 use thiserror::Error;
-use tracing::{debug, warn};
+use tracing::{debug, trace_span, warn, Instrument};
 
+use super::commands::Commands;
 use super::protocol::{Notification, TmuxEvent};
+use super::version::TmuxVersion;
 
 #[derive(Debug, Error)]
 pub enum ConnectionError {
+    #[error("tmux not found in PATH")]
+    TmuxNotFound,
     #[error("Failed to spawn tmux: {0}")]
     SpawnFailed(#[from] std::io::Error),
     #[error("tmux stdin not available")]
@@ -24,16 +31,241 @@ pub enum ConnectionError {
 
 pub type Result<T> = std::result::Result<T, ConnectionError>;
 
+/// How many events the reader task can get ahead of the main loop before it
+/// stops reading from tmux
+const EVENT_CHANNEL_CAPACITY: usize = 1024;
+
+/// IDs of sent commands still waiting for a %begin block, in send order
+/// Shared between the connection, which hands IDs out, and the reader task
+type PendingIds = Arc<Mutex<VecDeque<u64>>>;
+
+/// What happens to the tmux session when helmux exits
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ExitPolicy {
+    /// Detach and leave the session running, to attach to again later
+    #[default]
+    Detach,
+    /// Kill the session and everything running in it
+    KillSession,
+}
+
+impl ExitPolicy {
+    /// The tmux command that carries out this policy
+    pub fn command(self) -> String {
+        match self {
+            ExitPolicy::Detach => Commands::detach(),
+            ExitPolicy::KillSession => Commands::kill_session(),
+        }
+    }
+}
+
 /// Connection to tmux in control mode
+/// Dropping it closes tmux's stdin, and a control mode client detaches and
+/// exits when its input ends, so the session keeps running (as with
+/// ExitPolicy::Detach). The session is only killed when asked for
 pub struct TmuxConnection {
     child: Child,
     stdin: ChildStdin,
-    stdout: BufReader<ChildStdout>,
+    /// Events parsed by the reader task
+    events: mpsc::Receiver<Result<TmuxEvent>>,
     command_id: u64,
+    /// Commands waiting to be written to tmux
+    outbound: OutboundQueue,
+    /// IDs the reader task maps response blocks back to
+    pending_ids: PendingIds,
+    /// Server version, set by the reader task once known
+    version: Arc<OnceLock<TmuxVersion>>,
+    /// Most recently sent command, for the debug overlay
+    last_command: Option<String>,
+}
+
+/// Map a failure to start tmux, singling out a missing binary
+fn spawn_error(e: std::io::Error) -> ConnectionError {
+    if e.kind() == std::io::ErrorKind::NotFound {
+        ConnectionError::TmuxNotFound
+    } else {
+        ConnectionError::SpawnFailed(e)
+    }
+}
+
+/// Run `<program> -V` to check it exists
+fn check_program(program: &str) -> Result<()> {
+    std::process::Command::new(program)
+        .arg("-V")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map_err(spawn_error)?;
+    Ok(())
+}
+
+/// Read from `reader` until a complete line produces an event
+/// Returns None at end of stream. Only the read itself awaits, and it doesn't
+/// consume anything when cancelled, so no output is lost if this future is dropped
+async fn read_event<R: AsyncRead + Unpin>(
+    reader: &mut R,
+    lines: &mut LineBuffer,
+    state: &mut ControlState,
+) -> Result<Option<TmuxEvent>> {
+    loop {
+        while let Some(line) = lines.next_line() {
+            if let Some(event) = state.handle_line(&line)? {
+                return Ok(Some(event));
+            }
+        }
+
+        let mut chunk = [0u8; 4096];
+        let n = reader.read(&mut chunk).await?;
+        if n == 0 {
+            return Ok(None);
+        }
+        lines.extend(&chunk[..n]);
+    }
+}
+
+/// Read and parse everything tmux sends, forwarding events to the connection
+/// Runs as its own task, so reading never depends on when the main loop gets
+/// round to asking. Ends when tmux's output ends, reading fails (the error is
+/// forwarded) or the connection is dropped
+async fn forward_events<R: AsyncRead + Unpin>(
+    mut reader: R,
+    mut state: ControlState,
+    events: mpsc::Sender<Result<TmuxEvent>>,
+) {
+    let mut lines = LineBuffer::default();
+    loop {
+        let event = match read_event(&mut reader, &mut lines, &mut state).await {
+            Ok(Some(event)) => Ok(event),
+            Ok(None) => break,
+            Err(e) => Err(e),
+        };
+        let failed = event.is_err();
+        if events.send(event).await.is_err() || failed {
+            break;
+        }
+    }
+}
+
+/// Commands queued for tmux, written out together
+#[derive(Debug, Default)]
+struct OutboundQueue {
+    bytes: Vec<u8>,
+}
+
+impl OutboundQueue {
+    fn push(&mut self, cmd: &str) {
+        self.bytes.extend_from_slice(cmd.as_bytes());
+        self.bytes.push(b'\n');
+    }
+
+    fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    /// Write every queued command in order, usually with a single write, and flush
+    /// Bytes leave the queue as they're written, so a flush cancelled part way
+    /// resumes where it stopped instead of sending commands twice
+    async fn flush_to<W: AsyncWrite + Unpin>(&mut self, writer: &mut W) -> Result<()> {
+        if self.bytes.is_empty() {
+            return Ok(());
+        }
+        while !self.bytes.is_empty() {
+            let written = writer.write(&self.bytes).await?;
+            if written == 0 {
+                return Err(ConnectionError::Closed);
+            }
+            self.bytes.drain(..written);
+        }
+        writer.flush().await?;
+        Ok(())
+    }
+}
+
+/// Longest unfinished line kept whole; a pane printing endlessly without a
+/// newline would otherwise grow the buffer without bound
+const MAX_LINE_LEN: usize = 1 << 20;
+
+/// Accumulates raw output and splits it into lines
+#[derive(Debug, Default)]
+struct LineBuffer {
+    bytes: Vec<u8>,
+    /// How much of `bytes` is already known to hold no newline
+    searched: usize,
+}
+
+impl LineBuffer {
+    fn extend(&mut self, data: &[u8]) {
+        self.bytes.extend_from_slice(data);
+    }
+
+    /// Take the next complete line, without its line ending
+    /// An unfinished line longer than MAX_LINE_LEN comes out in pieces instead
+    fn next_line(&mut self) -> Option<String> {
+        let Some(end) = self.bytes[self.searched..].iter().position(|&b| b == b'\n') else {
+            self.searched = self.bytes.len();
+            return (self.bytes.len() > MAX_LINE_LEN).then(|| self.split_long_line());
+        };
+        let line: Vec<u8> = self.bytes.drain(..=self.searched + end).collect();
+        self.searched = 0;
+        // Only trim newlines, not spaces - spaces might be significant in %output data
+        let text = String::from_utf8_lossy(&line);
+        Some(text.trim_end_matches(['\n', '\r']).to_string())
+    }
+
+    /// Cut MAX_LINE_LEN bytes off the front of an overlong unfinished line
+    /// Output notifications keep their prefix for the rest of the line, so each
+    /// piece is a complete notification for the same pane; other lines are just
+    /// broken in two. The cut never lands inside an escape or a UTF-8 character
+    fn split_long_line(&mut self) -> String {
+        let prefix_len = output_prefix_len(&self.bytes);
+        let mut cut = MAX_LINE_LEN;
+        // tmux escapes are a backslash and three octal digits
+        if let Some(i) = self.bytes[cut - 3..cut].iter().rposition(|&b| b == b'\\') {
+            cut = cut - 3 + i;
+        }
+        while cut > prefix_len + 1 && self.bytes[cut] & 0xc0 == 0x80 {
+            cut -= 1;
+        }
+
+        let mut piece = self.bytes[..prefix_len].to_vec();
+        piece.extend(self.bytes.drain(prefix_len..cut));
+        self.searched = self.bytes.len();
+        String::from_utf8_lossy(&piece).into_owned()
+    }
+}
+
+/// Length of the `%output <pane> ` or `%extended-output <pane> ... : ` prefix
+/// a line starts with, or 0 if it isn't output
+fn output_prefix_len(line: &[u8]) -> usize {
+    let find = |needle: &[u8], from: usize| {
+        line[from..]
+            .windows(needle.len())
+            .position(|w| w == needle)
+            .map_or(0, |i| from + i + needle.len())
+    };
+    if line.starts_with(b"%output ") {
+        find(b" ", "%output ".len())
+    } else if line.starts_with(b"%extended-output ") {
+        find(b" : ", "%extended-output ".len())
+    } else {
+        0
+    }
+}
+
+/// Turns control mode lines into events, assembling command responses
+#[derive(Debug, Default)]
+struct ControlState {
+    /// IDs of sent commands still waiting for a %begin block, in send order
+    pending_ids: PendingIds,
     /// Buffer for collecting command response data
     response_buffer: Vec<String>,
-    /// Current command ID we're collecting response for
-    collecting_for: Option<u64>,
+    /// tmux command number we're collecting a response for, and the ID we
+    /// handed out for it (None for blocks we didn't send, e.g. the initial attach)
+    collecting_for: Option<(u64, Option<u64>)>,
+    /// ID of the version query sent on connect, until its response arrives
+    version_query: Option<u64>,
+    /// Server version, once known
+    version: Arc<OnceLock<TmuxVersion>>,
 }
 
 impl TmuxConnection {
@@ -41,13 +273,16 @@ impl TmuxConnection {
     pub async fn connect(session: &str) -> Result<Self> {
         debug!("Connecting to tmux session: {}", session);
 
-        // Use -C for control mode (not -CC which is iTerm2 specific)
+        // Use -C for control mode (not -CC which is iTerm2 specific). -u keeps
+        // tmux from replacing UTF-8 and control characters in responses with '_'
+        // when the locale isn't UTF-8
         let mut child = Command::new("tmux")
-            .args(["-C", "new-session", "-A", "-s", session])
+            .args(["-u", "-C", "new-session", "-A", "-s", session])
             .stdin(Stdio::piped())
             .stdout(Stdio::piped())
             .stderr(Stdio::piped())
-            .spawn()?;
+            .spawn()
+            .map_err(spawn_error)?;
 
         let stdin = child.stdin.take().ok_or(ConnectionError::NoStdin)?;
         let stdout = child.stdout.take().ok_or(ConnectionError::NoStdout)?;
@@ -67,140 +302,538 @@ impl TmuxConnection {
             });
         }
 
-        Ok(Self {
+        let (events_tx, events) = mpsc::channel(EVENT_CHANNEL_CAPACITY);
+        let mut conn = Self {
             child,
             stdin,
-            stdout: BufReader::new(stdout),
+            events,
             command_id: 0,
-            response_buffer: Vec::new(),
-            collecting_for: None,
-        })
+            outbound: OutboundQueue::default(),
+            pending_ids: PendingIds::default(),
+            version: Arc::default(),
+            last_command: None,
+        };
+
+        // Ask for the server version; the response is consumed by the reader
+        // task rather than surfacing as an event
+        let query = conn.send_command(&Commands::display_message("#{version}")).await?;
+        let state = ControlState {
+            pending_ids: conn.pending_ids.clone(),
+            version_query: Some(query),
+            version: conn.version.clone(),
+            ..ControlState::default()
+        };
+        tokio::spawn(forward_events(stdout, state, events_tx));
+
+        Ok(conn)
     }
 
-    /// Send a command to tmux and return a command ID
-    /// The response will come back via next_event() as CommandResponse
+    /// The tmux server version, once its response to the connect-time query has arrived
+    pub fn tmux_version(&self) -> Option<TmuxVersion> {
+        self.version.get().copied()
+    }
+
+    /// The command most recently sent
+    pub fn last_command(&self) -> Option<&str> {
+        self.last_command.as_deref()
+    }
+
+    /// Check that tmux can be run, so a missing install is reported before
+    /// the terminal is taken over
+    pub fn check_installed() -> Result<()> {
+        check_program("tmux")
+    }
+
+    /// Queue a command for tmux and return a command ID
+    /// The command is written on the next `flush()` (or `next_event()`), so a
+    /// burst of keystrokes goes out in one write. The response will come back
+    /// via next_event() as CommandResponse with the same ID
     pub async fn send_command(&mut self, cmd: &str) -> Result<u64> {
         self.command_id += 1;
         let id = self.command_id;
-        debug!("Sending command [{}]: {}", id, cmd);
-        self.stdin.write_all(cmd.as_bytes()).await?;
-        self.stdin.write_all(b"\n").await?;
-        self.stdin.flush().await?;
+        self.pending_ids.lock().unwrap().push_back(id);
+        debug!("Queueing command [{}]: {}", id, cmd);
+        self.outbound.push(cmd);
+        self.last_command = Some(cmd.to_string());
         Ok(id)
     }
 
-    /// Read the next event from tmux
-    /// This processes notifications and assembles command responses
-    pub async fn next_event(&mut self) -> Result<TmuxEvent> {
-        loop {
-            let mut line = String::new();
-            let bytes_read = self.stdout.read_line(&mut line).await?;
+    /// Write all queued commands to tmux
+    pub async fn flush(&mut self) -> Result<()> {
+        self.outbound.flush_to(&mut self.stdin).await
+    }
 
-            if bytes_read == 0 {
+    /// Wait for the next event from tmux, as parsed by the reader task
+    /// Cancellation-safe, so this can be used in `tokio::select!`
+    pub async fn next_event(&mut self) -> Result<TmuxEvent> {
+        // Responses can't arrive for commands that were never written. A
+        // flush cut off here picks up where it left off on the next call
+        if !self.outbound.is_empty() {
+            self.flush().await?;
+        }
+        match self.events.recv().instrument(trace_span!("next_event")).await {
+            Some(event) => event,
+            None => {
                 // Check if tmux process exited
                 if let Ok(Some(status)) = self.child.try_wait() {
                     debug!("tmux process exited with status: {:?}", status);
                 }
-                return Err(ConnectionError::Closed);
+                Err(ConnectionError::Closed)
             }
+        }
+    }
+
+    /// Check if the tmux process is still running
+    pub fn is_running(&mut self) -> bool {
+        matches!(self.child.try_wait(), Ok(None))
+    }
 
-            // Only trim newlines, not spaces - spaces might be significant in %output data
-            let line = line.trim_end_matches(|c| c == '\n' || c == '\r');
+    /// Leave tmux according to `policy`, writing the command out straight away
+    pub async fn exit(&mut self, policy: ExitPolicy) -> Result<()> {
+        self.send_command(&policy.command()).await?;
+        self.flush().await
+    }
 
-            let notification = Notification::parse(line)?;
+    /// Gracefully detach from tmux
+    pub async fn detach(&mut self) -> Result<()> {
+        self.exit(ExitPolicy::Detach).await
+    }
 
-            match notification {
-                Notification::Begin { id } => {
-                    self.collecting_for = Some(id);
-                    self.response_buffer.clear();
-                    // Continue reading to get the response
+    /// Kill the tmux session
+    pub async fn kill_session(&mut self) -> Result<()> {
+        self.exit(ExitPolicy::KillSession).await
+    }
+}
+
+impl ControlState {
+    /// Handle one line of control mode output (without its line ending)
+    /// Returns an event once a notification or complete response is available
+    fn handle_line(&mut self, line: &str) -> Result<Option<TmuxEvent>> {
+        // Inside a response block every line is data until the matching
+        // %end/%error, even if it happens to start with '%' (e.g. a pane id or
+        // captured text that looks like a notification)
+        let notification = match self.collecting_for {
+            Some((number, _)) => match Notification::parse(line) {
+                Ok(n @ (Notification::End { id } | Notification::Error { id })) if id == number => n,
+                _ => Notification::Data(line.to_string()),
+            },
+            None => match Notification::parse(line) {
+                Ok(notification) => notification,
+                // One bad line (e.g. a malformed id) shouldn't end the session
+                Err(e) => {
+                    warn!("Skipping tmux line {:?}: {}", line, e);
+                    return Ok(None);
                 }
-                Notification::End { id } => {
-                    if self.collecting_for == Some(id) {
+            },
+        };
+
+        match notification {
+            Notification::Begin { id, from_client } => {
+                // tmux numbers commands server-wide, but answers our commands
+                // in the order we sent them, so map blocks back to our IDs
+                let ours = if from_client { self.pending_ids.lock().unwrap().pop_front() } else { None };
+                self.collecting_for = Some((id, ours));
+                self.response_buffer.clear();
+                // Continue reading to get the response
+            }
+            Notification::End { id } => {
+                if let Some((number, ours)) = self.collecting_for {
+                    if number == id {
                         let data = self.response_buffer.join("\n");
                         self.collecting_for = None;
                         self.response_buffer.clear();
-                        return Ok(TmuxEvent::CommandResponse { id, data });
+                        if ours.is_some() && ours == self.version_query {
+                            self.version_query = None;
+                            if let Some(version) = TmuxVersion::parse(&data) {
+                                let _ = self.version.set(version);
+                            }
+                            debug!("tmux version: {:?}", self.version.get());
+                        } else if let Some(id) = ours {
+                            return Ok(Some(TmuxEvent::CommandResponse { id, data }));
+                        }
                     }
                 }
-                Notification::Error { id } => {
-                    let message = self.response_buffer.join("\n");
-                    self.collecting_for = None;
-                    self.response_buffer.clear();
-                    return Ok(TmuxEvent::CommandError { id, message });
-                }
-                Notification::Data(data) => {
-                    if self.collecting_for.is_some() {
-                        self.response_buffer.push(data);
-                    }
-                    // Continue reading
+            }
+            Notification::Error { id } => {
+                let message = self.response_buffer.join("\n");
+                let ours = self.collecting_for.take().and_then(|(_, ours)| ours);
+                self.response_buffer.clear();
+                return Ok(Some(TmuxEvent::CommandError { id: ours.unwrap_or(id), message }));
+            }
+            Notification::Data(data) => {
+                if self.collecting_for.is_some() {
+                    self.response_buffer.push(data);
                 }
+                // Continue reading
+            }
+            Notification::Output { pane_id, data } => {
+                return Ok(Some(TmuxEvent::Output { pane_id, data }));
+            }
+            Notification::WindowAdd { window_id } => {
+                return Ok(Some(TmuxEvent::WindowAdd { window_id }));
+            }
+            Notification::WindowClose { window_id } => {
+                return Ok(Some(TmuxEvent::WindowClose { window_id }));
+            }
+            Notification::WindowRenamed { window_id, name } => {
+                return Ok(Some(TmuxEvent::WindowRenamed { window_id, name }));
+            }
+            Notification::SessionChanged { session_id, name } => {
+                return Ok(Some(TmuxEvent::SessionChanged { session_id, name }));
+            }
+            Notification::Exit { reason } => {
+                return Ok(Some(TmuxEvent::Exit { reason }));
+            }
+            Notification::SessionWindowChanged { window_id, .. } => {
+                return Ok(Some(TmuxEvent::WindowChanged { window_id }));
+            }
+            Notification::UnlinkedWindowClose { window_id } => {
+                // Treat same as WindowClose
+                return Ok(Some(TmuxEvent::WindowClose { window_id }));
+            }
+            Notification::LayoutChange { .. }
+            | Notification::PaneModeChanged { .. }
+            | Notification::SessionsChanged
+            | Notification::ClientSessionChanged { .. }
+            | Notification::WindowPaneChanged { .. }
+            | Notification::UnlinkedWindowAdd { .. }
+            | Notification::ClientDetached { .. } => {
+                // Ignore these for now, continue reading
+            }
+            Notification::Unknown { notification_type, .. } => {
+                debug!("Unknown tmux notification: {}", notification_type);
+                // Continue reading
+            }
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Feed lines through the state, collecting every event produced
+    fn feed(state: &mut ControlState, lines: &[&str]) -> Vec<TmuxEvent> {
+        lines
+            .iter()
+            .filter_map(|line| state.handle_line(line).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_response_lines_starting_with_percent_are_data() {
+        let mut state = ControlState::default();
+        state.pending_ids.lock().unwrap().push_back(7);
+        let events = feed(
+            &mut state,
+            &["%begin 1 20 1", "%1", "%output %1 not a notification", "%end 1 20 1"],
+        );
+        assert_eq!(events.len(), 1);
+        match &events[0] {
+            TmuxEvent::CommandResponse { id, data } => {
+                assert_eq!(*id, 7);
+                assert_eq!(data, "%1\n%output %1 not a notification");
+            }
+            e => panic!("Expected CommandResponse, got {:?}", e),
+        }
+    }
+
+    #[test]
+    fn test_output_is_not_a_command_response() {
+        let mut state = ControlState::default();
+        state.pending_ids.lock().unwrap().push_back(1);
+        let events = feed(&mut state, &["%output %1 @1:one:1:%1", "%begin 1 5 1", "%end 1 5 1"]);
+        assert_eq!(events.len(), 2);
+        match &events[0] {
+            TmuxEvent::Output { pane_id, data } => {
+                assert_eq!(pane_id, "%1");
+                assert_eq!(data, b"@1:one:1:%1");
+            }
+            e => panic!("Expected Output, got {:?}", e),
+        }
+        match &events[1] {
+            TmuxEvent::CommandResponse { id, data } => {
+                assert_eq!(*id, 1);
+                assert_eq!(data, "");
+            }
+            e => panic!("Expected CommandResponse, got {:?}", e),
+        }
+    }
+
+    #[test]
+    fn test_version_response_is_consumed() {
+        let mut state = ControlState::default();
+        state.pending_ids.lock().unwrap().extend([1, 2]);
+        state.version_query = Some(1);
+        let events = feed(
+            &mut state,
+            &["%begin 1 3 1", "3.3a", "%end 1 3 1", "%begin 1 4 1", "ok", "%end 1 4 1"],
+        );
+        assert_eq!(state.version.get().copied(), TmuxVersion::parse("3.3a"));
+        match &events[..] {
+            [TmuxEvent::CommandResponse { id: 2, data }] => assert_eq!(data, "ok"),
+            e => panic!("Expected only the second response, got {:?}", e),
+        }
+    }
+
+    #[test]
+    fn test_missing_binary_is_tmux_not_found() {
+        let err = spawn_error(std::io::Error::from(std::io::ErrorKind::NotFound));
+        assert!(matches!(err, ConnectionError::TmuxNotFound));
+        assert!(matches!(
+            check_program("helmux-test-no-such-program"),
+            Err(ConnectionError::TmuxNotFound)
+        ));
+
+        let err = spawn_error(std::io::Error::from(std::io::ErrorKind::PermissionDenied));
+        assert!(matches!(err, ConnectionError::SpawnFailed(_)));
+    }
+
+    #[test]
+    fn test_line_buffer_joins_split_reads() {
+        let mut lines = LineBuffer::default();
+        lines.extend(b"%window-ad");
+        assert_eq!(lines.next_line(), None);
+        lines.extend(b"d @5\r\n%out");
+        assert_eq!(lines.next_line().as_deref(), Some("%window-add @5"));
+        assert_eq!(lines.next_line(), None);
+    }
+
+    #[test]
+    fn test_line_buffer_splits_endless_output() {
+        use crate::tmux::protocol::Notification;
+
+        // A pane printing 3 MiB without a newline, with escapes and multibyte
+        // characters that pieces mustn't cut through
+        let mut data = Vec::new();
+        while data.len() < 3 * MAX_LINE_LEN {
+            data.extend_from_slice("progress é\\015".as_bytes());
+        }
+        let mut lines = LineBuffer::default();
+        let mut pieces = Vec::new();
+        lines.extend(b"%output %4 ");
+        for chunk in data.chunks(4096) {
+            lines.extend(chunk);
+            while let Some(line) = lines.next_line() {
+                pieces.push(line);
+            }
+            assert!(lines.bytes.len() <= MAX_LINE_LEN + 4096);
+        }
+        lines.extend(b"\n");
+        pieces.extend(lines.next_line());
+        assert!(pieces.len() > 3);
+
+        let mut decoded = Vec::new();
+        for piece in &pieces {
+            match Notification::parse(piece).unwrap() {
                 Notification::Output { pane_id, data } => {
-                    return Ok(TmuxEvent::Output { pane_id, data });
-                }
-                Notification::WindowAdd { window_id } => {
-                    return Ok(TmuxEvent::WindowAdd { window_id });
-                }
-                Notification::WindowClose { window_id } => {
-                    return Ok(TmuxEvent::WindowClose { window_id });
-                }
-                Notification::WindowRenamed { window_id, name } => {
-                    return Ok(TmuxEvent::WindowRenamed { window_id, name });
-                }
-                Notification::SessionChanged { session_id, name } => {
-                    return Ok(TmuxEvent::SessionChanged { session_id, name });
-                }
-                Notification::Exit { reason } => {
-                    return Ok(TmuxEvent::Exit { reason });
-                }
-                Notification::SessionWindowChanged { window_id, .. } => {
-                    return Ok(TmuxEvent::WindowChanged { window_id });
-                }
-                Notification::UnlinkedWindowClose { window_id } => {
-                    // Treat same as WindowClose
-                    return Ok(TmuxEvent::WindowClose { window_id });
-                }
-                Notification::LayoutChange { .. }
-                | Notification::PaneModeChanged { .. }
-                | Notification::SessionsChanged
-                | Notification::ClientSessionChanged { .. }
-                | Notification::WindowPaneChanged { .. }
-                | Notification::UnlinkedWindowAdd { .. }
-                | Notification::ClientDetached { .. } => {
-                    // Ignore these for now, continue reading
-                }
-                Notification::Unknown { notification_type, .. } => {
-                    debug!("Unknown tmux notification: {}", notification_type);
-                    // Continue reading
+                    assert_eq!(pane_id, "%4");
+                    decoded.extend(data);
                 }
+                n => panic!("Expected Output notification, got {:?}", n),
             }
         }
+        let expected: Vec<u8> = String::from_utf8(data).unwrap().replace("\\015", "\r").into_bytes();
+        assert_eq!(decoded, expected);
     }
 
-    /// Check if the tmux process is still running
-    pub fn is_running(&mut self) -> bool {
-        match self.child.try_wait() {
-            Ok(None) => true,
-            _ => false,
+    #[tokio::test]
+    async fn test_reader_task_forwards_events() {
+        let (mut tmux_side, reader) = tokio::io::duplex(64);
+        let state = ControlState::default();
+        state.pending_ids.lock().unwrap().push_back(5);
+        let (tx, mut events) = mpsc::channel(4);
+        let task = tokio::spawn(forward_events(reader, state, tx));
+
+        tmux_side
+            .write_all(b"%window-add @3\n%begin 1 2 1\nhi\n%end 1 2 1\n%output %1 x\n")
+            .await
+            .unwrap();
+        assert!(matches!(
+            events.recv().await,
+            Some(Ok(TmuxEvent::WindowAdd { window_id })) if window_id == "@3"
+        ));
+        assert!(matches!(
+            events.recv().await,
+            Some(Ok(TmuxEvent::CommandResponse { id: 5, data })) if data == "hi"
+        ));
+        assert!(matches!(events.recv().await, Some(Ok(TmuxEvent::Output { .. }))));
+
+        // tmux's output ending closes the channel
+        drop(tmux_side);
+        assert!(events.recv().await.is_none());
+        task.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_read_keeps_partial_line() {
+        let (mut tmux_side, mut reader) = tokio::io::duplex(64);
+        let mut lines = LineBuffer::default();
+        let mut state = ControlState::default();
+
+        // First chunk arrives, then the read is cancelled while waiting for more
+        tmux_side.write_all(b"%output %1 hel").await.unwrap();
+        let cancelled = tokio::time::timeout(
+            std::time::Duration::from_millis(20),
+            read_event(&mut reader, &mut lines, &mut state),
+        )
+        .await;
+        assert!(cancelled.is_err());
+
+        tmux_side.write_all(b"lo\n").await.unwrap();
+        let event = read_event(&mut reader, &mut lines, &mut state).await.unwrap();
+        match event {
+            Some(TmuxEvent::Output { pane_id, data }) => {
+                assert_eq!(pane_id, "%1");
+                assert_eq!(data, b"hello");
+            }
+            e => panic!("Expected Output, got {:?}", e),
         }
+
+        drop(tmux_side);
+        assert!(read_event(&mut reader, &mut lines, &mut state).await.unwrap().is_none());
     }
 
-    /// Gracefully detach from tmux
-    pub async fn detach(&mut self) -> Result<()> {
-        self.send_command("detach-client").await?;
-        Ok(())
+    /// Writer that records what was written and how often it was flushed
+    #[derive(Default)]
+    struct CountingWriter {
+        written: Vec<u8>,
+        writes: usize,
+        flushes: usize,
     }
 
-    /// Kill the tmux session
-    pub async fn kill_session(&mut self) -> Result<()> {
-        self.send_command("kill-session").await?;
-        Ok(())
+    impl AsyncWrite for CountingWriter {
+        fn poll_write(
+            mut self: std::pin::Pin<&mut Self>,
+            _cx: &mut std::task::Context<'_>,
+            buf: &[u8],
+        ) -> std::task::Poll<std::io::Result<usize>> {
+            self.written.extend_from_slice(buf);
+            self.writes += 1;
+            std::task::Poll::Ready(Ok(buf.len()))
+        }
+
+        fn poll_flush(
+            mut self: std::pin::Pin<&mut Self>,
+            _cx: &mut std::task::Context<'_>,
+        ) -> std::task::Poll<std::io::Result<()>> {
+            self.flushes += 1;
+            std::task::Poll::Ready(Ok(()))
+        }
+
+        fn poll_shutdown(
+            self: std::pin::Pin<&mut Self>,
+            _cx: &mut std::task::Context<'_>,
+        ) -> std::task::Poll<std::io::Result<()>> {
+            std::task::Poll::Ready(Ok(()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_queued_commands_flush_together_in_order() {
+        let mut queue = OutboundQueue::default();
+        queue.push("send-keys -t %1 -l 'a'");
+        queue.push("send-keys -t %1 -l 'b'");
+        queue.push("send-keys -t %1 Enter");
+
+        let mut writer = CountingWriter::default();
+        queue.flush_to(&mut writer).await.unwrap();
+        assert_eq!(
+            writer.written,
+            b"send-keys -t %1 -l 'a'\nsend-keys -t %1 -l 'b'\nsend-keys -t %1 Enter\n"
+        );
+        assert_eq!(writer.writes, 1);
+        assert_eq!(writer.flushes, 1);
+        assert!(queue.is_empty());
+
+        // Nothing queued, nothing written
+        queue.flush_to(&mut writer).await.unwrap();
+        assert_eq!(writer.flushes, 1);
+    }
+
+    /// Writer that takes `budget` more bytes, then stalls
+    struct StallingWriter {
+        written: Vec<u8>,
+        budget: usize,
+    }
+
+    impl AsyncWrite for StallingWriter {
+        fn poll_write(
+            mut self: std::pin::Pin<&mut Self>,
+            _cx: &mut std::task::Context<'_>,
+            buf: &[u8],
+        ) -> std::task::Poll<std::io::Result<usize>> {
+            if self.budget == 0 {
+                return std::task::Poll::Pending;
+            }
+            let n = buf.len().min(self.budget);
+            self.written.extend_from_slice(&buf[..n]);
+            self.budget -= n;
+            std::task::Poll::Ready(Ok(n))
+        }
+
+        fn poll_flush(
+            self: std::pin::Pin<&mut Self>,
+            _cx: &mut std::task::Context<'_>,
+        ) -> std::task::Poll<std::io::Result<()>> {
+            std::task::Poll::Ready(Ok(()))
+        }
+
+        fn poll_shutdown(
+            self: std::pin::Pin<&mut Self>,
+            _cx: &mut std::task::Context<'_>,
+        ) -> std::task::Poll<std::io::Result<()>> {
+            std::task::Poll::Ready(Ok(()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_flush_does_not_resend() {
+        let mut queue = OutboundQueue::default();
+        queue.push("select-window -t @1");
+        queue.push("send-keys -t %1 Enter");
+
+        // tmux takes the first few bytes, then the flush is cancelled
+        let mut writer = StallingWriter { written: Vec::new(), budget: 10 };
+        let cancelled = tokio::time::timeout(
+            std::time::Duration::from_millis(20),
+            queue.flush_to(&mut writer),
+        )
+        .await;
+        assert!(cancelled.is_err());
+
+        writer.budget = usize::MAX;
+        queue.flush_to(&mut writer).await.unwrap();
+        assert_eq!(writer.written, b"select-window -t @1\nsend-keys -t %1 Enter\n");
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn test_end_for_other_block_is_data() {
+        let mut state = ControlState::default();
+        state.pending_ids.lock().unwrap().push_back(3);
+        let events = feed(&mut state, &["%begin 1 9 1", "%end 1 8 1", "%end 1 9 1"]);
+        match &events[..] {
+            [TmuxEvent::CommandResponse { id: 3, data }] => assert_eq!(data, "%end 1 8 1"),
+            e => panic!("Expected one CommandResponse, got {:?}", e),
+        }
+    }
+
+    #[test]
+    fn test_malformed_notification_is_skipped() {
+        let mut state = ControlState::default();
+        let events = feed(&mut state, &["%window-add @3;kill-server", "%output %1 x"]);
+        match &events[..] {
+            [TmuxEvent::Output { pane_id, .. }] => assert_eq!(pane_id, "%1"),
+            e => panic!("Expected one Output, got {:?}", e),
+        }
     }
-}
 
-impl Drop for TmuxConnection {
-    fn drop(&mut self) {
-        // Try to kill the child process if still running
-        let _ = self.child.start_kill();
+    #[test]
+    fn test_exit_policy_commands() {
+        assert_eq!(ExitPolicy::default(), ExitPolicy::Detach);
+        assert_eq!(ExitPolicy::Detach.command(), "detach-client");
+        assert_eq!(ExitPolicy::KillSession.command(), "kill-session");
     }
 }
@@ -0,0 +1,55 @@
+use std::collections::VecDeque;
+
+use super::client::TmuxClient;
+use super::connection::{ConnectionError, Result};
+use super::protocol::TmuxEvent;
+use super::version::TmuxVersion;
+
+/// Scripted tmux client for tests
+///
+/// Records every command sent and yields queued events in order. Once the
+/// queue is empty, `next_event` reports the connection as closed.
+#[derive(Debug, Default)]
+pub struct MockTmux {
+    /// Commands sent so far, in order
+    pub sent: Vec<String>,
+    /// Events to hand out from `next_event`
+    pub events: VecDeque<TmuxEvent>,
+    /// Version reported by `tmux_version`
+    pub version: Option<TmuxVersion>,
+    command_id: u64,
+}
+
+impl MockTmux {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a mock that will yield the given events
+    pub fn with_events(events: impl IntoIterator<Item = TmuxEvent>) -> Self {
+        Self {
+            events: events.into_iter().collect(),
+            ..Self::default()
+        }
+    }
+}
+
+impl TmuxClient for MockTmux {
+    async fn send_command(&mut self, cmd: &str) -> Result<u64> {
+        self.command_id += 1;
+        self.sent.push(cmd.to_string());
+        Ok(self.command_id)
+    }
+
+    async fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    async fn next_event(&mut self) -> Result<TmuxEvent> {
+        self.events.pop_front().ok_or(ConnectionError::Closed)
+    }
+
+    fn tmux_version(&self) -> Option<TmuxVersion> {
+        self.version
+    }
+}
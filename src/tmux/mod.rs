@@ -1,7 +1,17 @@
+mod client;
 mod connection;
+#[cfg(any(test, feature = "test-util"))]
+mod mock;
 mod protocol;
 mod commands;
+mod target;
+mod version;
 
-pub use connection::TmuxConnection;
-pub use protocol::{TmuxEvent, Notification};
-pub use commands::Commands;
+pub use client::TmuxClient;
+pub use connection::{ConnectionError, ExitPolicy, TmuxConnection};
+#[cfg(any(test, feature = "test-util"))]
+pub use mock::MockTmux;
+pub use protocol::{Notification, ProtocolError, TmuxEvent};
+pub use commands::{Commands, Direction, Placement};
+pub use target::{InvalidTarget, TmuxTarget};
+pub use version::TmuxVersion;
@@ -12,7 +12,8 @@ pub enum ProtocolError {
 #[derive(Debug, Clone)]
 pub enum Notification {
     /// %begin <time> <num> <flags>
-    Begin { id: u64 },
+    /// `from_client` is set when the command was sent by this control client
+    Begin { id: u64, from_client: bool },
     /// %end <time> <num> <flags>
     End { id: u64 },
     /// %error <time> <num> <flags>
@@ -94,7 +95,10 @@ impl Notification {
                 let id = parts.get(2)
                     .and_then(|s| s.parse().ok())
                     .unwrap_or(0);
-                Ok(Notification::Begin { id })
+                let from_client = parts.get(3)
+                    .and_then(|s| s.parse::<u32>().ok())
+                    .is_some_and(|flags| flags & 1 != 0);
+                Ok(Notification::Begin { id, from_client })
             }
             "%end" => {
                 let id = parts.get(2)
@@ -110,35 +114,30 @@ impl Notification {
             }
             "%output" => {
                 // Parse: %output <pane_id> <data...>
-                // Need to handle data that may contain spaces
-                let pane_id = parts.get(1)
-                    .ok_or_else(|| ProtocolError::InvalidFormat("missing pane_id".to_string()))?
-                    .to_string();
-                // Find where the data starts (after "%output " and "<pane_id> ")
-                let prefix_len = "%output ".len() + pane_id.len() + 1; // +1 for space after pane_id
-                let data = if line.len() > prefix_len {
-                    decode_output(&line[prefix_len..])
-                } else {
-                    Vec::new()
-                };
-                Ok(Notification::Output { pane_id, data })
+                // The data is everything after the single space following the
+                // pane id, so spaces and colons inside it are kept as-is
+                let (pane_id, encoded) = split_pane_id(&line["%output ".len().min(line.len())..])?;
+                Ok(Notification::Output { pane_id, data: decode_output(encoded) })
+            }
+            "%extended-output" => {
+                // Parse: %extended-output <pane_id> <age> ... : <data...>
+                // Sent instead of %output when pause-after is set; the data
+                // starts after the " : " that ends the extra fields
+                let (pane_id, rest) =
+                    split_pane_id(&line["%extended-output ".len().min(line.len())..])?;
+                let encoded = rest.split_once(" : ").map_or("", |(_, data)| data);
+                Ok(Notification::Output { pane_id, data: decode_output(encoded) })
             }
             "%window-add" => {
-                let window_id = parts.get(1)
-                    .ok_or_else(|| ProtocolError::InvalidFormat("missing window_id".to_string()))?
-                    .to_string();
+                let window_id = window_id(parts.get(1))?;
                 Ok(Notification::WindowAdd { window_id })
             }
             "%window-close" => {
-                let window_id = parts.get(1)
-                    .ok_or_else(|| ProtocolError::InvalidFormat("missing window_id".to_string()))?
-                    .to_string();
+                let window_id = window_id(parts.get(1))?;
                 Ok(Notification::WindowClose { window_id })
             }
             "%window-renamed" => {
-                let window_id = parts.get(1)
-                    .ok_or_else(|| ProtocolError::InvalidFormat("missing window_id".to_string()))?
-                    .to_string();
+                let window_id = window_id(parts.get(1))?;
                 // Name can contain spaces, so we need everything after "%window-renamed <window_id> "
                 let prefix_len = "%window-renamed ".len() + window_id.len() + 1;
                 let name = if line.len() > prefix_len {
@@ -156,16 +155,12 @@ impl Notification {
                 Ok(Notification::SessionChanged { session_id, name })
             }
             "%layout-change" => {
-                let window_id = parts.get(1)
-                    .ok_or_else(|| ProtocolError::InvalidFormat("missing window_id".to_string()))?
-                    .to_string();
+                let window_id = window_id(parts.get(1))?;
                 let layout = parts.get(2).unwrap_or(&"").to_string();
                 Ok(Notification::LayoutChange { window_id, layout })
             }
             "%pane-mode-changed" => {
-                let pane_id = parts.get(1)
-                    .ok_or_else(|| ProtocolError::InvalidFormat("missing pane_id".to_string()))?
-                    .to_string();
+                let pane_id = pane_id(parts.get(1))?;
                 Ok(Notification::PaneModeChanged { pane_id })
             }
             "%sessions-changed" => {
@@ -178,21 +173,21 @@ impl Notification {
                 Ok(Notification::ClientSessionChanged { client, session_id, name })
             }
             "%window-pane-changed" => {
-                let window_id = parts.get(1).unwrap_or(&"").to_string();
-                let pane_id = parts.get(2).unwrap_or(&"").to_string();
+                let window_id = window_id(parts.get(1))?;
+                let pane_id = pane_id(parts.get(2))?;
                 Ok(Notification::WindowPaneChanged { window_id, pane_id })
             }
             "%session-window-changed" => {
                 let session_id = parts.get(1).unwrap_or(&"").to_string();
-                let window_id = parts.get(2).unwrap_or(&"").to_string();
+                let window_id = window_id(parts.get(2))?;
                 Ok(Notification::SessionWindowChanged { session_id, window_id })
             }
             "%unlinked-window-add" => {
-                let window_id = parts.get(1).unwrap_or(&"").to_string();
+                let window_id = window_id(parts.get(1))?;
                 Ok(Notification::UnlinkedWindowAdd { window_id })
             }
             "%unlinked-window-close" => {
-                let window_id = parts.get(1).unwrap_or(&"").to_string();
+                let window_id = window_id(parts.get(1))?;
                 Ok(Notification::UnlinkedWindowClose { window_id })
             }
             "%client-detached" => {
@@ -215,6 +210,38 @@ impl Notification {
     }
 }
 
+/// Split `<pane_id> <data>` into the pane id and the raw data after the first space
+/// The data may be empty and the separating space may be missing entirely
+fn split_pane_id(rest: &str) -> Result<(String, &str), ProtocolError> {
+    let (id, data) = rest.split_once(' ').unwrap_or((rest, ""));
+    Ok((pane_id(Some(&id))?, data))
+}
+
+/// Check a notification's pane id field is `%<number>`
+fn pane_id(field: Option<&&str>) -> Result<String, ProtocolError> {
+    checked_id(field, '%', "pane_id")
+}
+
+/// Check a notification's window id field is `@<number>`
+fn window_id(field: Option<&&str>) -> Result<String, ProtocolError> {
+    checked_id(field, '@', "window_id")
+}
+
+/// Ids end up as targets in commands sent back to tmux, so anything that isn't
+/// the expected sigil followed by digits is rejected here
+fn checked_id(field: Option<&&str>, sigil: char, what: &str) -> Result<String, ProtocolError> {
+    let id = field.copied().unwrap_or("");
+    if id.is_empty() {
+        return Err(ProtocolError::InvalidFormat(format!("missing {}", what)));
+    }
+    match id.strip_prefix(sigil) {
+        Some(digits) if !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit()) => {
+            Ok(id.to_string())
+        }
+        _ => Err(ProtocolError::InvalidFormat(format!("invalid {} {:?}", what, id))),
+    }
+}
+
 /// Decode tmux escaped output
 /// tmux escapes special characters in %output data
 fn decode_output(encoded: &str) -> Vec<u8> {
@@ -228,21 +255,19 @@ fn decode_output(encoded: &str) -> Vec<u8> {
                 Some('r') => result.push(b'\r'),
                 Some('n') => result.push(b'\n'),
                 Some('t') => result.push(b'\t'),
-                Some('0') => {
-                    // Octal escape: \0xx
-                    let mut octal = String::new();
+                Some(d @ '0'..='7') => {
+                    // Octal escape: up to three digits (\033, \177, \7)
+                    let mut byte = d as u32 - '0' as u32;
                     for _ in 0..2 {
-                        if let Some(&c) = chars.peek() {
-                            if c.is_ascii_digit() && c < '8' {
-                                octal.push(chars.next().unwrap());
-                            } else {
-                                break;
+                        match chars.peek() {
+                            Some(&c @ '0'..='7') => {
+                                byte = byte * 8 + (c as u32 - '0' as u32);
+                                chars.next();
                             }
+                            _ => break,
                         }
                     }
-                    if let Ok(byte) = u8::from_str_radix(&octal, 8) {
-                        result.push(byte);
-                    }
+                    result.push(byte as u8);
                 }
                 Some(c) => {
                     // Unknown escape, keep as-is
@@ -269,7 +294,19 @@ mod tests {
     fn test_parse_begin() {
         let notif = Notification::parse("%begin 1234567890 1 0").unwrap();
         match notif {
-            Notification::Begin { id } => assert_eq!(id, 1),
+            Notification::Begin { id, from_client } => {
+                assert_eq!(id, 1);
+                assert!(!from_client);
+            }
+            _ => panic!("Expected Begin notification"),
+        }
+
+        let notif = Notification::parse("%begin 1234567890 42 1").unwrap();
+        match notif {
+            Notification::Begin { id, from_client } => {
+                assert_eq!(id, 42);
+                assert!(from_client);
+            }
             _ => panic!("Expected Begin notification"),
         }
     }
@@ -286,6 +323,45 @@ mod tests {
         }
     }
 
+    /// Parse a line that must be an %output/%extended-output notification
+    fn output(line: &str) -> (String, Vec<u8>) {
+        match Notification::parse(line).unwrap() {
+            Notification::Output { pane_id, data } => (pane_id, data),
+            n => panic!("Expected Output notification, got {:?}", n),
+        }
+    }
+
+    #[test]
+    fn test_parse_output_keeps_spaces_and_colons() {
+        let (pane_id, data) = output("%output %12 a  b: c:d ");
+        assert_eq!(pane_id, "%12");
+        assert_eq!(data, b"a  b: c:d ");
+
+        // Leading spaces in the data belong to the data
+        assert_eq!(output("%output %1   x").1, b"  x");
+    }
+
+    #[test]
+    fn test_parse_output_escapes_round_trip() {
+        // tmux escapes control characters and backslashes as octal
+        let (_, data) = output("%output %1 \\033[1mhi\\033[0m\\011tab end\\015\\012");
+        assert_eq!(data, b"\x1b[1mhi\x1b[0m\ttab end\r\n");
+    }
+
+    #[test]
+    fn test_parse_output_without_data() {
+        assert_eq!(output("%output %1 "), ("%1".to_string(), Vec::new()));
+        assert_eq!(output("%output %1"), ("%1".to_string(), Vec::new()));
+        assert!(Notification::parse("%output").is_err());
+    }
+
+    #[test]
+    fn test_parse_extended_output() {
+        let (pane_id, data) = output("%extended-output %3 125 : x : y\\015");
+        assert_eq!(pane_id, "%3");
+        assert_eq!(data, b"x : y\r");
+    }
+
     #[test]
     fn test_parse_window_add() {
         let notif = Notification::parse("%window-add @1").unwrap();
@@ -343,4 +419,48 @@ mod tests {
         assert_eq!(decode_output("tab\\there"), b"tab\there");
         assert_eq!(decode_output("back\\\\slash"), b"back\\slash");
     }
+
+    #[test]
+    fn test_decode_octal_escapes() {
+        assert_eq!(decode_output("\\033[0m"), b"\x1b[0m");
+        assert_eq!(decode_output("\\177"), [0x7f]);
+        assert_eq!(decode_output("\\007"), [0x07]);
+        assert_eq!(decode_output("a\\134b"), b"a\\b");
+        // At most three digits are consumed
+        assert_eq!(decode_output("\\0331"), b"\x1b1");
+        // Shorter forms end at the first non-octal digit
+        assert_eq!(decode_output("\\7x"), b"\x07x");
+    }
+
+    #[test]
+    fn test_parse_validates_ids() {
+        assert!(matches!(
+            Notification::parse("%window-pane-changed @3 %7").unwrap(),
+            Notification::WindowPaneChanged { window_id, pane_id } if window_id == "@3" && pane_id == "%7"
+        ));
+        assert!(matches!(
+            Notification::parse("%unlinked-window-close @12").unwrap(),
+            Notification::UnlinkedWindowClose { window_id } if window_id == "@12"
+        ));
+
+        for line in [
+            "%output 1 data",
+            "%output %1;kill-server data",
+            "%extended-output @1 0 : data",
+            "%window-add %1",
+            "%window-close @",
+            "%window-renamed @x name",
+            "%layout-change 3 layout",
+            "%pane-mode-changed %-1",
+            "%window-pane-changed @1 %1'",
+            "%session-window-changed $1 main",
+            "%unlinked-window-add",
+        ] {
+            assert!(
+                matches!(Notification::parse(line), Err(ProtocolError::InvalidFormat(_))),
+                "{:?} should be rejected",
+                line
+            );
+        }
+    }
 }
@@ -0,0 +1,59 @@
+use std::fmt;
+
+use thiserror::Error;
+
+/// A string that isn't a target tmux commands can take unquoted
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("invalid tmux target {0:?}")]
+pub struct InvalidTarget(pub String);
+
+/// A `-t`/`-s` target for a command builder: a window id (`@1`), pane id (`%2`)
+/// or window index (`:3`)
+/// Only these forms are accepted, so nothing put in a command as a target can
+/// be taken for another argument, option or command
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TmuxTarget<'a>(&'a str);
+
+impl<'a> TmuxTarget<'a> {
+    /// Check that `target` is a window id, pane id or window index
+    pub fn new(target: &'a str) -> Result<Self, InvalidTarget> {
+        let digits = match target.chars().next() {
+            Some('@' | '%' | ':') => &target[1..],
+            _ => "",
+        };
+        if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(InvalidTarget(target.to_string()));
+        }
+        Ok(Self(target))
+    }
+
+    pub fn as_str(&self) -> &'a str {
+        self.0
+    }
+}
+
+impl fmt::Display for TmuxTarget<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_valid_targets() {
+        for target in ["@1", "%23", ":0", "@100"] {
+            assert_eq!(TmuxTarget::new(target).unwrap().as_str(), target);
+        }
+        assert_eq!(TmuxTarget::new("%7").unwrap().to_string(), "%7");
+    }
+
+    #[test]
+    fn test_invalid_targets() {
+        for target in ["", "@", "%", "1", "main", "@1 ; kill-server", "%2'", "@-1", ":1.2", "@1\n"] {
+            assert_eq!(TmuxTarget::new(target), Err(InvalidTarget(target.to_string())));
+        }
+    }
+}
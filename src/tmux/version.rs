@@ -0,0 +1,98 @@
+use std::fmt;
+
+/// A tmux server version, as reported by `#{version}` or `tmux -V`
+///
+/// Ordering follows tmux's release order: 3.3 < 3.3a < 3.4.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct TmuxVersion {
+    pub major: u32,
+    pub minor: u32,
+    /// Patch release letter (the "a" in 3.3a)
+    pub letter: Option<char>,
+}
+
+impl TmuxVersion {
+    pub const fn new(major: u32, minor: u32) -> Self {
+        Self { major, minor, letter: None }
+    }
+
+    /// Parse a version string such as "3.4", "3.3a", "next-3.5" or "tmux 3.2a"
+    /// Returns None for builds without a numeric version (e.g. "master")
+    pub fn parse(s: &str) -> Option<Self> {
+        let start = s.find(|c: char| c.is_ascii_digit())?;
+        let (major, rest) = s[start..].split_once('.')?;
+        let digits = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+        let letter = rest[digits..].chars().next().filter(|c| c.is_ascii_lowercase());
+        Some(Self {
+            major: major.parse().ok()?,
+            minor: rest[..digits].parse().ok()?,
+            letter,
+        })
+    }
+
+    /// Whether `send-keys` has -H, to send keys as hex bytes
+    pub fn supports_send_keys_hex(&self) -> bool {
+        *self >= Self::new(3, 0)
+    }
+
+    /// Whether `refresh-client -C` takes the size as WxH
+    /// Older servers only accept W,H
+    pub fn supports_wxh_client_size(&self) -> bool {
+        *self >= Self::new(3, 1)
+    }
+
+    /// Whether `new-window` and `move-window` take -b, to place a window before
+    /// their target
+    pub fn supports_window_before(&self) -> bool {
+        *self >= Self::new(3, 2)
+    }
+}
+
+impl fmt::Display for TmuxVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}", self.major, self.minor)?;
+        if let Some(letter) = self.letter {
+            write!(f, "{}", letter)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_versions() {
+        assert_eq!(TmuxVersion::parse("3.4"), Some(TmuxVersion::new(3, 4)));
+        assert_eq!(
+            TmuxVersion::parse("3.3a"),
+            Some(TmuxVersion { major: 3, minor: 3, letter: Some('a') })
+        );
+        assert_eq!(TmuxVersion::parse("next-3.5"), Some(TmuxVersion::new(3, 5)));
+        assert_eq!(TmuxVersion::parse("tmux 2.9\n"), Some(TmuxVersion::new(2, 9)));
+        assert_eq!(TmuxVersion::parse("master"), None);
+    }
+
+    #[test]
+    fn test_versions_compare_in_release_order() {
+        let v = |s| TmuxVersion::parse(s).unwrap();
+        assert!(v("3.3") < v("3.3a"));
+        assert!(v("3.3a") < v("3.4"));
+        assert!(v("2.9a") < v("3.0"));
+        assert!(v("3.10") > v("3.9"));
+        assert_eq!(v("3.3a").to_string(), "3.3a");
+    }
+
+    #[test]
+    fn test_old_version_uses_legacy_client_size() {
+        assert!(!TmuxVersion::new(2, 9).supports_wxh_client_size());
+        assert!(TmuxVersion::new(3, 1).supports_wxh_client_size());
+    }
+
+    #[test]
+    fn test_send_keys_hex_needs_3_0() {
+        assert!(!TmuxVersion::new(2, 9).supports_send_keys_hex());
+        assert!(TmuxVersion::new(3, 0).supports_send_keys_hex());
+    }
+}
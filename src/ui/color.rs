@@ -0,0 +1,176 @@
+use ratatui::style::Color;
+
+use crate::terminal::ansi_to_color;
+
+/// How many colors the host terminal can show
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorDepth {
+    /// 24-bit RGB
+    TrueColor,
+    /// The xterm 256-color palette
+    Ansi256,
+    /// Only the 16 base colors
+    Ansi16,
+}
+
+/// RGB values xterm uses for the 16 base colors, to pick the nearest one
+const ANSI16_RGB: [(u8, u8, u8); 16] = [
+    (0, 0, 0),
+    (205, 0, 0),
+    (0, 205, 0),
+    (205, 205, 0),
+    (0, 0, 238),
+    (205, 0, 205),
+    (0, 205, 205),
+    (229, 229, 229),
+    (127, 127, 127),
+    (255, 0, 0),
+    (0, 255, 0),
+    (255, 255, 0),
+    (92, 92, 255),
+    (255, 0, 255),
+    (0, 255, 255),
+    (255, 255, 255),
+];
+
+impl ColorDepth {
+    /// Detect the host terminal's color support from `$COLORTERM` and `$TERM`
+    pub fn detect() -> Self {
+        let colorterm = std::env::var("COLORTERM").ok();
+        let term = std::env::var("TERM").ok();
+        Self::from_env(colorterm.as_deref(), term.as_deref())
+    }
+
+    /// Work out color support from the values of `$COLORTERM` and `$TERM`
+    pub fn from_env(colorterm: Option<&str>, term: Option<&str>) -> Self {
+        if matches!(colorterm, Some("truecolor" | "24bit")) {
+            return ColorDepth::TrueColor;
+        }
+        match term {
+            Some(term) if term.ends_with("-direct") => ColorDepth::TrueColor,
+            Some(term) if term.contains("256color") => ColorDepth::Ansi256,
+            _ => ColorDepth::Ansi16,
+        }
+    }
+
+    /// Convert an RGB color to the nearest one this terminal can show
+    /// Other colors pass through, as the terminal resolves them itself
+    pub fn downsample(self, color: Color) -> Color {
+        match (self, color) {
+            (ColorDepth::Ansi256, Color::Rgb(r, g, b)) => Color::Indexed(rgb_to_ansi256(r, g, b)),
+            (ColorDepth::Ansi16, Color::Rgb(r, g, b)) => ansi_to_color(rgb_to_ansi16(r, g, b) as u16),
+            (ColorDepth::Ansi16, Color::Indexed(i)) if i >= 16 => match ansi_to_color(i as u16) {
+                Color::Rgb(r, g, b) => ansi_to_color(rgb_to_ansi16(r, g, b) as u16),
+                named => named,
+            },
+            _ => color,
+        }
+    }
+}
+
+/// Channel values of the xterm 6x6x6 color cube (indices 16-231)
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+/// Nearest xterm 256-color index to an RGB color, from the color cube or the gray ramp
+/// The base 16 are skipped since their RGB depends on the user's terminal theme
+pub fn rgb_to_ansi256(r: u8, g: u8, b: u8) -> u8 {
+    // Each channel snaps to its nearest cube level on its own
+    let (ri, gi, bi) = (cube_index(r), cube_index(g), cube_index(b));
+    let cube = (CUBE_LEVELS[ri], CUBE_LEVELS[gi], CUBE_LEVELS[bi]);
+    // The nearest gray (8, 18, ..., 238) is one of the two either side of the average
+    let gray = |step: u8| {
+        let level = 8 + 10 * step;
+        distance((r, g, b), (level, level, level))
+    };
+    let below = ((r as u32 + g as u32 + b as u32).saturating_sub(24) / 30).min(23) as u8;
+    let step = if below < 23 && gray(below + 1) < gray(below) { below + 1 } else { below };
+    // Ties go to the lower index, as an exhaustive search would find first
+    if gray(step) < distance((r, g, b), cube) {
+        232 + step
+    } else {
+        16 + 36 * ri as u8 + 6 * gi as u8 + bi as u8
+    }
+}
+
+/// Index of the cube level nearest a channel value
+fn cube_index(v: u8) -> usize {
+    match v {
+        0..=47 => 0,
+        48..=115 => 1,
+        _ => (v as usize - 36) / 40,
+    }
+}
+
+/// Nearest of the 16 base ANSI colors to an RGB color, by xterm's default values
+pub fn rgb_to_ansi16(r: u8, g: u8, b: u8) -> u8 {
+    (0..16u8)
+        .min_by_key(|&i| distance((r, g, b), ANSI16_RGB[i as usize]))
+        .unwrap_or(0)
+}
+
+/// Squared distance between two colors
+fn distance(a: (u8, u8, u8), b: (u8, u8, u8)) -> u32 {
+    let d = |x: u8, y: u8| (x as i32 - y as i32).pow(2) as u32;
+    d(a.0, b.0) + d(a.1, b.1) + d(a.2, b.2)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(ColorDepth::from_env(Some("truecolor"), Some("xterm")), ColorDepth::TrueColor);
+        assert_eq!(ColorDepth::from_env(None, Some("xterm-direct")), ColorDepth::TrueColor);
+        assert_eq!(ColorDepth::from_env(None, Some("tmux-256color")), ColorDepth::Ansi256);
+        assert_eq!(ColorDepth::from_env(None, Some("xterm")), ColorDepth::Ansi16);
+        assert_eq!(ColorDepth::from_env(None, None), ColorDepth::Ansi16);
+    }
+
+    #[test]
+    fn test_rgb_to_ansi256() {
+        assert_eq!(rgb_to_ansi256(255, 0, 0), 196);
+        assert_eq!(rgb_to_ansi256(0, 0, 255), 21);
+        assert_eq!(rgb_to_ansi256(95, 135, 175), 67);
+        // Grays land on the gray ramp
+        assert_eq!(rgb_to_ansi256(128, 128, 128), 244);
+        assert_eq!(rgb_to_ansi256(250, 100, 10), 202);
+    }
+
+    #[test]
+    fn test_rgb_to_ansi256_matches_nearest_search() {
+        let nearest = |rgb: (u8, u8, u8)| {
+            (16..=255u8)
+                .min_by_key(|&i| match ansi_to_color(i as u16) {
+                    Color::Rgb(r, g, b) => distance(rgb, (r, g, b)),
+                    _ => u32::MAX,
+                })
+                .unwrap()
+        };
+        for r in (0..=255u8).step_by(15) {
+            for g in (0..=255u8).step_by(15) {
+                for b in (0..=255u8).step_by(15) {
+                    assert_eq!(rgb_to_ansi256(r, g, b), nearest((r, g, b)), "({}, {}, {})", r, g, b);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_rgb_to_ansi16() {
+        assert_eq!(rgb_to_ansi16(0, 0, 0), 0);
+        assert_eq!(rgb_to_ansi16(200, 10, 10), 1);
+        assert_eq!(rgb_to_ansi16(250, 250, 250), 15);
+        assert_eq!(rgb_to_ansi16(100, 100, 255), 12);
+    }
+
+    #[test]
+    fn test_downsample() {
+        let orange = Color::Rgb(250, 100, 10);
+        assert_eq!(ColorDepth::TrueColor.downsample(orange), orange);
+        assert_eq!(ColorDepth::Ansi256.downsample(orange), Color::Indexed(202));
+        assert_eq!(ColorDepth::Ansi16.downsample(Color::Rgb(255, 0, 0)), Color::LightRed);
+        assert_eq!(ColorDepth::Ansi16.downsample(Color::Indexed(196)), Color::LightRed);
+        assert_eq!(ColorDepth::Ansi16.downsample(Color::Reset), Color::Reset);
+    }
+}
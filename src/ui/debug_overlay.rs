@@ -0,0 +1,78 @@
+use ratatui::{
+    buffer::Buffer,
+    layout::Rect,
+    style::Style,
+    widgets::Widget,
+};
+
+use super::Theme;
+
+/// Labelled values drawn in the top-right corner of the viewport, for
+/// looking into what helmux is doing. Each row covers only its own text, so
+/// the pane stays visible beside the shorter ones
+pub struct DebugOverlay<'a> {
+    rows: &'a [(&'a str, String)],
+    theme: &'a Theme,
+}
+
+impl<'a> DebugOverlay<'a> {
+    pub fn new(rows: &'a [(&'a str, String)], theme: &'a Theme) -> Self {
+        Self { rows, theme }
+    }
+
+    /// The corner of `viewport` the overlay covers: wide enough for the
+    /// longest row, with one line per row
+    pub fn area(&self, viewport: Rect) -> Rect {
+        let width = self
+            .rows
+            .iter()
+            .map(|(label, value)| Self::line(label, value).chars().count())
+            .max()
+            .unwrap_or(0)
+            .min(viewport.width as usize) as u16;
+        let height = (self.rows.len() as u16).min(viewport.height);
+        Rect::new(viewport.x + viewport.width - width, viewport.y, width, height)
+    }
+
+    fn line(label: &str, value: &str) -> String {
+        format!(" {}: {} ", label, value)
+    }
+}
+
+impl Widget for DebugOverlay<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let style = Style::default().fg(self.theme.debug_fg).bg(self.theme.debug_bg);
+        for ((label, value), y) in self.rows.iter().zip(area.top()..area.bottom()) {
+            let line = Self::line(label, value);
+            let width = (line.chars().count() as u16).min(area.width);
+            buf.set_stringn(area.right() - width, y, &line, width as usize, style);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ratatui::style::Color;
+
+    #[test]
+    fn test_renders_rows_in_top_right_corner() {
+        let rows = [("bytes", "1234".to_string()), ("frames", "7".to_string())];
+        let theme = Theme::default();
+        let overlay = DebugOverlay::new(&rows, &theme);
+        let viewport = Rect::new(0, 0, 30, 5);
+        let area = overlay.area(viewport);
+        assert_eq!(area, Rect::new(17, 0, 13, 2));
+
+        let mut buf = Buffer::empty(viewport);
+        overlay.render(area, &mut buf);
+        let line = |y| (17..30).map(|x| buf[(x, y)].symbol()).collect::<String>();
+        assert_eq!(line(0), " bytes: 1234 ");
+        assert_eq!(line(1), "   frames: 7 ");
+        assert_eq!(buf[(19, 1)].bg, Theme::default().debug_bg);
+        // Nothing beside a row's text is touched
+        assert_eq!(buf[(18, 1)].bg, Color::Reset);
+        assert_eq!(buf[(0, 0)].symbol(), " ");
+        assert_eq!(buf[(16, 0)].bg, Color::Reset);
+    }
+}
@@ -0,0 +1,79 @@
+use std::time::{Duration, Instant};
+
+/// Limits how often the screen is redrawn. A frame asked for too soon after
+/// the last one is held back and drawn once the interval is up, so a burst
+/// of output costs one draw per interval at most
+#[derive(Debug, Clone)]
+pub struct FramePacer {
+    /// Shortest time between two frames
+    interval: Duration,
+    /// When the last frame was drawn
+    last_frame: Option<Instant>,
+    /// A frame was held back and still needs drawing
+    pending: bool,
+}
+
+impl FramePacer {
+    /// Pace frames to at most `max_fps` a second (0 means no limit)
+    pub fn new(max_fps: u32) -> Self {
+        let interval = if max_fps == 0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs(1) / max_fps
+        };
+        Self { interval, last_frame: None, pending: false }
+    }
+
+    /// Ask to draw a frame at `now`. Returns whether to draw it now; if not,
+    /// it's remembered and `deadline` says when to try again
+    pub fn ready(&mut self, now: Instant) -> bool {
+        let due = self
+            .last_frame
+            .is_none_or(|last| now.saturating_duration_since(last) >= self.interval);
+        if due {
+            self.last_frame = Some(now);
+        }
+        self.pending = !due;
+        due
+    }
+
+    /// When a held-back frame can be drawn, if there is one
+    pub fn deadline(&self) -> Option<Instant> {
+        match self.last_frame {
+            Some(last) if self.pending => Some(last + self.interval),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_one_frame_per_interval() {
+        let mut pacer = FramePacer::new(50);
+        let start = Instant::now();
+        assert!(pacer.ready(start));
+        assert_eq!(pacer.deadline(), None);
+
+        // Everything within the next 20ms is held back to the same deadline
+        for ms in [1, 5, 19] {
+            assert!(!pacer.ready(start + Duration::from_millis(ms)));
+            assert_eq!(pacer.deadline(), Some(start + Duration::from_millis(20)));
+        }
+
+        assert!(pacer.ready(start + Duration::from_millis(20)));
+        assert_eq!(pacer.deadline(), None);
+        assert!(!pacer.ready(start + Duration::from_millis(25)));
+    }
+
+    #[test]
+    fn test_zero_means_unlimited() {
+        let mut pacer = FramePacer::new(0);
+        let now = Instant::now();
+        assert!(pacer.ready(now));
+        assert!(pacer.ready(now));
+        assert_eq!(pacer.deadline(), None);
+    }
+}
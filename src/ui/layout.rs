@@ -138,6 +138,35 @@ impl Layout {
         self.sidebar_width = width;
     }
 
+    /// Whether the sidebar is left of the viewport
+    pub fn sidebar_left(&self) -> bool {
+        self.sidebar_left
+    }
+
+    /// Move the sidebar to the other side of the viewport
+    pub fn flip_sidebar(&mut self) {
+        self.sidebar_left = !self.sidebar_left;
+    }
+
+    /// Whether the sidebar is narrowed to tab numbers
+    pub fn is_sidebar_collapsed(&self) -> bool {
+        self.sidebar_width == COLLAPSED_SIDEBAR_WIDTH
+    }
+
+    /// Whether the sidebar is hidden, giving the viewport the whole width
+    pub fn is_sidebar_hidden(&self) -> bool {
+        self.sidebar_width == 0
+    }
+
+    /// Step the sidebar from full to collapsed to hidden, and back to full
+    pub fn cycle_sidebar(&mut self) {
+        self.sidebar_width = match self.sidebar_width {
+            0 => DEFAULT_SIDEBAR_WIDTH,
+            COLLAPSED_SIDEBAR_WIDTH => 0,
+            _ => COLLAPSED_SIDEBAR_WIDTH,
+        };
+    }
+
     /// Toggle between collapsed and expanded sidebar
     pub fn toggle_sidebar(&mut self) {
         if self.sidebar_width == COLLAPSED_SIDEBAR_WIDTH {
@@ -191,6 +220,56 @@ mod tests {
         assert_eq!(hit, HitRegion::Viewport { row: 20, col: 50 - DEFAULT_SIDEBAR_WIDTH });
     }
 
+    #[test]
+    fn test_sidebar_on_right() {
+        let area = Rect::new(0, 0, 100, 40);
+        let mut layout = Layout::new(area);
+        layout.flip_sidebar();
+        assert!(!layout.sidebar_left());
+
+        assert_eq!(layout.sidebar_area(), Rect::new(80, 0, 20, 40));
+        assert_eq!(layout.viewport_area(), Rect::new(0, 0, 80, 40));
+        // Same viewport size either side, so tmux needn't change size
+        assert_eq!(layout.tmux_size(), (80, 40));
+
+        assert_eq!(layout.hit_test(5, 10), HitRegion::Viewport { row: 10, col: 5 });
+        assert_eq!(layout.hit_test(79, 0), HitRegion::Viewport { row: 0, col: 79 });
+        assert_eq!(layout.hit_test(80, 3), HitRegion::Sidebar { row: 3 });
+        assert_eq!(layout.hit_test(99, 39), HitRegion::Sidebar { row: 39 });
+
+        layout.flip_sidebar();
+        assert_eq!(layout.hit_test(5, 10), HitRegion::Sidebar { row: 10 });
+    }
+
+    #[test]
+    fn test_cycle_sidebar() {
+        let mut layout = Layout::new(Rect::new(0, 0, 100, 40));
+        layout.cycle_sidebar();
+        assert!(layout.is_sidebar_collapsed());
+        assert_eq!(layout.tmux_size(), (100 - COLLAPSED_SIDEBAR_WIDTH, 40));
+        layout.cycle_sidebar();
+        assert!(layout.is_sidebar_hidden());
+        assert_eq!(layout.tmux_size(), (100, 40));
+        layout.cycle_sidebar();
+        assert_eq!(layout.sidebar_width(), DEFAULT_SIDEBAR_WIDTH);
+
+        // Collapsing a hidden sidebar leaves it hidden
+        layout.set_sidebar_width(0);
+        layout.toggle_sidebar();
+        assert!(layout.is_sidebar_hidden());
+    }
+
+    #[test]
+    fn test_hidden_sidebar_is_never_hit() {
+        for left in [true, false] {
+            let layout = Layout::new(Rect::new(0, 0, 100, 40)).with_sidebar_width(0).with_sidebar_left(left);
+            for (x, y) in [(0, 0), (5, 10), (99, 39), (80, 0)] {
+                assert_eq!(layout.hit_test(x, y), HitRegion::Viewport { row: y, col: x });
+            }
+            assert_eq!(layout.hit_test(100, 0), HitRegion::None);
+        }
+    }
+
     #[test]
     fn test_toggle_sidebar() {
         let area = Rect::new(0, 0, 100, 40);
@@ -1,9 +1,21 @@
+mod color;
+mod debug_overlay;
+mod frame_pacer;
 mod layout;
 mod rename_overlay;
 mod sidebar;
+mod splash;
+mod status_line;
+mod theme;
 mod viewport;
 
-pub use layout::{HitRegion, Layout, COLLAPSED_SIDEBAR_WIDTH, DEFAULT_SIDEBAR_WIDTH};
+pub use color::{rgb_to_ansi16, rgb_to_ansi256, ColorDepth};
+pub use debug_overlay::DebugOverlay;
+pub use frame_pacer::FramePacer;
+pub use layout::{HitRegion, Layout};
 pub use rename_overlay::RenameOverlay;
 pub use sidebar::{is_new_tab_button, row_to_tab_index, Sidebar, SidebarMode, TabInfo};
+pub use splash::Splash;
+pub use status_line::StatusLine;
+pub use theme::Theme;
 pub use viewport::Viewport;
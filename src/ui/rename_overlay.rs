@@ -5,15 +5,29 @@ use ratatui::{
     widgets::{Block, Borders, Clear, Paragraph, Widget},
 };
 
-/// A modal overlay for renaming tabs
+use super::Theme;
+
+/// A modal overlay for renaming tabs (and other one-line prompts)
 pub struct RenameOverlay<'a> {
     /// Current input text
     text: &'a str,
+    /// Box title
+    title: &'a str,
+    theme: &'a Theme,
 }
 
 impl<'a> RenameOverlay<'a> {
-    pub fn new(text: &'a str) -> Self {
-        Self { text }
+    pub fn new(text: &'a str, theme: &'a Theme) -> Self {
+        Self {
+            text,
+            title: " Rename Tab ",
+            theme,
+        }
+    }
+
+    pub fn title(mut self, title: &'a str) -> Self {
+        self.title = title;
+        self
     }
 
     /// Calculate the centered area for the overlay
@@ -33,10 +47,10 @@ impl Widget for RenameOverlay<'_> {
 
         // Draw the box
         let block = Block::default()
-            .title(" Rename Tab ")
+            .title(self.title)
             .title_alignment(Alignment::Center)
             .borders(Borders::ALL)
-            .border_style(Style::default().fg(Color::Cyan));
+            .border_style(Style::default().fg(self.theme.overlay_border_fg));
 
         let inner = block.inner(area);
         block.render(area, buf);
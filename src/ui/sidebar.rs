@@ -1,3 +1,5 @@
+use std::time::Instant;
+
 use ratatui::{
     buffer::Buffer,
     layout::Rect,
@@ -5,6 +7,8 @@ use ratatui::{
     widgets::Widget,
 };
 
+use super::Theme;
+
 /// Information about a single tab
 #[derive(Debug, Clone)]
 pub struct TabInfo {
@@ -16,10 +20,35 @@ pub struct TabInfo {
     pub active: bool,
     /// Whether there's unseen activity
     pub activity: bool,
+    /// Whether a bell rang in this tab
+    pub bell: bool,
+    /// Whether this tab is marked as the swap/join target
+    pub marked: bool,
+    /// Whether tmux names the tab after its running command
+    pub auto_rename: bool,
+    /// Whether tmux's synchronize-panes is on for the tab
+    pub synchronized: bool,
+    /// Whether the command in the tab's active pane has exited
+    pub dead: bool,
+    /// Foreground command in the tab's active pane (empty if unknown)
+    pub command: String,
+    /// Working directory of the tab's active pane (empty if unknown)
+    pub path: String,
+    /// When output last arrived in the tab
+    pub last_activity: Option<Instant>,
     /// Tab index (1-based for display)
     pub index: usize,
 }
 
+/// Glyph shown next to the marked tab
+const MARK_GLYPH: &str = "◆";
+
+/// Annotation on tabs with tmux's synchronize-panes on
+const SYNC_LABEL: &str = "[sync]";
+
+/// Annotation on tabs whose command has exited
+const DEAD_LABEL: &str = "[exited]";
+
 /// Mode indicator for the sidebar
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum SidebarMode {
@@ -29,21 +58,42 @@ pub enum SidebarMode {
     Prefix,
     /// Renaming a tab
     Rename,
+    /// Naming a new tab
+    NewTab,
+    /// Typing a find-window query
+    Find,
+    /// Typing a scrollback search query
+    Search,
+    /// Browsing scrollback
+    Scroll,
+    /// Waiting for y/n on a destructive action
+    Confirm,
 }
 
 /// Widget that renders the sidebar with tab list
 pub struct Sidebar<'a> {
     tabs: &'a [TabInfo],
+    theme: &'a Theme,
     collapsed: bool,
     mode: SidebarMode,
+    /// Show each tab's working directory beside its name
+    show_paths: bool,
+    /// Typed input is going to every tab
+    broadcast: bool,
+    /// Placed right of the viewport, so the border goes on the left edge
+    on_right: bool,
 }
 
 impl<'a> Sidebar<'a> {
-    pub fn new(tabs: &'a [TabInfo]) -> Self {
+    pub fn new(tabs: &'a [TabInfo], theme: &'a Theme) -> Self {
         Self {
             tabs,
+            theme,
             collapsed: false,
             mode: SidebarMode::Normal,
+            show_paths: false,
+            broadcast: false,
+            on_right: false,
         }
     }
 
@@ -56,6 +106,23 @@ impl<'a> Sidebar<'a> {
         self.mode = mode;
         self
     }
+
+    pub fn show_paths(mut self, show: bool) -> Self {
+        self.show_paths = show;
+        self
+    }
+
+    /// Draw for a sidebar right of the viewport, with the border on its left
+    pub fn on_right(mut self, on_right: bool) -> Self {
+        self.on_right = on_right;
+        self
+    }
+
+    /// Flag that input is broadcast to all tabs (shown in place of the normal-mode indicator)
+    pub fn broadcast(mut self, broadcast: bool) -> Self {
+        self.broadcast = broadcast;
+        self
+    }
 }
 
 impl Widget for Sidebar<'_> {
@@ -65,16 +132,16 @@ impl Widget for Sidebar<'_> {
         }
 
         // Draw background
-        let bg_style = Style::default().bg(Color::DarkGray);
+        let bg_style = Style::default().bg(self.theme.sidebar_bg);
         for y in area.y..area.y + area.height {
             for x in area.x..area.x + area.width {
                 buf.set_string(x, y, " ", bg_style);
             }
         }
 
-        // Draw border on the right edge
-        let border_style = Style::default().fg(Color::Gray).bg(Color::DarkGray);
-        let border_x = area.x + area.width - 1;
+        // Draw border on the edge next to the viewport
+        let border_style = Style::default().fg(self.theme.border_fg).bg(self.theme.sidebar_bg);
+        let border_x = self.border_x(area);
         for y in area.y..area.y + area.height {
             buf.set_string(border_x, y, "│", border_style);
         }
@@ -105,9 +172,42 @@ impl Widget for Sidebar<'_> {
 }
 
 impl Sidebar<'_> {
+    /// Column of the border, on the edge facing the viewport
+    fn border_x(&self, area: Rect) -> u16 {
+        if self.on_right {
+            area.x
+        } else {
+            area.x + area.width - 1
+        }
+    }
+
+    /// First column of content, just past a left-hand border
+    fn content_x(&self, area: Rect) -> u16 {
+        if self.on_right {
+            area.x + 1
+        } else {
+            area.x
+        }
+    }
+
     /// Render mode indicator at top of sidebar, returns the y position where tabs should start
     fn render_mode_indicator(&self, area: Rect, buf: &mut Buffer, content_width: u16) -> u16 {
         match self.mode {
+            SidebarMode::Normal if self.broadcast => {
+                let style = Style::default()
+                    .fg(self.theme.broadcast_fg)
+                    .bg(self.theme.broadcast_bg)
+                    .add_modifier(Modifier::BOLD);
+                let text = if content_width >= 10 {
+                    "BROADCAST"
+                } else {
+                    "ALL"
+                };
+                let fill = " ".repeat(content_width as usize);
+                buf.set_string(self.content_x(area), area.y, &fill, style);
+                buf.set_string(self.content_x(area), area.y, text, style);
+                area.y + 1
+            }
             SidebarMode::Normal => area.y, // No indicator in normal mode
             SidebarMode::Prefix => {
                 let style = Style::default()
@@ -120,8 +220,8 @@ impl Sidebar<'_> {
                     "^B"
                 };
                 let fill = " ".repeat(content_width as usize);
-                buf.set_string(area.x, area.y, &fill, style);
-                buf.set_string(area.x, area.y, text, style);
+                buf.set_string(self.content_x(area), area.y, &fill, style);
+                buf.set_string(self.content_x(area), area.y, text, style);
                 area.y + 1
             }
             SidebarMode::Rename => {
@@ -135,8 +235,83 @@ impl Sidebar<'_> {
                     "REN"
                 };
                 let fill = " ".repeat(content_width as usize);
-                buf.set_string(area.x, area.y, &fill, style);
-                buf.set_string(area.x, area.y, text, style);
+                buf.set_string(self.content_x(area), area.y, &fill, style);
+                buf.set_string(self.content_x(area), area.y, text, style);
+                area.y + 1
+            }
+            SidebarMode::NewTab => {
+                let style = Style::default()
+                    .fg(Color::Black)
+                    .bg(self.theme.new_tab_indicator_bg)
+                    .add_modifier(Modifier::BOLD);
+                let text = if content_width >= 10 {
+                    "NEW TAB"
+                } else {
+                    "NEW"
+                };
+                let fill = " ".repeat(content_width as usize);
+                buf.set_string(self.content_x(area), area.y, &fill, style);
+                buf.set_string(self.content_x(area), area.y, text, style);
+                area.y + 1
+            }
+            SidebarMode::Find => {
+                let style = Style::default()
+                    .fg(Color::Black)
+                    .bg(Color::Green)
+                    .add_modifier(Modifier::BOLD);
+                let text = if content_width >= 10 {
+                    "FIND"
+                } else {
+                    "FND"
+                };
+                let fill = " ".repeat(content_width as usize);
+                buf.set_string(self.content_x(area), area.y, &fill, style);
+                buf.set_string(self.content_x(area), area.y, text, style);
+                area.y + 1
+            }
+            SidebarMode::Search => {
+                let style = Style::default()
+                    .fg(Color::Black)
+                    .bg(Color::Green)
+                    .add_modifier(Modifier::BOLD);
+                let text = if content_width >= 10 {
+                    "SEARCH"
+                } else {
+                    "/"
+                };
+                let fill = " ".repeat(content_width as usize);
+                buf.set_string(self.content_x(area), area.y, &fill, style);
+                buf.set_string(self.content_x(area), area.y, text, style);
+                area.y + 1
+            }
+            SidebarMode::Scroll => {
+                let style = Style::default()
+                    .fg(Color::Black)
+                    .bg(Color::Magenta)
+                    .add_modifier(Modifier::BOLD);
+                let text = if content_width >= 10 {
+                    "SCROLL"
+                } else {
+                    "SCR"
+                };
+                let fill = " ".repeat(content_width as usize);
+                buf.set_string(self.content_x(area), area.y, &fill, style);
+                buf.set_string(self.content_x(area), area.y, text, style);
+                area.y + 1
+            }
+            SidebarMode::Confirm => {
+                let style = Style::default()
+                    .fg(Color::White)
+                    .bg(Color::Red)
+                    .add_modifier(Modifier::BOLD);
+                let text = if content_width >= 10 {
+                    "CONFIRM"
+                } else {
+                    "Y/N"
+                };
+                let fill = " ".repeat(content_width as usize);
+                buf.set_string(self.content_x(area), area.y, &fill, style);
+                buf.set_string(self.content_x(area), area.y, text, style);
                 area.y + 1
             }
         }
@@ -153,6 +328,10 @@ impl Sidebar<'_> {
             let y = area.y + i as u16;
             let indicator = if tab.active {
                 "●"
+            } else if tab.marked {
+                MARK_GLYPH
+            } else if tab.bell {
+                "!"
             } else if tab.activity {
                 "*"
             } else {
@@ -161,28 +340,30 @@ impl Sidebar<'_> {
 
             let style = if tab.active {
                 Style::default()
-                    .fg(Color::White)
-                    .bg(Color::Blue)
+                    .fg(self.theme.active_tab_fg)
+                    .bg(self.theme.active_tab_bg)
                     .add_modifier(Modifier::BOLD)
-            } else if tab.activity {
+            } else if tab.activity || tab.bell {
                 Style::default()
-                    .fg(Color::Yellow)
-                    .bg(Color::DarkGray)
+                    .fg(self.theme.activity_fg)
+                    .bg(self.theme.sidebar_bg)
             } else {
                 Style::default()
-                    .fg(Color::White)
-                    .bg(Color::DarkGray)
+                    .fg(self.theme.sidebar_fg)
+                    .bg(self.theme.sidebar_bg)
             };
 
             let text = format!("{}{}", indicator, tab.index);
             let text = truncate_to_width(&text, content_width as usize);
-            buf.set_string(area.x, y, text, style);
+            self.render_tab_row(area, y, &text, style, tab.active, buf);
         }
     }
 
     fn render_expanded(&self, area: Rect, buf: &mut Buffer, content_width: u16) {
         // Expanded mode: show full tab names
-        // Format: "● 1: tab-name" or "  2: other-tab"
+        // Format: "● 1: tab-name" or "  2: other-tab", followed by the running
+        // command and optionally the path
+        let home = if self.show_paths { dirs::home_dir() } else { None };
         for (i, tab) in self.tabs.iter().enumerate() {
             if i as u16 >= area.height.saturating_sub(1) {
                 break;
@@ -191,6 +372,8 @@ impl Sidebar<'_> {
             let y = area.y + i as u16;
             let indicator = if tab.active {
                 "●"
+            } else if tab.bell {
+                "!"
             } else if tab.activity {
                 "*"
             } else {
@@ -199,27 +382,79 @@ impl Sidebar<'_> {
 
             let style = if tab.active {
                 Style::default()
-                    .fg(Color::White)
-                    .bg(Color::Blue)
+                    .fg(self.theme.active_tab_fg)
+                    .bg(self.theme.active_tab_bg)
                     .add_modifier(Modifier::BOLD)
-            } else if tab.activity {
+            } else if tab.activity || tab.bell {
                 Style::default()
-                    .fg(Color::Yellow)
-                    .bg(Color::DarkGray)
+                    .fg(self.theme.activity_fg)
+                    .bg(self.theme.sidebar_bg)
             } else {
                 Style::default()
-                    .fg(Color::White)
-                    .bg(Color::DarkGray)
+                    .fg(self.theme.sidebar_fg)
+                    .bg(self.theme.sidebar_bg)
             };
 
-            // Format: "● 1: name"
-            let text = format!("{} {}: {}", indicator, tab.index, tab.name);
+            // Names tmux picks automatically are italic, names set by hand upright
+            let style = if tab.auto_rename {
+                style.add_modifier(Modifier::ITALIC)
+            } else {
+                style
+            };
+
+            // Format: "● 1: name", with the mark glyph in place of the space
+            let mark = if tab.marked { MARK_GLYPH } else { " " };
+            let text = format!("{}{}{}: {}", indicator, mark, tab.index, tab.name);
             let text = truncate_to_width(&text, content_width as usize);
+            self.render_tab_row(area, y, &text, style, tab.active, buf);
+
+            let mut width = text.chars().count();
+            if tab.dead {
+                width = self.render_annotation(area, y, width, DEAD_LABEL, style, buf);
+            }
+            if tab.synchronized {
+                width = self.render_annotation(area, y, width, SYNC_LABEL, style, buf);
+            }
+            // How long the tab has gone without output, once it's been a minute
+            if let Some(idle) = tab.last_activity.and_then(idle_label) {
+                width = self.render_annotation(area, y, width, &idle, style, buf);
+            }
+            if !tab.command.is_empty() && tab.command != tab.name {
+                width = self.render_annotation(area, y, width, &tab.command, style, buf);
+            }
+            if self.show_paths && !tab.path.is_empty() {
+                let path = short_path(&tab.path, home.as_deref());
+                self.render_annotation(area, y, width, &path, style, buf);
+            }
+        }
+    }
+
+    /// Draw dimmed extra text after a tab's label, truncated to the space left
+    /// Returns the row's used width afterwards
+    fn render_annotation(&self, area: Rect, y: u16, label_width: usize, text: &str, style: Style, buf: &mut Buffer) -> usize {
+        let content_width = area.width.saturating_sub(1) as usize;
+        let start = label_width + 1;
+        if start >= content_width {
+            return label_width;
+        }
+        let text = truncate_to_width(text, content_width - start);
+        buf.set_string(self.content_x(area) + start as u16, y, &text, style.add_modifier(Modifier::DIM));
+        start + text.chars().count()
+    }
 
-            // Fill the entire row with background color first
-            let fill = " ".repeat(content_width as usize);
-            buf.set_string(area.x, y, &fill, style);
-            buf.set_string(area.x, y, text, style);
+    /// Draw a single tab row, filling it with the row style first
+    /// The active row's background also extends under the border column so the
+    /// highlight spans the full sidebar width, while the border glyph stays visible
+    fn render_tab_row(&self, area: Rect, y: u16, text: &str, style: Style, active: bool, buf: &mut Buffer) {
+        let content_width = area.width.saturating_sub(1);
+        let fill = " ".repeat(content_width as usize);
+        buf.set_string(self.content_x(area), y, &fill, style);
+        buf.set_string(self.content_x(area), y, text, style);
+
+        if active {
+            if let (Some(bg), Some(cell)) = (style.bg, buf.cell_mut((self.border_x(area), y))) {
+                cell.set_bg(bg);
+            }
         }
     }
 
@@ -230,8 +465,8 @@ impl Sidebar<'_> {
 
         let y = area.y + area.height - 1;
         let style = Style::default()
-            .fg(Color::Green)
-            .bg(Color::DarkGray);
+            .fg(self.theme.new_tab_button_fg)
+            .bg(self.theme.sidebar_bg);
 
         let text = if content_width >= 9 {
             "[+] New"
@@ -241,22 +476,45 @@ impl Sidebar<'_> {
 
         // Fill row first
         let fill = " ".repeat(content_width as usize);
-        buf.set_string(area.x, y, &fill, Style::default().bg(Color::DarkGray));
-        buf.set_string(area.x, y, text, style);
+        buf.set_string(self.content_x(area), y, &fill, Style::default().bg(self.theme.sidebar_bg));
+        buf.set_string(self.content_x(area), y, text, style);
     }
 }
 
 /// Truncate a string to fit within a given width
 fn truncate_to_width(s: &str, max_width: usize) -> String {
-    if s.len() <= max_width {
+    if s.chars().count() <= max_width {
         s.to_string()
     } else if max_width >= 3 {
-        format!("{}...", &s[..max_width - 3])
+        let kept: String = s.chars().take(max_width - 3).collect();
+        format!("{}...", kept)
     } else {
         s.chars().take(max_width).collect()
     }
 }
 
+/// How long ago `since` was, as "5m", "3h" or "2d"; None under a minute
+fn idle_label(since: Instant) -> Option<String> {
+    let secs = since.elapsed().as_secs();
+    match secs {
+        0..60 => None,
+        60..3600 => Some(format!("{}m", secs / 60)),
+        3600..86400 => Some(format!("{}h", secs / 3600)),
+        _ => Some(format!("{}d", secs / 86400)),
+    }
+}
+
+/// Shorten a directory for display: its last component, or "~" for the home directory
+fn short_path(path: &str, home: Option<&std::path::Path>) -> String {
+    let path = std::path::Path::new(path);
+    if home == Some(path) {
+        return "~".to_string();
+    }
+    path.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| path.display().to_string())
+}
+
 /// Calculate which tab index was clicked given a row in the sidebar
 /// Returns None if the click was on the [+] button or outside tabs
 /// `header_rows` is the number of rows used by mode indicator (0 in normal mode, 1 in prefix/rename)
@@ -313,10 +571,203 @@ mod tests {
         assert_eq!(row_to_tab_index(4, 3, 10, 1), None); // No tab at row 4
     }
 
+    fn tab(index: usize, active: bool, marked: bool) -> TabInfo {
+        TabInfo {
+            id: format!("@{}", index),
+            name: format!("tab{}", index),
+            active,
+            activity: false,
+            bell: false,
+            marked,
+            auto_rename: false,
+            synchronized: false,
+            dead: false,
+            command: String::new(),
+            path: String::new(),
+            last_activity: None,
+            index,
+        }
+    }
+
+    fn row_text(buf: &Buffer, y: u16, width: u16) -> String {
+        (0..width).map(|x| buf[(x, y)].symbol().to_string()).collect()
+    }
+
+    #[test]
+    fn test_marked_tab_shows_glyph() {
+        let tabs = vec![tab(1, true, false), tab(2, false, true)];
+        let area = Rect::new(0, 0, 20, 5);
+        let mut buf = Buffer::empty(area);
+        Sidebar::new(&tabs, &Theme::default()).render(area, &mut buf);
+
+        assert!(!row_text(&buf, 0, 19).contains(MARK_GLYPH));
+        assert!(row_text(&buf, 1, 19).starts_with(&format!(" {}2: tab2", MARK_GLYPH)));
+    }
+
+    #[test]
+    fn test_active_row_highlight_spans_full_width() {
+        let tabs = vec![tab(1, false, false), tab(2, true, false)];
+        let area = Rect::new(0, 0, 20, 5);
+        let content_width = area.width - 1;
+        let mut buf = Buffer::empty(area);
+        Sidebar::new(&tabs, &Theme::default()).render(area, &mut buf);
+
+        // Every content cell of the active row carries the active background
+        for x in 0..content_width {
+            assert_eq!(buf[(x, 1)].bg, Color::Blue, "column {}", x);
+        }
+        // The border keeps its glyph but picks up the highlight
+        assert_eq!(buf[(content_width, 1)].symbol(), "│");
+        assert_eq!(buf[(content_width, 1)].bg, Color::Blue);
+        // Inactive rows keep the plain border
+        assert_eq!(buf[(content_width, 0)].bg, Color::DarkGray);
+    }
+
+    #[test]
+    fn test_border_faces_viewport_when_on_right() {
+        let tabs = vec![tab(1, true, false)];
+        let screen = Rect::new(0, 0, 30, 5);
+        let area = Rect::new(10, 0, 20, 5);
+        let mut buf = Buffer::empty(screen);
+        Sidebar::new(&tabs, &Theme::default()).on_right(true).render(area, &mut buf);
+
+        assert_eq!(buf[(10, 2)].symbol(), "│");
+        assert_eq!(buf[(29, 2)].symbol(), " ");
+        let row: String = (11..30).map(|x| buf[(x, 0)].symbol().to_string()).collect();
+        assert!(row.starts_with("● 1: tab1"), "{:?}", row);
+        // The active highlight still spans the border
+        assert_eq!(buf[(10, 0)].bg, Color::Blue);
+        assert_eq!(buf[(29, 0)].bg, Color::Blue);
+        // Nothing drawn left of the sidebar
+        assert_eq!(buf[(9, 0)].symbol(), " ");
+    }
+
     #[test]
     fn test_is_new_tab_button() {
         assert!(!is_new_tab_button(0, 10));
         assert!(!is_new_tab_button(8, 10));
         assert!(is_new_tab_button(9, 10));
     }
+
+    #[test]
+    fn test_short_path() {
+        let home = std::path::Path::new("/home/me");
+        assert_eq!(short_path("/home/me", Some(home)), "~");
+        assert_eq!(short_path("/home/me/src/helmux", Some(home)), "helmux");
+        assert_eq!(short_path("/", Some(home)), "/");
+    }
+
+    #[test]
+    fn test_path_shown_and_truncated() {
+        let mut tabs = vec![tab(1, true, false)];
+        tabs[0].path = "/srv/projects/a-long-directory-name".to_string();
+        let area = Rect::new(0, 0, 20, 3);
+        let mut buf = Buffer::empty(area);
+        Sidebar::new(&tabs, &Theme::default()).show_paths(true).render(area, &mut buf);
+
+        // "● 1: tab1" takes 9 columns, leaving 9 of the 19 for the path
+        assert_eq!(row_text(&buf, 0, 19), "● 1: tab1 a-long...");
+        assert!(buf[(10, 0)].modifier.contains(Modifier::DIM));
+
+        // Hidden unless enabled
+        let mut buf = Buffer::empty(area);
+        Sidebar::new(&tabs, &Theme::default()).render(area, &mut buf);
+        assert!(!row_text(&buf, 0, 19).contains("a-long"));
+    }
+
+    #[test]
+    fn test_command_annotation_shown_and_truncated() {
+        let mut tabs = vec![tab(1, true, false), tab(2, false, false)];
+        tabs[0].command = "vim".to_string();
+        tabs[1].command = "cargo-watch".to_string();
+        let area = Rect::new(0, 0, 16, 3);
+        let mut buf = Buffer::empty(area);
+        Sidebar::new(&tabs, &Theme::default()).render(area, &mut buf);
+
+        assert_eq!(row_text(&buf, 0, 15), "● 1: tab1 vim  ");
+        assert!(buf[(10, 0)].modifier.contains(Modifier::DIM));
+        // "  2: tab2" leaves 5 of the 15 columns for the command
+        assert_eq!(row_text(&buf, 1, 15), "  2: tab2 ca...");
+    }
+
+    #[test]
+    fn test_truncate_counts_characters() {
+        assert_eq!(truncate_to_width("● 1: café", 9), "● 1: café");
+        assert_eq!(truncate_to_width("● 1: cafés", 9), "● 1: c...");
+        // Cutting inside a multi-byte character used to panic
+        assert_eq!(truncate_to_width("●●●●●", 4), "●...");
+    }
+
+    #[test]
+    fn test_automatic_names_are_italic() {
+        let mut tabs = vec![tab(1, true, false), tab(2, false, false)];
+        tabs[1].auto_rename = true;
+        let area = Rect::new(0, 0, 20, 3);
+        let mut buf = Buffer::empty(area);
+        Sidebar::new(&tabs, &Theme::default()).render(area, &mut buf);
+
+        assert!(!buf[(5, 0)].modifier.contains(Modifier::ITALIC));
+        assert!(buf[(5, 1)].modifier.contains(Modifier::ITALIC));
+    }
+
+    #[test]
+    fn test_theme_colors() {
+        let tabs = vec![tab(1, true, false), tab(2, false, false)];
+        let theme = Theme { active_tab_bg: Color::Magenta, sidebar_bg: Color::Black, ..Theme::default() };
+        let area = Rect::new(0, 0, 20, 3);
+        let mut buf = Buffer::empty(area);
+        Sidebar::new(&tabs, &theme).render(area, &mut buf);
+
+        assert_eq!(buf[(0, 0)].bg, Color::Magenta);
+        assert_eq!(buf[(0, 1)].bg, Color::Black);
+        assert_eq!(buf[(19, 1)].fg, theme.border_fg);
+    }
+
+    #[test]
+    fn test_broadcast_indicator() {
+        let tabs = vec![tab(1, true, false)];
+        let area = Rect::new(0, 0, 20, 4);
+        let mut buf = Buffer::empty(area);
+        Sidebar::new(&tabs, &Theme::default()).broadcast(true).render(area, &mut buf);
+        assert!(row_text(&buf, 0, 19).starts_with("BROADCAST"));
+        assert!(row_text(&buf, 1, 19).starts_with("● 1: tab1"));
+    }
+
+    #[test]
+    fn test_synchronized_tab_annotation() {
+        let mut tabs = vec![tab(1, true, false), tab(2, false, false)];
+        tabs[1].synchronized = true;
+        let area = Rect::new(0, 0, 20, 3);
+        let mut buf = Buffer::empty(area);
+        Sidebar::new(&tabs, &Theme::default()).render(area, &mut buf);
+        assert!(!row_text(&buf, 0, 19).contains(SYNC_LABEL));
+        assert_eq!(row_text(&buf, 1, 19).trim_end(), "  2: tab2 [sync]");
+    }
+
+    #[test]
+    fn test_dead_tab_annotation() {
+        let mut tabs = vec![tab(1, true, false), tab(2, false, false)];
+        tabs[0].dead = true;
+        let area = Rect::new(0, 0, 20, 3);
+        let mut buf = Buffer::empty(area);
+        Sidebar::new(&tabs, &Theme::default()).render(area, &mut buf);
+        assert_eq!(row_text(&buf, 0, 19).trim_end(), "● 1: tab1 [exited]");
+        assert!(!row_text(&buf, 1, 19).contains(DEAD_LABEL));
+    }
+
+    #[test]
+    fn test_idle_annotation() {
+        let mut tabs = vec![tab(1, true, false), tab(2, false, false), tab(3, false, false)];
+        let now = Instant::now();
+        tabs[0].last_activity = Some(now);
+        tabs[1].last_activity = now.checked_sub(std::time::Duration::from_secs(5 * 60));
+        tabs[2].last_activity = now.checked_sub(std::time::Duration::from_secs(3 * 3600));
+        let area = Rect::new(0, 0, 20, 4);
+
+        let mut buf = Buffer::empty(area);
+        Sidebar::new(&tabs, &Theme::default()).render(area, &mut buf);
+        assert_eq!(row_text(&buf, 0, 19).trim_end(), "● 1: tab1");
+        assert_eq!(row_text(&buf, 1, 19).trim_end(), "  2: tab2 5m");
+        assert_eq!(row_text(&buf, 2, 19).trim_end(), "  3: tab3 3h");
+    }
 }
@@ -0,0 +1,31 @@
+use ratatui::{
+    buffer::Buffer,
+    layout::Rect,
+    style::{Color, Style},
+    widgets::Widget,
+};
+
+/// A message centered in the viewport while there's nothing to show yet,
+/// such as before tmux has sent its first window list
+pub struct Splash<'a> {
+    message: &'a str,
+}
+
+impl<'a> Splash<'a> {
+    pub fn new(message: &'a str) -> Self {
+        Self { message }
+    }
+}
+
+impl Widget for Splash<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        if area.width == 0 || area.height == 0 {
+            return;
+        }
+
+        let width = (self.message.chars().count() as u16).min(area.width);
+        let x = area.x + (area.width - width) / 2;
+        let y = area.y + area.height / 2;
+        buf.set_stringn(x, y, self.message, width as usize, Style::default().fg(Color::Gray));
+    }
+}
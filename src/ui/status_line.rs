@@ -0,0 +1,82 @@
+use ratatui::{
+    buffer::Buffer,
+    layout::Rect,
+    style::Style,
+    widgets::Widget,
+};
+
+use super::Theme;
+
+/// A one-line message bar drawn over the bottom of the viewport, with an
+/// optional indicator kept at its right end
+pub struct StatusLine<'a> {
+    message: &'a str,
+    indicator: Option<&'a str>,
+    theme: &'a Theme,
+}
+
+impl<'a> StatusLine<'a> {
+    pub fn new(message: &'a str, theme: &'a Theme) -> Self {
+        Self { message, indicator: None, theme }
+    }
+
+    /// Show a short state label at the right end, even when there's no message
+    pub fn indicator(mut self, indicator: Option<&'a str>) -> Self {
+        self.indicator = indicator;
+        self
+    }
+
+    /// Calculate the area for the status line (bottom row of the given area)
+    pub fn area(area: Rect) -> Rect {
+        if area.height == 0 {
+            return area;
+        }
+        Rect::new(area.x, area.y + area.height - 1, area.width, 1)
+    }
+}
+
+impl Widget for StatusLine<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        if area.width == 0 || area.height == 0 {
+            return;
+        }
+
+        let style = Style::default().fg(self.theme.status_fg).bg(self.theme.status_bg);
+        let indicator_width = self.indicator.map_or(0, |text| text.chars().count() as u16).min(area.width);
+        if !self.message.is_empty() {
+            let fill = " ".repeat(area.width as usize);
+            buf.set_string(area.x, area.y, &fill, style);
+            let room = area.width.saturating_sub(indicator_width);
+            buf.set_stringn(area.x, area.y, self.message, room as usize, style);
+        }
+        // Without a message only the indicator's own cells are covered
+        if let Some(text) = self.indicator {
+            let x = area.x + area.width - indicator_width;
+            buf.set_stringn(x, area.y, text, indicator_width as usize, style);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ratatui::style::Color;
+
+    fn row(buf: &Buffer) -> String {
+        (0..buf.area.width).map(|x| buf[(x, 0)].symbol()).collect()
+    }
+
+    #[test]
+    fn test_indicator_sits_at_right_end() {
+        let area = Rect::new(0, 0, 20, 1);
+        let mut buf = Buffer::empty(area);
+        StatusLine::new("saved", &Theme::default()).indicator(Some(" mouse on ")).render(area, &mut buf);
+        assert_eq!(row(&buf), "saved      mouse on ");
+
+        // With no message, the rest of the row is left alone
+        let mut buf = Buffer::empty(area);
+        StatusLine::new("", &Theme::default()).indicator(Some(" mouse off ")).render(area, &mut buf);
+        assert_eq!(row(&buf), "          mouse off ");
+        assert_eq!(buf[(0, 0)].bg, Color::Reset);
+    }
+}
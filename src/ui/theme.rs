@@ -0,0 +1,174 @@
+use std::str::FromStr;
+
+use ratatui::style::Color;
+use serde::{Deserialize, Deserializer};
+
+use crate::terminal::Palette;
+
+/// Colors for helmux's own chrome (sidebar and overlays), loaded from the
+/// `[appearance]` section of the config file
+/// Colors are names ("blue", "darkgray"), palette indexes ("33") or hex ("#1e1e2e");
+/// any left out keep the built-in defaults
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct Theme {
+    /// Sidebar background
+    #[serde(deserialize_with = "deserialize_color")]
+    pub sidebar_bg: Color,
+    /// Text of inactive tabs
+    #[serde(deserialize_with = "deserialize_color")]
+    pub sidebar_fg: Color,
+    /// Text of the active tab
+    #[serde(deserialize_with = "deserialize_color")]
+    pub active_tab_fg: Color,
+    /// Background of the active tab
+    #[serde(deserialize_with = "deserialize_color")]
+    pub active_tab_bg: Color,
+    /// Text of tabs with activity or a bell
+    #[serde(deserialize_with = "deserialize_color")]
+    pub activity_fg: Color,
+    /// Sidebar border line
+    #[serde(deserialize_with = "deserialize_color")]
+    pub border_fg: Color,
+    /// Border of the rename/find/search overlay
+    #[serde(deserialize_with = "deserialize_color")]
+    pub overlay_border_fg: Color,
+    /// The [+] button at the bottom of the sidebar
+    #[serde(deserialize_with = "deserialize_color")]
+    pub new_tab_button_fg: Color,
+    /// BROADCAST indicator at the top of the sidebar
+    #[serde(deserialize_with = "deserialize_color")]
+    pub broadcast_fg: Color,
+    #[serde(deserialize_with = "deserialize_color")]
+    pub broadcast_bg: Color,
+    /// NEW TAB indicator at the top of the sidebar while naming a new tab
+    #[serde(deserialize_with = "deserialize_color")]
+    pub new_tab_indicator_bg: Color,
+    /// Status line over the bottom of the viewport
+    #[serde(deserialize_with = "deserialize_color")]
+    pub status_fg: Color,
+    #[serde(deserialize_with = "deserialize_color")]
+    pub status_bg: Color,
+    /// Block cursor color; left out, the cursor cell is drawn in reverse video
+    #[serde(deserialize_with = "deserialize_optional_color")]
+    pub cursor_bg: Option<Color>,
+    /// Debug overlay
+    #[serde(deserialize_with = "deserialize_color")]
+    pub debug_fg: Color,
+    #[serde(deserialize_with = "deserialize_color")]
+    pub debug_bg: Color,
+    /// Viewport scrollbar thumb, and its track (also used for the scroll indicator)
+    #[serde(deserialize_with = "deserialize_color")]
+    pub scrollbar_thumb_fg: Color,
+    #[serde(deserialize_with = "deserialize_color")]
+    pub scrollbar_track_fg: Color,
+    /// Draw default-colored pane text white and brighten black/dark gray text,
+    /// as helmux used to, instead of leaving colors to the terminal's palette
+    pub legacy_color_fix: bool,
+    /// Exact RGB colors for ANSI indices 0-15 in pane output, as 16 hex strings
+    /// (black, red, green, yellow, blue, magenta, cyan, white, then the bright ones)
+    #[serde(deserialize_with = "deserialize_palette")]
+    pub palette: Option<Palette>,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            sidebar_bg: Color::DarkGray,
+            sidebar_fg: Color::White,
+            active_tab_fg: Color::White,
+            active_tab_bg: Color::Blue,
+            activity_fg: Color::Yellow,
+            border_fg: Color::Gray,
+            overlay_border_fg: Color::Cyan,
+            new_tab_button_fg: Color::Green,
+            broadcast_fg: Color::White,
+            broadcast_bg: Color::Red,
+            new_tab_indicator_bg: Color::Cyan,
+            status_fg: Color::Black,
+            status_bg: Color::Gray,
+            cursor_bg: None,
+            debug_fg: Color::White,
+            debug_bg: Color::DarkGray,
+            scrollbar_thumb_fg: Color::Gray,
+            scrollbar_track_fg: Color::DarkGray,
+            legacy_color_fix: false,
+            palette: None,
+        }
+    }
+}
+
+/// Parse a color the way ratatui spells them
+fn deserialize_color<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Color, D::Error> {
+    let value = String::deserialize(deserializer)?;
+    Color::from_str(&value)
+        .map_err(|_| serde::de::Error::custom(format!("unknown color \"{}\"", value)))
+}
+
+/// Parse a color for a setting whose default is no color at all
+fn deserialize_optional_color<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Color>, D::Error> {
+    deserialize_color(deserializer).map(Some)
+}
+
+/// Parse a palette of exactly 16 "#rrggbb" colors
+fn deserialize_palette<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Palette>, D::Error> {
+    let values = Vec::<String>::deserialize(deserializer)?;
+    let mut palette: Palette = [Color::Reset; 16];
+    if values.len() != palette.len() {
+        return Err(serde::de::Error::custom(format!(
+            "palette needs 16 colors, got {}",
+            values.len()
+        )));
+    }
+    for (slot, value) in palette.iter_mut().zip(&values) {
+        *slot = match Color::from_str(value) {
+            Ok(color @ Color::Rgb(..)) => color,
+            _ => return Err(serde::de::Error::custom(format!("palette color \"{}\" isn't #rrggbb", value))),
+        };
+    }
+    Ok(Some(palette))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_missing_fields_keep_defaults() {
+        let theme: Theme = toml::from_str("active_tab_bg = \"#1e66f5\"\nsidebar_bg = \"236\"").unwrap();
+        assert_eq!(theme.active_tab_bg, Color::Rgb(0x1e, 0x66, 0xf5));
+        assert_eq!(theme.sidebar_bg, Color::Indexed(236));
+        assert_eq!(theme.active_tab_fg, Theme::default().active_tab_fg);
+        assert_eq!(theme.overlay_border_fg, Color::Cyan);
+        assert_eq!(theme.status_bg, Color::Gray);
+        assert_eq!(theme.cursor_bg, None);
+    }
+
+    #[test]
+    fn test_chrome_colors() {
+        let theme: Theme = toml::from_str("cursor_bg = \"#ff8800\"\nbroadcast_bg = \"magenta\"").unwrap();
+        assert_eq!(theme.cursor_bg, Some(Color::Rgb(0xff, 0x88, 0x00)));
+        assert_eq!(theme.broadcast_bg, Color::Magenta);
+        assert_eq!(theme.broadcast_fg, Color::White);
+        assert!(toml::from_str::<Theme>("cursor_bg = \"blurple\"").is_err());
+    }
+
+    #[test]
+    fn test_unknown_color_is_an_error() {
+        assert!(toml::from_str::<Theme>("border_fg = \"blurple\"").is_err());
+    }
+
+    #[test]
+    fn test_palette() {
+        assert_eq!(Theme::default().palette, None);
+        let colors: Vec<String> = (0..16).map(|i| format!("\"#0000{:02x}\"", i)).collect();
+        let theme: Theme = toml::from_str(&format!("palette = [{}]", colors.join(", "))).unwrap();
+        let palette = theme.palette.unwrap();
+        assert_eq!(palette[1], Color::Rgb(0, 0, 1));
+        assert_eq!(palette[15], Color::Rgb(0, 0, 15));
+
+        assert!(toml::from_str::<Theme>("palette = [\"#000000\"]").is_err());
+        let named = vec!["\"red\""; 16].join(", ");
+        assert!(toml::from_str::<Theme>(&format!("palette = [{}]", named)).is_err());
+    }
+}
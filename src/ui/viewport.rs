@@ -5,12 +5,30 @@ use ratatui::{
     widgets::Widget,
 };
 
-use crate::terminal::{Cell, CellAttributes, TerminalBuffer};
+use std::collections::HashMap;
+use std::ops::Range;
+
+use super::{ColorDepth, Theme};
+use crate::app::Search;
+use crate::terminal::{Cell, TerminalBuffer};
 
 /// Widget that renders a terminal buffer to the screen
 pub struct Viewport<'a> {
     buffer: &'a TerminalBuffer,
     show_cursor: bool,
+    /// Whether this pane has keyboard focus; an unfocused cursor is drawn hollow
+    focused: bool,
+    search: Option<&'a Search>,
+    /// Brighten black/dark gray text and draw default text white, as older versions did
+    legacy_colors: bool,
+    /// What the host terminal can show; RGB colors are downsampled to fit
+    color_depth: ColorDepth,
+    /// Mark the corners when there's history above or below what's shown
+    scroll_indicator: bool,
+    /// Draw a scrollbar down the right edge while there's history
+    scrollbar: bool,
+    /// Colors for the cursor, scrollbar and scroll indicator
+    theme: Theme,
 }
 
 impl<'a> Viewport<'a> {
@@ -18,6 +36,13 @@ impl<'a> Viewport<'a> {
         Self {
             buffer,
             show_cursor: true,
+            focused: true,
+            search: None,
+            legacy_colors: false,
+            color_depth: ColorDepth::TrueColor,
+            scroll_indicator: false,
+            scrollbar: false,
+            theme: Theme::default(),
         }
     }
 
@@ -25,34 +50,185 @@ impl<'a> Viewport<'a> {
         self.show_cursor = show;
         self
     }
+
+    pub fn focused(mut self, focused: bool) -> Self {
+        self.focused = focused;
+        self
+    }
+
+    /// Highlight a scrollback search's matches
+    pub fn search(mut self, search: Option<&'a Search>) -> Self {
+        self.search = search;
+        self
+    }
+
+    /// Remap dark foreground colors the way helmux used to (see `legacy_color_fix`)
+    pub fn legacy_colors(mut self, legacy: bool) -> Self {
+        self.legacy_colors = legacy;
+        self
+    }
+
+    pub fn color_depth(mut self, depth: ColorDepth) -> Self {
+        self.color_depth = depth;
+        self
+    }
+
+    /// Draw ▲ in the top-right corner while scrollback is hidden above the view,
+    /// and ▼ in the bottom-right while scrolled back from the live screen
+    pub fn scroll_indicator(mut self, show: bool) -> Self {
+        self.scroll_indicator = show;
+        self
+    }
+
+    /// Draw a one-column scrollbar over the right edge showing where the view
+    /// sits in scrollback plus screen. Hidden while there's no scrollback
+    pub fn scrollbar(mut self, show: bool) -> Self {
+        self.scrollbar = show;
+        self
+    }
+
+    /// Use the configured cursor and scrollbar colors
+    pub fn theme(mut self, theme: &Theme) -> Self {
+        self.theme = *theme;
+        self
+    }
+
+    /// Draw the scrollbar's track and thumb down the right edge of `area`
+    fn render_scrollbar(&self, area: Rect, buf: &mut Buffer) {
+        let history = self.buffer.scrollback_len();
+        if area.is_empty() || history == 0 {
+            return;
+        }
+        let (_, height) = self.buffer.size();
+        let top = history - self.buffer.scroll_offset();
+        let thumb = scrollbar_thumb(history + height as usize, height as usize, top, area.height);
+        let x = area.right() - 1;
+        for y in 0..area.height {
+            let (symbol, color) = if thumb.contains(&y) {
+                ("┃", self.theme.scrollbar_thumb_fg)
+            } else {
+                ("│", self.theme.scrollbar_track_fg)
+            };
+            buf.set_string(x, area.y + y, symbol, Style::default().fg(color));
+        }
+    }
+
+    /// Draw the scroll indicator's arrows over the corners of `area`
+    fn render_scroll_indicator(&self, area: Rect, buf: &mut Buffer) {
+        if area.is_empty() {
+            return;
+        }
+        let style = Style::default().fg(self.theme.scrollbar_track_fg);
+        let x = area.right() - 1;
+        let offset = self.buffer.scroll_offset();
+        if self.buffer.scrollback_len() > offset {
+            buf.set_string(x, area.y, "▲", style);
+        }
+        if offset > 0 {
+            buf.set_string(x, area.bottom() - 1, "▼", style);
+        }
+    }
+
+    /// Where to draw the cursor (row, col), if anywhere: only for a pane showing its
+    /// cursor (not a background tab), while DECTCEM has it visible, on the live screen,
+    /// and inside the grid (which a resize may have shrunk)
+    fn cursor_cell(&self) -> Option<(u16, u16)> {
+        let (width, height) = self.buffer.size();
+        let (row, col) = self.buffer.cursor();
+        let drawn = self.show_cursor
+            && self.buffer.cursor_visible()
+            && !self.buffer.is_scrolled()
+            && row < height
+            && col < width;
+        drawn.then_some((row, col))
+    }
+
+    /// Cells covered by search matches, by (history row, column)
+    /// The value is true for cells of the current match
+    fn highlighted_cells(&self) -> HashMap<(usize, usize), bool> {
+        let mut cells = HashMap::new();
+        let Some(search) = self.search else {
+            return cells;
+        };
+        let (width, _) = self.buffer.size();
+        let len = search.query.chars().count();
+        for &(row, col) in &search.matches {
+            let current = (row, col) == search.current;
+            // Matches can continue onto the next row when the line wrapped
+            for i in 0..len {
+                let offset = col + i;
+                let cell = (row + offset / width as usize, offset % width as usize);
+                cells.insert(cell, current);
+            }
+        }
+        cells
+    }
 }
 
 impl Widget for Viewport<'_> {
     fn render(self, area: Rect, buf: &mut Buffer) {
-        let cells = self.buffer.cells();
-        let (cursor_row, cursor_col) = self.buffer.cursor();
+        ratatui::widgets::StatefulWidget::render(self, area, buf, &mut 0);
+    }
+}
 
-        // Render each cell from the terminal buffer
-        for (row_idx, row) in cells.iter().enumerate() {
-            if row_idx as u16 >= area.height {
+/// Rendered as a stateful widget, the state counts the grid cells written (a
+/// buffer smaller than the area leaves the rest alone)
+impl ratatui::widgets::StatefulWidget for Viewport<'_> {
+    type State = u64;
+
+    fn render(self, area: Rect, buf: &mut Buffer, cells_written: &mut u64) {
+        let (_, height) = self.buffer.size();
+        let cursor = self.cursor_cell();
+        let highlighted = self.highlighted_cells();
+
+        // Render each row as displayed (scrollback rows first when scrolled back)
+        for row_idx in 0..height.min(area.height) as usize {
+            let Some(row) = self.buffer.display_row(row_idx as u16) else {
                 break;
-            }
+            };
+
+            // Double-width (and double-height, approximated as double-width) lines
+            // space each character over two columns
+            let col_span = if self
+                .buffer
+                .display_line_size(row_idx as u16)
+                .is_double_width()
+            {
+                2
+            } else {
+                1
+            };
 
             for (col_idx, cell) in row.iter().enumerate() {
-                if col_idx as u16 >= area.width {
+                let x_offset = col_idx as u16 * col_span;
+                if x_offset >= area.width {
                     break;
                 }
 
-                let x = area.x + col_idx as u16;
+                let x = area.x + x_offset;
                 let y = area.y + row_idx as u16;
 
-                // Check if this is the cursor position
-                let is_cursor = self.show_cursor
-                    && self.buffer.cursor_visible()
-                    && row_idx as u16 == cursor_row
-                    && col_idx as u16 == cursor_col;
+                let is_cursor = cursor == Some((row_idx as u16, col_idx as u16));
 
-                let style = cell_to_style(cell, is_cursor);
+                let history_row = self.buffer.history_row(row_idx as u16);
+                // A focused cursor is a solid block (reverse video unless the theme
+                // gives it a color); an unfocused one just an underline
+                let block = is_cursor && self.focused;
+                let reversed = block && self.theme.cursor_bg.is_none();
+                let style = cell_to_style(cell, reversed, self.legacy_colors, self.color_depth);
+                let style = match self.theme.cursor_bg {
+                    Some(bg) if block => {
+                        let text = if cell.bg == Color::Reset { Color::Black } else { cell.bg };
+                        style.fg(self.color_depth.downsample(text)).bg(self.color_depth.downsample(bg))
+                    }
+                    _ if is_cursor && !self.focused => style.add_modifier(Modifier::UNDERLINED),
+                    _ => style,
+                };
+                let style = match highlighted.get(&(history_row, col_idx)) {
+                    Some(true) => style.fg(Color::Black).bg(Color::LightRed),
+                    Some(false) => style.fg(Color::Black).bg(Color::Yellow),
+                    None => style,
+                };
                 let ch = if cell.character.is_control() {
                     ' '
                 } else {
@@ -60,34 +236,55 @@ impl Widget for Viewport<'_> {
                 };
 
                 buf.set_string(x, y, ch.to_string(), style);
+                *cells_written += 1;
+                if col_span == 2 && x_offset + 1 < area.width {
+                    buf.set_string(x + 1, y, " ", cell_to_style(cell, false, self.legacy_colors, self.color_depth));
+                    *cells_written += 1;
+                }
             }
         }
+
+        if self.scrollbar {
+            self.render_scrollbar(area, buf);
+        } else if self.scroll_indicator {
+            self.render_scroll_indicator(area, buf);
+        }
     }
 }
 
+/// Rows of a `track`-row scrollbar the thumb covers, for a view of `visible`
+/// rows starting `top` rows into `total` rows of history and screen
+/// The thumb is at least one row, and reaches the bottom on the live screen
+fn scrollbar_thumb(total: usize, visible: usize, top: usize, track: u16) -> Range<u16> {
+    let track = track as usize;
+    if total == 0 || track == 0 {
+        return 0..0;
+    }
+    let len = (track * visible).div_ceil(total).clamp(1, track);
+    let start = if top + visible >= total {
+        track - len
+    } else {
+        (track * top / total).min(track - len)
+    };
+    start as u16..(start + len) as u16
+}
+
 /// Convert a terminal Cell to a ratatui Style
-fn cell_to_style(cell: &Cell, is_cursor: bool) -> Style {
+/// Colors pass through unchanged, so the user's terminal theme decides what they
+/// look like, unless `legacy` asks for the old dark-color remapping. RGB colors
+/// are brought down to what the host terminal can show
+fn cell_to_style(cell: &Cell, is_cursor: bool, legacy: bool, depth: ColorDepth) -> Style {
     let mut style = Style::default();
 
-    // Set foreground color - map dark colors to lighter variants for visibility
-    let fg = match cell.fg {
-        Color::Reset => Color::White,
-        Color::Black => Color::DarkGray,      // Make black visible
-        Color::DarkGray => Color::Gray,       // Make dark gray lighter
-        c => c,
-    };
-    style = style.fg(fg);
+    // Set foreground color - Reset uses terminal's default foreground
+    let fg = if legacy { legacy_fg(cell.fg) } else { cell.fg };
+    style = style.fg(depth.downsample(fg));
 
-    // Set background color - use terminal default for Reset
-    let bg = match cell.bg {
-        Color::Reset => Color::Reset,  // Use terminal's default background
-        c => c,
-    };
-    style = style.bg(bg);
+    // Set background color - Reset uses terminal's default background
+    style = style.bg(depth.downsample(cell.bg));
 
     // Apply attributes
-    let modifier = attrs_to_modifier(&cell.attrs);
-    style = style.add_modifier(modifier);
+    style = style.add_modifier(cell.attrs.to_modifier());
 
     // If this is the cursor, invert colors
     if is_cursor {
@@ -97,29 +294,234 @@ fn cell_to_style(cell: &Cell, is_cursor: bool) -> Style {
     style
 }
 
-/// Convert CellAttributes to ratatui Modifier
-fn attrs_to_modifier(attrs: &CellAttributes) -> Modifier {
-    let mut m = Modifier::empty();
-    if attrs.bold {
-        m |= Modifier::BOLD;
+/// Map dark foreground colors to lighter variants, for `legacy_color_fix`
+fn legacy_fg(color: Color) -> Color {
+    match color {
+        Color::Reset => Color::White,
+        Color::Black => Color::DarkGray,      // Make black visible
+        Color::DarkGray => Color::Gray,       // Make dark gray lighter
+        c => c,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(term: &TerminalBuffer) -> Buffer {
+        let (width, height) = term.size();
+        let area = Rect::new(0, 0, width, height);
+        let mut buf = Buffer::empty(area);
+        Viewport::new(term).show_cursor(false).render(area, &mut buf);
+        buf
+    }
+
+    fn row_text(buf: &Buffer, y: u16) -> String {
+        (0..buf.area.width)
+            .map(|x| buf[(x, y)].symbol())
+            .collect::<String>()
+    }
+
+    #[test]
+    fn test_double_width_line_is_spaced() {
+        let mut term = TerminalBuffer::new(10, 2);
+        term.process(b"\x1b#6Wide\r\nNarrow");
+        let buf = render(&term);
+        assert_eq!(row_text(&buf, 0), "W i d e   ");
+        assert_eq!(row_text(&buf, 1), "Narrow    ");
+    }
+
+    #[test]
+    fn test_counts_cells_written() {
+        let mut term = TerminalBuffer::new(4, 2);
+        term.process(b"\x1b#6ab");
+        let area = Rect::new(0, 0, 7, 3);
+        let mut buf = Buffer::empty(area);
+        let mut cells = 0;
+        ratatui::widgets::StatefulWidget::render(Viewport::new(&term), area, &mut buf, &mut cells);
+        // The double-width row is cut off at the area's edge; past the grid is untouched
+        assert_eq!(cells, 7 + 4);
+    }
+
+    #[test]
+    fn test_search_matches_are_highlighted() {
+        let mut term = TerminalBuffer::new(6, 2);
+        term.process(b"abc\r\nxabcab");
+        let search = Search {
+            query: "abc".to_string(),
+            matches: term.search("abc"),
+            current: (1, 1),
+        };
+        let area = Rect::new(0, 0, 6, 2);
+        let mut buf = Buffer::empty(area);
+        Viewport::new(&term)
+            .show_cursor(false)
+            .search(Some(&search))
+            .render(area, &mut buf);
+
+        assert_eq!(buf[(0, 0)].bg, Color::Yellow);
+        assert_eq!(buf[(2, 0)].bg, Color::Yellow);
+        assert_eq!(buf[(3, 0)].bg, Color::Reset);
+        assert_eq!(buf[(0, 1)].bg, Color::Reset);
+        assert_eq!(buf[(1, 1)].bg, Color::LightRed);
+        assert_eq!(buf[(3, 1)].bg, Color::LightRed);
+        assert_eq!(buf[(4, 1)].bg, Color::Reset);
     }
-    if attrs.italic {
-        m |= Modifier::ITALIC;
+
+    #[test]
+    fn test_scroll_indicator_follows_offset() {
+        let mut term = TerminalBuffer::new(6, 3);
+        let area = Rect::new(0, 0, 6, 3);
+        let corners = |term: &TerminalBuffer| {
+            let mut buf = Buffer::empty(area);
+            Viewport::new(term).show_cursor(false).scroll_indicator(true).render(area, &mut buf);
+            (buf[(5, 0)].symbol().to_string(), buf[(5, 2)].symbol().to_string())
+        };
+
+        // No history, nothing to point at
+        term.process(b"one");
+        assert_eq!(corners(&term), (" ".to_string(), " ".to_string()));
+
+        // History above the live screen
+        term.process(b"\r\ntwo\r\nthree\r\nfour\r\nfive");
+        assert_eq!(corners(&term), ("▲".to_string(), " ".to_string()));
+
+        // Scrolled part way, there's history both ways
+        term.scroll_view_up(1);
+        assert_eq!(corners(&term), ("▲".to_string(), "▼".to_string()));
+
+        // At the top of history, only below
+        term.scroll_view_up(10);
+        assert_eq!(corners(&term), (" ".to_string(), "▼".to_string()));
+
+        // Off unless asked for
+        assert_eq!(row_text(&render(&term), 0), "one   ");
     }
-    if attrs.underline {
-        m |= Modifier::UNDERLINED;
+
+    #[test]
+    fn test_scrollbar_thumb() {
+        // 80 lines of history over a 20-row screen, on a 20-row track
+        assert_eq!(scrollbar_thumb(100, 20, 80, 20), 16..20);
+        assert_eq!(scrollbar_thumb(100, 20, 0, 20), 0..4);
+        assert_eq!(scrollbar_thumb(100, 20, 40, 20), 8..12);
+        // Long history still leaves a visible thumb
+        assert_eq!(scrollbar_thumb(10_000, 20, 0, 20), 0..1);
+        assert_eq!(scrollbar_thumb(10_000, 20, 5_000, 20), 10..11);
+        assert_eq!(scrollbar_thumb(10_000, 20, 9_980, 20), 19..20);
+        // Nothing to scroll: the thumb fills the track
+        assert_eq!(scrollbar_thumb(20, 20, 0, 20), 0..20);
     }
-    if attrs.blink {
-        m |= Modifier::SLOW_BLINK;
+
+    #[test]
+    fn test_scrollbar_tracks_offset() {
+        let mut term = TerminalBuffer::new(6, 4);
+        let area = Rect::new(0, 0, 6, 4);
+        let column = |term: &TerminalBuffer| {
+            let mut buf = Buffer::empty(area);
+            Viewport::new(term).show_cursor(false).scrollbar(true).render(area, &mut buf);
+            (0..4).map(|y| buf[(5, y)].symbol().to_string()).collect::<String>()
+        };
+
+        // Hidden with no history
+        term.process(b"one");
+        assert_eq!(column(&term), "    ");
+
+        // 4 lines of history over a 4-row screen: half the track, at the bottom
+        term.process(b"\r\n2\r\n3\r\n4\r\n5\r\n6\r\n7\r\n8");
+        assert_eq!(column(&term), "││┃┃");
+        term.scroll_view_up(4);
+        assert_eq!(column(&term), "┃┃││");
     }
-    if attrs.reverse {
-        m |= Modifier::REVERSED;
+
+    #[test]
+    fn test_double_width_line_is_cut_at_area_edge() {
+        let mut term = TerminalBuffer::new(6, 1);
+        term.process(b"\x1b#3abcdef");
+        let buf = render(&term);
+        assert_eq!(row_text(&buf, 0), "a b c ");
     }
-    if attrs.hidden {
-        m |= Modifier::HIDDEN;
+
+    #[test]
+    fn test_colors_pass_through() {
+        let mut cell = Cell { fg: Color::Black, ..Cell::default() };
+        assert_eq!(cell_to_style(&cell, false, false, ColorDepth::TrueColor).fg, Some(Color::Black));
+        assert_eq!(cell_to_style(&cell, false, true, ColorDepth::TrueColor).fg, Some(Color::DarkGray));
+
+        cell.fg = Color::Reset;
+        assert_eq!(cell_to_style(&cell, false, false, ColorDepth::TrueColor).fg, Some(Color::Reset));
+        assert_eq!(cell_to_style(&cell, false, true, ColorDepth::TrueColor).fg, Some(Color::White));
     }
-    if attrs.strikethrough {
-        m |= Modifier::CROSSED_OUT;
+
+    #[test]
+    fn test_rgb_downsampled_for_limited_terminals() {
+        let cell = Cell { fg: Color::Rgb(255, 0, 0), bg: Color::Rgb(0, 0, 255), ..Cell::default() };
+        let style = cell_to_style(&cell, false, false, ColorDepth::Ansi256);
+        assert_eq!(style.fg, Some(Color::Indexed(196)));
+        assert_eq!(style.bg, Some(Color::Indexed(21)));
+        let style = cell_to_style(&cell, false, false, ColorDepth::TrueColor);
+        assert_eq!(style.fg, Some(Color::Rgb(255, 0, 0)));
+    }
+
+    #[test]
+    fn test_unfocused_cursor_is_hollow() {
+        let mut term = TerminalBuffer::new(4, 1);
+        term.process(b"ab");
+        let area = Rect::new(0, 0, 4, 1);
+
+        let mut buf = Buffer::empty(area);
+        Viewport::new(&term).render(area, &mut buf);
+        assert!(buf[(2, 0)].modifier.contains(Modifier::REVERSED));
+
+        let mut buf = Buffer::empty(area);
+        Viewport::new(&term).focused(false).render(area, &mut buf);
+        assert!(!buf[(2, 0)].modifier.contains(Modifier::REVERSED));
+        assert!(buf[(2, 0)].modifier.contains(Modifier::UNDERLINED));
+        assert!(!buf[(1, 0)].modifier.contains(Modifier::UNDERLINED));
+    }
+
+    #[test]
+    fn test_themed_cursor_color() {
+        let mut term = TerminalBuffer::new(4, 1);
+        term.process(b"ab");
+        let area = Rect::new(0, 0, 4, 1);
+        let theme = Theme { cursor_bg: Some(Color::Rgb(255, 136, 0)), ..Theme::default() };
+
+        let mut buf = Buffer::empty(area);
+        Viewport::new(&term).theme(&theme).render(area, &mut buf);
+        assert!(!buf[(2, 0)].modifier.contains(Modifier::REVERSED));
+        assert_eq!(buf[(2, 0)].bg, Color::Rgb(255, 136, 0));
+        assert_eq!(buf[(2, 0)].fg, Color::Black);
+        assert_eq!(buf[(1, 0)].bg, Color::Reset);
+    }
+
+    #[test]
+    fn test_cursor_only_drawn_where_shown() {
+        let cursor_cells = |viewport: Viewport, area: Rect| {
+            let mut buf = Buffer::empty(area);
+            viewport.render(area, &mut buf);
+            buf.content()
+                .iter()
+                .filter(|c| c.modifier.intersects(Modifier::REVERSED | Modifier::UNDERLINED))
+                .count()
+        };
+        let mut term = TerminalBuffer::new(6, 3);
+        term.process(b"one\r\ntwo");
+        let area = Rect::new(0, 0, 6, 3);
+
+        assert_eq!(cursor_cells(Viewport::new(&term), area), 1);
+        // A background tab draws none, nor does a pane that hid its cursor
+        assert_eq!(cursor_cells(Viewport::new(&term).show_cursor(false), area), 0);
+        term.process(b"\x1b[?25l");
+        assert_eq!(cursor_cells(Viewport::new(&term), area), 0);
+        term.process(b"\x1b[?25h");
+
+        // After shrinking, the cursor is clamped into the grid and still drawn there
+        term.process(b"\x1b[3;6H");
+        term.resize(4, 2);
+        assert_eq!(term.cursor(), (1, 3));
+        let area = Rect::new(0, 0, 4, 2);
+        let mut buf = Buffer::empty(area);
+        Viewport::new(&term).render(area, &mut buf);
+        assert!(buf[(3, 1)].modifier.contains(Modifier::REVERSED));
     }
-    m
 }
@@ -0,0 +1,155 @@
+// Integration tests against a real tmux server in control mode.
+// Each test uses its own throwaway session on a private socket directory and
+// is skipped when tmux isn't installed.
+
+use std::process::Command;
+use std::sync::Once;
+use std::time::Duration;
+
+use helmux::app::WindowListEntry;
+use helmux::tmux::{Commands, TmuxConnection, TmuxEvent, TmuxTarget};
+
+const EVENT_TIMEOUT: Duration = Duration::from_secs(5);
+
+static ISOLATE: Once = Once::new();
+
+/// Point tmux at a private socket directory so tests never touch a real server
+fn isolate_tmux() {
+    ISOLATE.call_once(|| {
+        let dir = std::env::temp_dir().join(format!("helmux-tests-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::env::set_var("TMUX_TMPDIR", &dir);
+        std::env::remove_var("TMUX");
+    });
+}
+
+/// A control mode connection to a throwaway session, killed on drop
+struct TestSession {
+    name: String,
+    conn: TmuxConnection,
+}
+
+impl TestSession {
+    /// Connect to a fresh session, or None if tmux isn't installed
+    async fn start(name: &str) -> Option<Self> {
+        if TmuxConnection::check_installed().is_err() {
+            eprintln!("tmux not installed, skipping");
+            return None;
+        }
+        isolate_tmux();
+        let name = format!("helmux-test-{}-{}", name, std::process::id());
+        let conn = TmuxConnection::connect(&name).await.expect("connect to tmux");
+        let mut session = Self { name, conn };
+        // Commands without a target fail until the client is attached to the session
+        session
+            .wait_for(|event| matches!(event, TmuxEvent::SessionChanged { .. }).then_some(()))
+            .await;
+        Some(session)
+    }
+
+    /// Read events until one matches, failing the test on timeout
+    async fn wait_for<T>(&mut self, mut matches: impl FnMut(&TmuxEvent) -> Option<T>) -> T {
+        let wait = async {
+            loop {
+                let event = self.conn.next_event().await.expect("tmux event");
+                if let Some(found) = matches(&event) {
+                    return found;
+                }
+            }
+        };
+        tokio::time::timeout(EVENT_TIMEOUT, wait)
+            .await
+            .expect("timed out waiting for tmux event")
+    }
+
+    /// Send a command and wait for its response
+    async fn command(&mut self, cmd: &str) -> String {
+        let sent = self.conn.send_command(cmd).await.expect("send command");
+        self.wait_for(|event| match event {
+            TmuxEvent::CommandResponse { id, data } if *id == sent => Some(data.clone()),
+            TmuxEvent::CommandError { id, message } if *id == sent => {
+                panic!("{} failed: {}", cmd, message)
+            }
+            _ => None,
+        })
+        .await
+    }
+}
+
+impl Drop for TestSession {
+    fn drop(&mut self) {
+        let _ = Command::new("tmux")
+            .args(["kill-session", "-t", &self.name])
+            .output();
+    }
+}
+
+#[tokio::test]
+async fn window_lifecycle() {
+    let Some(mut session) = TestSession::start("lifecycle").await else {
+        return;
+    };
+
+    let window_id = session
+        .command(&format!("{} -P -F '#{{window_id}}'", Commands::new_window(Some("first"))))
+        .await;
+    assert!(window_id.starts_with('@'), "unexpected window id {:?}", window_id);
+    // The connect-time version query is answered before any later command
+    assert!(session.conn.tmux_version().is_some());
+
+    let list = session.command(&Commands::list_windows()).await;
+    let entry = list
+        .lines()
+        .filter_map(WindowListEntry::parse)
+        .find(|entry| entry.window_id == window_id)
+        .unwrap_or_else(|| panic!("new window not in {:?}", list));
+    assert_eq!(entry.name, "first");
+    assert!(entry.active);
+    assert!(entry.pane_id.starts_with('%'));
+    assert!(!entry.path.is_empty());
+
+    let target = TmuxTarget::new(&window_id).unwrap();
+    session.conn.send_command(&Commands::rename_window(target, "my: tab")).await.unwrap();
+    let name = session
+        .wait_for(|event| match event {
+            TmuxEvent::WindowRenamed { window_id: id, name } if *id == window_id => {
+                Some(name.clone())
+            }
+            _ => None,
+        })
+        .await;
+    assert_eq!(name, "my: tab");
+
+    session.conn.send_command(&Commands::kill_window(target)).await.unwrap();
+    session
+        .wait_for(|event| match event {
+            TmuxEvent::WindowClose { window_id: id } if *id == window_id => Some(()),
+            _ => None,
+        })
+        .await;
+}
+
+#[tokio::test]
+async fn output_roundtrip() {
+    let Some(mut session) = TestSession::start("output").await else {
+        return;
+    };
+
+    // Bytes tmux has to escape: ESC, tab, backslash and CR/LF
+    let pane_id = session
+        .command("new-window -P -F '#{pane_id}' \"printf 'a\\\\033[1mb\\\\tc\\\\\\\\d\\\\n'; sleep 5\"")
+        .await;
+
+    let mut output = Vec::new();
+    let expected = b"a\x1b[1mb\tc\\d\r\n";
+    session
+        .wait_for(|event| {
+            if let TmuxEvent::Output { pane_id: id, data } = event {
+                if *id == pane_id {
+                    output.extend_from_slice(data);
+                }
+            }
+            output.windows(expected.len()).any(|w| w == expected).then_some(())
+        })
+        .await;
+}